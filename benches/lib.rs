@@ -37,21 +37,21 @@ fn gen_simple_row<R: rand::Rng>(rng: &mut R) -> String {
     gen_row![
         "\t";
         generate_integer(rng, 0, 100000),
-        generate_gauss(rng, 10000, 1000),
-        generate_gauss_f32(rng, 10000.0, 1000.0),
+        generate_gauss(rng, 10000, 1000, None, None),
+        generate_gauss_f32(rng, 10000.0, 1000.0, None, None),
         generate_string(rng, 64),
         generate_date(rng),
-        generate_choice(rng, &choices, 2, 2)
+        generate_choice(rng, &choices, 1)
     ]
 }
 
 fn gen_complex_row<R: rand::Rng>(rng: &mut R) -> String {
     gen_row![
         "\t";
-        generate_gauss(rng, 4000, 1000),
-        generate_gauss_f32(rng, 4000.0, 1000.0),
+        generate_gauss(rng, 4000, 1000, None, None),
+        generate_gauss_f32(rng, 4000.0, 1000.0, None, None),
         generate_integer(rng, 0, 1000000),
-        generate_choice(rng, &choices, 2, 2),
+        generate_choice(rng, &choices, 1),
         generate_string(rng, 32),
         generate_integer(rng, 0, 1000000),
         generate_integer(rng, 0, 1000000),
@@ -59,45 +59,45 @@ fn gen_complex_row<R: rand::Rng>(rng: &mut R) -> String {
         generate_integer(rng, 0, 1000000),
         generate_string(rng, 32),
         generate_date(rng),
-        generate_choice(rng, &choices, 2, 48),
+        generate_choice(rng, &choices, 24),
         generate_date(rng),
-        generate_gauss(rng, 4000, 1000),
+        generate_gauss(rng, 4000, 1000, None, None),
         generate_date(rng),
         generate_integer(rng, 0, 1000000),
-        generate_choice(rng, &choices, 2, 2),
+        generate_choice(rng, &choices, 1),
         generate_integer(rng, 0, 1000000),
-        generate_gauss(rng, 4000, 1000),
-        generate_gauss(rng, 4000, 1000),
+        generate_gauss(rng, 4000, 1000, None, None),
+        generate_gauss(rng, 4000, 1000, None, None),
         generate_date(rng),
         generate_integer(rng, 0, 1000000),
         generate_date(rng),
         generate_date(rng),
         generate_date(rng),
-        generate_choice(rng, &choices, 2, 2),
+        generate_choice(rng, &choices, 1),
         generate_string(rng, 32),
-        generate_choice(rng, &choices, 2, 2),
-        generate_choice(rng, &choices, 2, 2),
-        generate_choice(rng, &choices, 2, 64),
+        generate_choice(rng, &choices, 1),
+        generate_choice(rng, &choices, 1),
+        generate_choice(rng, &choices, 32),
         generate_integer(rng, 0, 1000000),
         generate_integer(rng, 0, 1000000),
         generate_integer(rng, 0, 1000000),
-        generate_choice(rng, &choices, 2, 2),
+        generate_choice(rng, &choices, 1),
         generate_string(rng, 32),
         generate_integer(rng, 0, 1000000),
         generate_integer(rng, 0, 1000000),
         generate_string(rng, 32),
         generate_string(rng, 64),
-        generate_choice(rng, &choices, 2, 2),
-        generate_choice(rng, &choices, 2, 2),
+        generate_choice(rng, &choices, 1),
+        generate_choice(rng, &choices, 1),
         generate_string(rng, 32),
         generate_date(rng),
         generate_string(rng, 32),
         generate_date(rng),
         generate_string(rng, 32),
-        generate_choice(rng, &choices, 2, 2),
+        generate_choice(rng, &choices, 1),
         generate_string(rng, 64),
-        generate_choice(rng, &choices, 2, 2),
-        generate_choice(rng, &choices, 2, 2),
+        generate_choice(rng, &choices, 1),
+        generate_choice(rng, &choices, 1),
         generate_date(rng),
         generate_string(rng, 128)
     ]
@@ -118,13 +118,13 @@ fn bench_generate_string(b: &mut Bencher) {
 #[bench]
 fn bench_generate_gauss(b: &mut Bencher) {
     let mut rng = rand::thread_rng();
-    b.iter(|| { generate_gauss(&mut rng, 100, 20).to_string(); });
+    b.iter(|| { generate_gauss(&mut rng, 100, 20, None, None).to_string(); });
 }
 
 #[bench]
 fn bench_generate_gauss_f32(b: &mut Bencher) {
     let mut rng = rand::thread_rng();
-    b.iter(|| { generate_gauss_f32(&mut rng, 100.0, 20.0).to_string(); });
+    b.iter(|| { generate_gauss_f32(&mut rng, 100.0, 20.0, None, None).to_string(); });
 }
 
 #[bench]
@@ -137,7 +137,7 @@ fn bench_generate_date(b: &mut Bencher) {
 fn bench_generate_choice(b: &mut Bencher) {
     let mut rng = rand::thread_rng();
     let ex_choices = vec!["X", "A", "H", "B", "C", "D", "E", "F", "G"];
-    b.iter(|| { generate_choice(&mut rng, ex_choices.as_slice(), 2, 2).to_string(); });
+    b.iter(|| { generate_choice(&mut rng, ex_choices.as_slice(), 1).to_string(); });
 }
 
 #[bench]
@@ -200,6 +200,15 @@ fn bench_generate_complex_row_from_file(b: &mut Bencher) {
     });
 }
 
+#[bench]
+fn bench_generate_complex_row_from_schema(b: &mut Bencher) {
+    // Row generation only, without file I/O, to measure the per-row overhead of the schema
+    let schema = load_schema_from_file("benches/complex.json").ok().unwrap();
+    let mut rng = rand::thread_rng();
+
+    b.iter(|| schema.generate_row(&mut rng).unwrap());
+}
+
 #[bench]
 fn bench_generate_1000_complex_rows_from_file(b: &mut Bencher) {
     let schema = load_schema_from_file("benches/complex.json").ok().unwrap();
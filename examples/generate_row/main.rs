@@ -12,14 +12,14 @@ fn main() {
 
     let row = gen_row![
         ", ";
-        generate_gauss(&mut rng, 1000, 300),
+        generate_gauss(&mut rng, 1000, 300, None, None),
         generate_date(&mut rng),
-        generate_gauss(&mut rng, 1000, 100),
-        generate_gauss(&mut rng, 4000, 1000),
+        generate_gauss(&mut rng, 1000, 100, None, None),
+        generate_gauss(&mut rng, 4000, 1000, None, None),
         generate_integer(&mut rng, 0, 100000),
         generate_string(&mut rng, 10),
         generate_date(&mut rng),
-        generate_choice(&mut rng, &CHOICES, 1, 10),
+        generate_choice(&mut rng, &CHOICES, 10),
         generate_date(&mut rng),
         generate_integer(&mut rng, 0, 100),
         generate_integer(&mut rng, 0, 1000000),
@@ -16,7 +16,7 @@ fn main() {
     println!("Random string: {}", my_string);
 
     // Generate a random integer from a Gaussian distribution
-    let my_gaussian = generate_gauss(&mut rng, 10, 2);
+    let my_gaussian = generate_gauss(&mut rng, 10, 2, None, None);
     println!("Random gaussian: {}", my_gaussian);
 
     // Generate a date
@@ -25,6 +25,6 @@ fn main() {
 
     // Generate a choice from an array
     let my_vector = vec!["01", "02"];
-    let my_choice = generate_choice(&mut rng, &my_vector, 2, 2);
+    let my_choice = generate_choice(&mut rng, &my_vector, 1);
     println!("Random choice from {:?}: {}", my_vector, my_choice);
 }
@@ -1,16 +1,24 @@
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Read};
+use std::thread;
+use std::env;
 
 use log::LogLevelFilter;
 use getopts::Options;
 
 use logger::init_logger;
+use crypto::parse_key;
+use util::{validate_output_template, parse_s3_region, parse_content_type, parse_kafka_location,
+           DEDUPE_MAX_RETRIES};
+use sql::{SqlDialect, parse_dialect};
 
 use reqwest;
 
 const NUM_ROWS_DEFAULT: u64 = 1000;
 const BATCH_SIZE_DEFAULT: u64 = 1;
 const MAX_THREADS: u64 = 128;
+const CHANNEL_CAPACITY_DEFAULT: usize = 64;
+const HTTP_RETRIES_DEFAULT: u32 = 5;
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum OutputMode {
@@ -18,7 +26,20 @@ pub enum OutputMode {
     Stdout,
     File,
     PostgreSQL,
-    S3
+    S3,
+    Kafka,
+    SqlFile,
+    Http
+}
+
+/// How each row is written, independent of where the output goes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OutputFormat {
+    /// Fields joined by the schema's delimiter, or fixed width
+    Delimited,
+    /// One JSON object per line, keyed by field name, with a space after each ':' and ','
+    /// when `spaces` is set
+    JsonLines { spaces: bool }
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -28,34 +49,338 @@ pub enum LogType {
 }
 
 pub struct Config {
+    /// Number of data rows to generate; the header, when displayed, is not counted
     pub num_rows: u64,
     pub batch_size: u64,
+    /// Percentage by which each batch's size randomly varies around batch_size
+    pub batch_jitter: f64,
+    /// Most batches queued for the output thread before generator threads wait for it
+    pub channel_capacity: usize,
     pub log_type: LogType,
+    /// The maximum number of threads; with auto_threads, the run starts with one and scales up
     pub num_threads: u64,
+    pub auto_threads: bool,
+    /// Seeds every random number generator, making a run reproducible whatever the number
+    /// of threads
+    pub seed: Option<u64>,
     pub output_mode: OutputMode,
-    pub input_file: String,
+    pub output_format: OutputFormat,
+    /// Written after each row in place of the schema's record_separator, when set
+    pub line_terminator: Option<String>,
+    /// Each input schema's source (path or URL) and contents
+    pub input_files: Vec<(String, String)>,
+    /// Report every bad schema instead of stopping at the first, and generate the good ones
+    pub collect_errors: bool,
+    /// Each input that could not be read with collect_errors, as "source: error"
+    pub input_errors: Vec<String>,
     pub output_file: Option<String>,
-    pub display_header: bool
+    pub display_header: bool,
+    pub kafka_brokers: Option<String>,
+    pub kafka_topic: Option<String>,
+    pub kafka_key: Option<String>,
+    /// Publish each chunk of rows received as a single Kafka message, rather than each row
+    pub kafka_batches: bool,
+    /// AWS region of the S3 bucket, e.g. "eu-west-1"; us-east-1 when not set
+    pub s3_region: Option<String>,
+    /// URL of an S3-compatible store, such as MinIO or LocalStack, to upload to instead of AWS
+    pub s3_endpoint: Option<String>,
+    /// Endpoint each batch is POSTed to, for http output
+    pub http_url: Option<String>,
+    /// Content-Type of the batches POSTed for http output
+    pub http_content_type: String,
+    /// How many times a batch that failed with a server error is POSTed again before
+    /// giving up
+    pub http_retries: u32,
+    /// PostgreSQL connection string, e.g. "postgresql://user@localhost/db", for postgresql output
+    pub connection_string: Option<String>,
+    pub from_table: Option<String>,
+    pub dedupe_rows: bool,
+    /// Periodically log how many rows have been generated, the throughput and an ETA
+    pub progress: bool,
+    pub display_ddl: bool,
+    pub encrypt_key: Option<Vec<u8>>,
+    pub output_template: Option<String>,
+    pub dialect: SqlDialect,
+    /// How many times a duplicate row or unique value is regenerated before giving up
+    pub unique_max_retries: u64,
+    pub dry_run: bool,
+    /// Only check that the schemas are valid and summarize them, without generating data
+    pub validate: bool,
+    /// Number of rows to sample from the run and print to stderr at the end
+    pub sample: Option<usize>,
+    /// Shell command run after each output file is finished, with {file} replaced by its path
+    pub on_complete: Option<String>,
+    /// Start a new, numbered output file after this many rows, for file output
+    pub max_rows_per_file: Option<u64>,
+    /// Fields to include in the dry run's cardinality report, all of them when empty
+    pub count_fields: Vec<String>,
+    /// Port to serve schema validation on instead of generating data
+    pub serve: Option<u16>,
+    /// Field whose value picks the output file each row is written to
+    pub partition_by: Option<String>
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            num_rows: NUM_ROWS_DEFAULT,
+            batch_size: BATCH_SIZE_DEFAULT,
+            batch_jitter: 0.0,
+            channel_capacity: CHANNEL_CAPACITY_DEFAULT,
+            log_type: LogType::Console,
+            num_threads: 1,
+            auto_threads: false,
+            seed: None,
+            output_mode: OutputMode::Stdout,
+            output_format: OutputFormat::Delimited,
+            line_terminator: None,
+            input_files: Vec::new(),
+            collect_errors: false,
+            input_errors: Vec::new(),
+            output_file: None,
+            display_header: false,
+            kafka_brokers: None,
+            kafka_topic: None,
+            kafka_key: None,
+            kafka_batches: false,
+            s3_region: None,
+            s3_endpoint: None,
+            http_url: None,
+            http_content_type: "text/plain".to_string(),
+            http_retries: HTTP_RETRIES_DEFAULT,
+            connection_string: None,
+            from_table: None,
+            dedupe_rows: false,
+            progress: false,
+            display_ddl: false,
+            encrypt_key: None,
+            output_template: None,
+            dialect: SqlDialect::Postgres,
+            unique_max_retries: DEDUPE_MAX_RETRIES,
+            dry_run: false,
+            validate: false,
+            sample: None,
+            on_complete: None,
+            max_rows_per_file: None,
+            count_fields: Vec::new(),
+            serve: None,
+            partition_by: None
+        }
+    }
+}
+
+/// Counts the batches needed for `num_rows` rows, including a final partial batch for any
+/// rows left over.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::config::batch_count;
+///
+/// # fn main() {
+/// assert_eq!(batch_count(1000, 100), 10);
+/// assert_eq!(batch_count(1000, 3), 334);
+/// assert_eq!(batch_count(0, 100), 0);
+/// # }
+/// ```
+pub fn batch_count(num_rows: u64, batch_size: u64) -> u64 {
+    num_rows / batch_size + if num_rows % batch_size > 0 { 1 } else { 0 }
+}
+
+/// Parses a `--line_terminator`: "lf", "crlf", or a sequence of characters in which
+/// `\n`, `\r`, `\t` and `\\` are unescaped.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::config::parse_line_terminator;
+///
+/// # fn main() {
+/// assert_eq!(parse_line_terminator("lf").unwrap(), "\n");
+/// assert_eq!(parse_line_terminator("crlf").unwrap(), "\r\n");
+/// assert_eq!(parse_line_terminator("\\r\\n").unwrap(), "\r\n");
+/// assert_eq!(parse_line_terminator("~\\n").unwrap(), "~\n");
+/// assert!(parse_line_terminator("").is_err());
+/// assert!(parse_line_terminator("\\x").is_err());
+/// # }
+/// ```
+pub fn parse_line_terminator(value: &str) -> Result<String, String> {
+    match value {
+        "lf" => return Ok("\n".to_string()),
+        "crlf" => return Ok("\r\n".to_string()),
+        "" => return Err("--line_terminator must not be empty.".to_string()),
+        _ => {}
+    }
+
+    let mut terminator = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            terminator.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => terminator.push('\n'),
+            Some('r') => terminator.push('\r'),
+            Some('t') => terminator.push('\t'),
+            Some('\\') => terminator.push('\\'),
+            _ => return Err(format!("Unsupported escape in --line_terminator {}, expected \
+                                     \\n, \\r, \\t or \\\\.", value))
+        }
+    }
+    Ok(terminator)
+}
+
+/// Caps the number of threads to the number of batches, since extra threads would have no
+/// work to do.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::config::effective_thread_count;
+///
+/// # fn main() {
+/// assert_eq!(effective_thread_count(8, 2), 2);
+/// assert_eq!(effective_thread_count(4, 100), 4);
+/// assert_eq!(effective_thread_count(4, 0), 1);
+/// # }
+/// ```
+pub fn effective_thread_count(num_threads: u64, num_batches: u64) -> u64 {
+    if num_threads > num_batches {
+        num_batches.max(1)
+    } else {
+        num_threads
+    }
+}
+
+/// Reads an input schema from a local path, an http(s) URL, or stdin when given "-"
+fn read_input_file(input_file_uri: &str) -> Result<String, String> {
+    if input_file_uri == "-" {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)
+            .map_err(|e| format!("Could not read the schema from stdin: {}", e))?;
+        if content.trim().is_empty() {
+            return Err("The schema read from stdin is empty.".to_string());
+        }
+        Ok(content)
+    } else if input_file_uri.starts_with("http") {
+        let mut response = reqwest::get(input_file_uri);
+        let mut content = String::new();
+
+        match response {
+            Ok(mut response) => {
+                info!("{:?}", response);
+                response.read_to_string(&mut content)
+                    .map_err(|e| format!("Could not read input file {}: {}", input_file_uri, e))?;
+                if !response.status().is_success() {
+                    return Err(format!("Getting input file from URL failed: {}: {}", response.status(), content))
+                }
+                Ok(content)
+            },
+            Err(error) => {
+                Err(format!("HTTP Error: {}", error))
+            }
+        }
+    } else {
+        let mut f = File::open(input_file_uri)
+            .map_err(|e| format!("Could not open input file {}: {}", input_file_uri, e))?;
+        let mut content = String::new();
+        f.read_to_string(&mut content)
+            .map_err(|e| format!("Could not read input file {}: {}", input_file_uri, e))?;
+        Ok(content)
+    }
 }
 
 /// Prints the command line usage options
 fn print_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} FILE [options]", program);
+    let brief = format!("Usage: {} FILE... [options]\n\n\
+                         Give - as the FILE to read the schema from stdin.  Schemas are JSON, \
+                         or YAML when the FILE ends in .yaml or .yml, with the same fields \
+                         either way.  A FILE may hold an array of tables, or an object with a \
+                         \"tables\" array, each written to its own output file.", program);
     print!("{}\n", opts.usage(&brief));
 }
 
+/// Builds the Config from the command line arguments, reading every input FILE.  An input
+/// that cannot be read is an error, unless `--collect_errors` records it in `input_errors`
+/// and carries on with the rest.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::config::load;
+///
+/// # fn main() {
+/// let log = std::env::temp_dir().join("fourree-load.log");
+/// let args = vec!["fourree".to_string(), "/nonexistent/schema.json".to_string(),
+///                 "-l".to_string(), log.to_str().unwrap().to_string()];
+/// let error = load(args).err().unwrap();
+/// assert!(error.starts_with("Could not open input file /nonexistent/schema.json"));
+/// # }
+/// ```
+///
+/// ```
+/// # extern crate fourree;
+/// use fourree::config::load;
+///
+/// # fn main() {
+/// let log = std::env::temp_dir().join("fourree-load-collect.log");
+/// let args = vec!["fourree".to_string(), "/nonexistent/schema.json".to_string(),
+///                 "--collect_errors".to_string(), "-l".to_string(),
+///                 log.to_str().unwrap().to_string()];
+/// let config = load(args).unwrap();
+/// assert!(config.input_files.is_empty());
+/// assert_eq!(config.input_errors.len(), 1);
+/// assert!(config.input_errors[0].starts_with("/nonexistent/schema.json: Could not open"));
+/// # }
+/// ```
 pub fn load(args: Vec<String>) -> Result<Config, String> {
     let program = args[0].clone();
 
     let mut opts = Options::new();
     opts.optflag("h", "help", "print this help menu");
-    opts.optopt("n", "num_rows", "specify number of records to generate", "NUM_ROWS");
+    opts.optopt("n", "num_rows", "specify number of records to generate, not counting the header", "NUM_ROWS");
     opts.optopt("b", "batch_size", "specify the size of each batch to be processed", "BATCH_SIZE");
+    opts.optopt("", "batch_jitter", "randomly vary each batch's size by up to PCT percent, still producing exactly num_rows", "PCT");
+    opts.optopt("", "channel_capacity", "the most batches queued for output before generation waits for it to catch up (default: 64)", "NUM");
+    opts.optopt("", "seed", "seed the random number generators so the same seed reproduces the same output, whatever the number of threads", "NUM");
     opts.optopt("l", "log_file", "specify a file to write the log to", "LOG_FILE_PATH");
-    opts.optopt("t", "threads", "specify the number of threads to use (default: 1)", "NUM_THREADS");
+    opts.optflag("v", "verbose", "also log debug messages");
+    opts.optflag("q", "quiet", "only log warnings and errors");
+    opts.optopt("t", "threads", "specify the number of threads to use, or 'auto' to scale up to the cpu count while throughput improves (default: the cpu count)", "NUM_THREADS");
     opts.optopt("o", "output", "specify the desired output (default: stdout)", "OUTPUT");
-    opts.optopt("f", "output_file", "specify the file to output to, when in file output mode, or key when in S3 output mode", "OUTPUT_FILE");
+    opts.optopt("f", "output_file", "specify the file to output to, when in file output mode, key when in S3 output mode, or brokers:topic when in kafka output mode", "OUTPUT_FILE");
     opts.optflag("d", "display_header", "print the header as the first row");
+    opts.optopt("", "output_template", "name output files from a template using {table}, {date}, {shard} and {seed}", "TEMPLATE");
+    opts.optopt("", "format", "how rows are written: delimited (default) or jsonl, one JSON object per line", "FORMAT");
+    opts.optflag("", "json_spaces", "with --format jsonl, put a space after each ':' and ',' instead of writing compact JSON");
+    opts.optopt("", "line_terminator", "end each row with lf, crlf or an escape sequence such as '\\r\\n', instead of the schema's record_separator", "TERMINATOR");
+    opts.optopt("", "dialect", "the SQL dialect used by sql_file output: postgres (default), mysql or sqlite", "DIALECT");
+    opts.optflag("", "validate", "check that the schemas are valid and summarize their fields, without generating data");
+    opts.optflag("", "dry_run", "report the estimated output size and field cardinality without generating data");
+    opts.optmulti("", "count", "limit the dry run cardinality report to FIELD, may be repeated", "FIELD");
+    opts.optopt("", "sample", "print a uniform random sample of K generated rows to stderr when done", "K");
+    opts.optopt("", "max_rows_per_file", "split file output into numbered files of at most NUM rows each, e.g. output-0001.txt", "NUM");
+    opts.optopt("", "partition_by", "write each row to a file named by the value of FIELD, keeping at most 64 files open at once", "FIELD");
+    opts.optopt("", "on_complete", "run a shell command after each output file is written, with {file} replaced by its path", "CMD");
+    opts.optflag("", "ddl", "print a CREATE TABLE statement for the schema before the data");
+    opts.optopt("", "encrypt_key", "encrypt file or s3 output with AES-256-GCM using a 64 character hex key", "KEY");
+    opts.optflag("", "dedupe_rows", "only emit distinct rows, at the cost of memory and a shared lock");
+    opts.optflag("", "progress", "every few seconds, log the rows generated so far, the throughput and an ETA");
+    opts.optopt("", "unique_max_retries", "how many times a duplicate row or unique value is regenerated before failing (default: 1000)", "NUM");
+    opts.optopt("", "brokers", "comma separated list of Kafka brokers, when in kafka output mode", "BROKERS");
+    opts.optopt("", "topic", "the Kafka topic to publish rows to, when in kafka output mode", "TOPIC");
+    opts.optflag("", "collect_errors", "with several input FILEs, report every invalid schema and still generate the valid ones, instead of stopping at the first");
+    opts.optopt("", "from_table", "derive the schema from an existing PostgreSQL table instead of a FILE", "CONNECTION:TABLE");
+    opts.optopt("", "region", "the AWS region of the S3 bucket, when in s3 output mode (default: $AWS_REGION, or us-east-1)", "REGION");
+    opts.optopt("", "s3_endpoint", "upload to an S3-compatible store such as MinIO at URL instead of AWS, when in s3 output mode (default: $AWS_ENDPOINT_URL)", "URL");
+    opts.optopt("", "url", "the URL each batch is POSTed to, when in http output mode", "URL");
+    opts.optopt("", "content_type", "the Content-Type of each batch POSTed in http output mode (default: text/plain)", "TYPE");
+    opts.optopt("", "http_retries", &format!("how many times a batch is retried after a server error, in http output mode (default: {})", HTTP_RETRIES_DEFAULT), "NUM");
+    opts.optopt("", "kafka_messages", "publish each 'row' (the default) or each 'batch' of rows as a Kafka message", "row|batch");
+    opts.optopt("", "kafka_key", "the field whose value is used as the Kafka message key", "FIELD");
+    opts.optopt("", "connection_string", "the PostgreSQL database to COPY rows into, when in postgresql output mode", "CONNECTION");
+    opts.optopt("", "serve", "instead of generating data, serve POST /validate for checking schemas on PORT", "PORT");
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => { m }
@@ -66,54 +391,62 @@ pub fn load(args: Vec<String>) -> Result<Config, String> {
     };
 
     // Setup logging
+    let (verbose, quiet) = (matches.opt_present("v"), matches.opt_present("q"));
+    let log_level = match (verbose, quiet) {
+        (true, false) => LogLevelFilter::Debug,
+        (false, true) => LogLevelFilter::Warn,
+        _ => LogLevelFilter::Info
+    };
     let log_type = if matches.opt_present("l") {
         let value = matches.opt_str("l").unwrap().trim().to_string();
         if value == "stdout" {
-            init_logger(LogLevelFilter::Info, None).ok().expect("Failed to initalize logger!");
+            init_logger(log_level, None).ok().expect("Failed to initalize logger!");
             LogType::Console
         } else {
-            init_logger(LogLevelFilter::Info, Some(value.clone())).ok().expect("Failed to initalize logger!");
+            init_logger(log_level, Some(value.clone())).ok().expect("Failed to initalize logger!");
             LogType::File
         }
     } else {
-        init_logger(LogLevelFilter::Info, Some("fourree.log".to_string())).ok().expect("Failed to initialize logger!");
+        init_logger(log_level, Some("fourree.log".to_string())).ok().expect("Failed to initialize logger!");
         LogType::File
     };
 
     info!("Logging initialized, proccessing command line options.");
 
+    if verbose && quiet {
+        return Err("--verbose and --quiet can't be used together.".to_string());
+    }
+
     // Get help
     if matches.opt_present("h") {
         print_usage(&program, opts);
         return Err("".to_string());
     }
 
-    // Determine input file, quit if none given
-    let input_file = if !matches.free.is_empty() {
-        let input_file_uri = matches.free[0].clone();
-        if input_file_uri.starts_with("http") {
-            let mut response = reqwest::get(&input_file_uri);
-            let mut content = String::new();
-
-            match response {
-                Ok(mut response) => {
-                    info!("{:?}", response);
-                    response.read_to_string(&mut content).unwrap();
-                    if !response.status().is_success() {
-                        return Err(format!("Getting input file from URL failed: {}: {}", response.status(), content))
-                    }
-                    content
+    let from_table = matches.opt_str("from_table").map(|t| t.trim().to_string());
+    let serve = match matches.opt_str("serve") {
+        Some(port) => Some(port.trim().parse::<u16>()
+            .map_err(|_| "--serve must be a port number.".to_string())?),
+        None => None
+    };
+
+    // Determine input files, quit if none given
+    let collect_errors = matches.opt_present("collect_errors");
+    let mut input_errors = Vec::new();
+    let input_files = if from_table.is_some() || serve.is_some() {
+        Vec::new()
+    } else if !matches.free.is_empty() {
+        let mut input_files = Vec::with_capacity(matches.free.len());
+        for input_file_uri in matches.free.iter() {
+            match read_input_file(input_file_uri) {
+                Ok(content) => input_files.push((input_file_uri.clone(), content)),
+                Err(e) if collect_errors => {
+                    input_errors.push(format!("{}: {}", input_file_uri, e))
                 },
-                Err(error) => {
-                    return Err(format!("HTTP Error: {}", error))
-                }
+                Err(e) => return Err(e)
             }
-        } else {
-            let mut f = File::open(input_file_uri).unwrap();
-            let mut content = String::new();
-            f.read_to_string(&mut content).unwrap();
-            content
         }
+        input_files
     } else {
         print_usage(&program, opts);
         return Err("An input file must be provided.".to_string());
@@ -143,14 +476,47 @@ pub fn load(args: Vec<String>) -> Result<Config, String> {
                 warn!("{}, using default value {}", err, BATCH_SIZE_DEFAULT);
                 BATCH_SIZE_DEFAULT
             },
+            Ok(0) => return Err("--batch_size must be a positive integer.".to_string()),
             Ok(bsize) => { bsize }
         }
     } else {
         BATCH_SIZE_DEFAULT
     };
 
+    let batch_jitter = match matches.opt_str("batch_jitter") {
+        Some(pct) => {
+            let pct = pct.trim().parse::<f64>()
+                .map_err(|_| "--batch_jitter must be a percentage.".to_string())?;
+            if !(0.0..=100.0).contains(&pct) {
+                return Err("--batch_jitter must be between 0 and 100.".to_string())
+            }
+            pct
+        },
+        None => 0.0
+    };
+
+    let channel_capacity = match matches.opt_str("channel_capacity") {
+        Some(c) => match c.trim().parse::<usize>() {
+            Ok(capacity) if capacity > 0 => capacity,
+            _ => return Err("--channel_capacity must be a positive integer.".to_string())
+        },
+        None => CHANNEL_CAPACITY_DEFAULT
+    };
+
     // Setup number of threads to use for data generation
-    let num_threads = if matches.opt_present("t") {
+    let auto_threads = matches.opt_str("t").map_or(false, |t| t.trim() == "auto");
+
+    let seed = match matches.opt_str("seed") {
+        Some(s) => Some(s.trim().parse::<u64>()
+            .map_err(|_| "--seed must be a non-negative integer.".to_string())?),
+        None => None
+    };
+    let cpus = thread::available_parallelism().map(|n| n.get() as u64).unwrap_or(1)
+        .min(MAX_THREADS);
+    let requested_threads = if auto_threads {
+        info!("Received option: threads = auto, scaling up to {} threads", cpus);
+        cpus
+    } else if matches.opt_present("t") {
         let thread_opt = matches.opt_str("t").unwrap().trim().to_string();
         info!("Received option: threads = {}", thread_opt);
         match thread_opt.parse::<u64>() {
@@ -168,16 +534,22 @@ pub fn load(args: Vec<String>) -> Result<Config, String> {
             }
         }
     } else {
-        1
+        cpus
     };
 
+    // A dry run or validation only prints its report and the server prints nothing, so
+    // stdout is free
+    let dry_run = matches.opt_present("dry_run");
+    let validate = matches.opt_present("validate");
+    let stdout_free = dry_run || validate || serve.is_some();
+
     // Set the output mode
     let output_mode = if matches.opt_present("o") {
         let output_opt = matches.opt_str("o").unwrap().trim().to_string();
         info!("Received option: output mode = {}", output_opt);
         match output_opt.as_ref() {
             "stdout"     => {
-                if log_type == LogType::Console {
+                if log_type == LogType::Console && !stdout_free {
                     return Err("To use stdout as the output destination, you must enable logging to file with the '-l' option.".to_string());
                 }
                 OutputMode::Stdout
@@ -191,20 +563,30 @@ pub fn load(args: Vec<String>) -> Result<Config, String> {
             "s3" => {
                 OutputMode::S3
             },
+            "kafka" => {
+                OutputMode::Kafka
+            },
+            "sql_file" => {
+                OutputMode::SqlFile
+            },
+            "http" => {
+                OutputMode::Http
+            },
             _ => {
                 warn!("Unupported output requested: {}, defaulting to 'None'", output_opt);
                 OutputMode::None
             }
         }
     } else {
-        if log_type == LogType::Console {
+        if log_type == LogType::Console && !stdout_free {
             return Err("To use stdout as the output destination, you must enable logging to file with the '-l' option.".to_string());
         }
         OutputMode::Stdout
     };
 
     let output_file =
-        if output_mode == OutputMode::File || output_mode == OutputMode::S3 {
+        if output_mode == OutputMode::File || output_mode == OutputMode::S3 ||
+           output_mode == OutputMode::SqlFile {
             if matches.opt_present("f") {
                 let output_file_opt = matches.opt_str("f").unwrap().trim().to_string();
                 Some(output_file_opt)
@@ -215,22 +597,234 @@ pub fn load(args: Vec<String>) -> Result<Config, String> {
             None
         };
 
-    let num_batches = num_rows / batch_size;
-    if num_batches % num_threads != 0 {
-        return Err("Number of batches must be evenly divisible by number of threads.".to_string())
+    let (s3_region, s3_endpoint) = if output_mode == OutputMode::S3 {
+        let region = matches.opt_str("region").or_else(|| env::var("AWS_REGION").ok())
+            .map(|r| r.trim().to_string());
+        let endpoint = matches.opt_str("s3_endpoint")
+            .or_else(|| env::var("AWS_ENDPOINT_URL").ok())
+            .map(|e| e.trim().to_string());
+        // Catch a bad region or endpoint now, rather than when the upload starts
+        parse_s3_region(region.as_ref().map(|r| r.as_str()),
+                        endpoint.as_ref().map(|e| e.as_str()))?;
+        (region, endpoint)
+    } else {
+        (None, None)
+    };
+
+    let output_template = match matches.opt_str("output_template") {
+        Some(template) => {
+            let template = template.trim().to_string();
+            validate_output_template(&template)?;
+            Some(template)
+        },
+        None => None
+    };
+
+    let (kafka_brokers, kafka_topic) = if output_mode == OutputMode::Kafka {
+        let (brokers, topic) = match (matches.opt_str("brokers"), matches.opt_str("topic"),
+                                      matches.opt_str("f")) {
+            (Some(brokers), Some(topic), _) => (brokers, topic),
+            (None, None, Some(location)) => parse_kafka_location(location.trim())?,
+            _ => return Err("--brokers and --topic, or --output_file brokers:topic, are \
+                             required when using kafka output.".to_string())
+        };
+        (Some(brokers.trim().to_string()), Some(topic.trim().to_string()))
+    } else {
+        (None, None)
+    };
+    let kafka_key = matches.opt_str("kafka_key").map(|k| k.trim().to_string());
+    let kafka_batches = match matches.opt_str("kafka_messages") {
+        Some(m) => match m.trim() {
+            "row" => false,
+            "batch" => true,
+            other => return Err(format!("Unsupported --kafka_messages {}, expected 'row' or \
+                                         'batch'.", other))
+        },
+        None => false
+    };
+    if kafka_batches && kafka_key.is_some() {
+        return Err("--kafka_key needs one message per row, not --kafka_messages batch."
+                   .to_string())
+    }
+
+    let http_url = if output_mode == OutputMode::Http {
+        let url = matches.opt_str("url")
+            .ok_or("--url is required when using http output.".to_string())?;
+        Some(url.trim().to_string())
+    } else {
+        None
+    };
+    let http_content_type = match matches.opt_str("content_type") {
+        Some(t) => t.trim().to_string(),
+        None => "text/plain".to_string()
+    };
+    // Catch a bad content type now, rather than when the first batch is sent
+    parse_content_type(&http_content_type)?;
+    let http_retries = match matches.opt_str("http_retries") {
+        Some(r) => r.trim().parse::<u32>()
+            .map_err(|_| "--http_retries must be a non-negative integer.".to_string())?,
+        None => HTTP_RETRIES_DEFAULT
+    };
+
+    let connection_string = if output_mode == OutputMode::PostgreSQL {
+        let connection = matches.opt_str("connection_string")
+            .ok_or("--connection_string is required when using postgresql output.".to_string())?;
+        Some(connection.trim().to_string())
+    } else {
+        None
+    };
+
+    let num_batches = batch_count(num_rows, batch_size);
+    let num_threads = effective_thread_count(requested_threads, num_batches);
+    if num_threads < requested_threads && matches.opt_present("t") {
+        warn!("Only {} batches to generate, using {} threads instead of {}",
+              num_batches, num_threads, requested_threads);
     }
 
     // Get help
     let display_header = matches.opt_present("d");
+    let dedupe_rows = matches.opt_present("dedupe_rows");
+    let progress = matches.opt_present("progress");
+    let display_ddl = matches.opt_present("ddl");
+    let count_fields: Vec<String> = matches.opt_strs("count").iter()
+        .map(|f| f.trim().to_string())
+        .collect();
+
+    let unique_max_retries = match matches.opt_str("unique_max_retries") {
+        Some(r) => r.trim().parse::<u64>()
+            .map_err(|_| "--unique_max_retries must be a positive integer.".to_string())?,
+        None => DEDUPE_MAX_RETRIES
+    };
+
+    let sample = match matches.opt_str("sample") {
+        Some(k) => Some(k.trim().parse::<usize>()
+            .map_err(|_| "--sample must be a positive integer.".to_string())?),
+        None => None
+    };
+    if output_mode == OutputMode::SqlFile && sample.is_some() {
+        return Err("--sample is not supported with sql_file output.".to_string())
+    }
+
+    let on_complete = matches.opt_str("on_complete");
+    if on_complete.is_some() && output_mode != OutputMode::File && output_mode != OutputMode::SqlFile {
+        return Err("--on_complete requires file or sql_file output.".to_string())
+    }
+
+    let partition_by = matches.opt_str("partition_by").map(|f| f.trim().to_string());
+    if partition_by.is_some() {
+        if output_mode != OutputMode::File {
+            return Err("--partition_by requires file output.".to_string())
+        }
+        if matches.opt_present("encrypt_key") {
+            return Err("--partition_by is not supported with --encrypt_key.".to_string())
+        }
+    }
+
+    let max_rows_per_file = match matches.opt_str("max_rows_per_file") {
+        Some(m) => match m.trim().parse::<u64>() {
+            Ok(rows) if rows > 0 => Some(rows),
+            _ => return Err("--max_rows_per_file must be a positive integer.".to_string())
+        },
+        None => None
+    };
+    if max_rows_per_file.is_some() {
+        if output_mode != OutputMode::File {
+            return Err("--max_rows_per_file requires file output.".to_string())
+        }
+        if partition_by.is_some() || matches.opt_present("encrypt_key") {
+            return Err("--max_rows_per_file is not supported with --partition_by or \
+                        --encrypt_key.".to_string())
+        }
+    }
+
+    let json_spaces = matches.opt_present("json_spaces");
+    let output_format = match matches.opt_str("format") {
+        Some(f) => match f.trim() {
+            "delimited" => OutputFormat::Delimited,
+            "jsonl" => OutputFormat::JsonLines { spaces: json_spaces },
+            other => return Err(format!(
+                "Unsupported format: {}, expected 'delimited' or 'jsonl'.", other))
+        },
+        None => OutputFormat::Delimited
+    };
+    if output_format != OutputFormat::Delimited {
+        if output_mode == OutputMode::SqlFile || output_mode == OutputMode::PostgreSQL {
+            return Err("--format jsonl is not supported with sql_file or postgresql output."
+                       .to_string())
+        }
+        if display_header || display_ddl {
+            return Err("--format jsonl is not supported with --display_header or --ddl."
+                       .to_string())
+        }
+        if partition_by.is_some() || kafka_key.is_some() {
+            return Err("--format jsonl is not supported with --partition_by or --kafka_key."
+                       .to_string())
+        }
+    }
+    if json_spaces && output_format == OutputFormat::Delimited {
+        return Err("--json_spaces requires --format jsonl.".to_string())
+    }
+
+    let line_terminator = match matches.opt_str("line_terminator") {
+        Some(t) => Some(parse_line_terminator(&t)?),
+        None => None
+    };
+
+    let dialect = match matches.opt_str("dialect") {
+        Some(d) => parse_dialect(d.trim())?,
+        None => SqlDialect::Postgres
+    };
+    if output_mode == OutputMode::SqlFile && dedupe_rows {
+        return Err("--dedupe_rows is not supported with sql_file output.".to_string())
+    }
+
+    let encrypt_key = match matches.opt_str("encrypt_key") {
+        Some(key) => Some(parse_key(key.trim())?),
+        None => None
+    };
 
     Ok(Config {
         num_rows: num_rows,
         num_threads: num_threads,
+        auto_threads: auto_threads,
+        seed: seed,
         log_type: log_type,
         output_mode: output_mode,
+        output_format: output_format,
+        line_terminator: line_terminator,
         batch_size: batch_size,
-        input_file: input_file,
+        batch_jitter: batch_jitter,
+        channel_capacity: channel_capacity,
+        input_files: input_files,
+        collect_errors: collect_errors,
+        input_errors: input_errors,
         output_file: output_file,
-        display_header: display_header
+        display_header: display_header,
+        kafka_brokers: kafka_brokers,
+        kafka_topic: kafka_topic,
+        kafka_key: kafka_key,
+        kafka_batches: kafka_batches,
+        s3_region: s3_region,
+        s3_endpoint: s3_endpoint,
+        http_url: http_url,
+        http_content_type: http_content_type,
+        http_retries: http_retries,
+        connection_string: connection_string,
+        from_table: from_table,
+        dedupe_rows: dedupe_rows,
+        progress: progress,
+        display_ddl: display_ddl,
+        encrypt_key: encrypt_key,
+        output_template: output_template,
+        dialect: dialect,
+        unique_max_retries: unique_max_retries,
+        dry_run: dry_run,
+        validate: validate,
+        sample: sample,
+        on_complete: on_complete,
+        max_rows_per_file: max_rows_per_file,
+        count_fields: count_fields,
+        serve: serve,
+        partition_by: partition_by
     })
 }
@@ -0,0 +1,244 @@
+//! Client-side AES-256-GCM encryption of generated output.
+//!
+//! Encrypted output starts with the 8 byte header `FOURREE1`, followed by one or more
+//! chunks.  Each chunk is laid out as:
+//!
+//! * a 12 byte random nonce,
+//! * a 1 byte flag, 1 for the final chunk and 0 for every other,
+//! * the ciphertext length as a 4 byte big-endian integer,
+//! * the ciphertext, which includes the 16 byte GCM authentication tag.
+//!
+//! The chunk's position, counting from 0 as an 8 byte big-endian integer, followed by the
+//! flag, is authenticated as associated data.  The output always ends with a final chunk,
+//! which may be empty, so chunks that are reordered, repeated or dropped from anywhere,
+//! the end included, fail to decrypt.  Concatenating the decrypted chunks in order yields
+//! the original output; `decrypt` implements exactly this.
+
+use rand::{OsRng, Rng};
+
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+
+/// Marks the start of encrypted output and the version of the format
+pub const HEADER: &'static [u8] = b"FOURREE1";
+
+const NONCE_SIZE: usize = 12;
+const FLAG_SIZE: usize = 1;
+const LENGTH_SIZE: usize = 4;
+
+/// The associated data of a chunk: its position in the output and whether it is the last
+fn chunk_aad(index: u64, last: bool) -> [u8; 9] {
+    let mut aad = [0u8; 9];
+    for (i, byte) in aad[..8].iter_mut().enumerate() {
+        *byte = (index >> (56 - 8 * i)) as u8;
+    }
+    aad[8] = last as u8;
+    aad
+}
+
+/// Parses a 256-bit key given as 64 hexadecimal characters.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::crypto::parse_key;
+///
+/// # fn main() {
+/// assert_eq!(parse_key(&"ab".repeat(32)).unwrap().len(), 32);
+/// assert!(parse_key("abc").is_err());
+/// assert!(parse_key(&"é".repeat(32)).is_err());
+/// # }
+/// ```
+pub fn parse_key(hex: &str) -> Result<Vec<u8>, String> {
+    if hex.len() != 64 {
+        return Err("Encryption key must be 64 hexadecimal characters (256 bits).".to_string());
+    }
+    // Slicing by byte below needs every character to be a single byte
+    if !hex.is_ascii() {
+        return Err("Encryption key must be hexadecimal.".to_string());
+    }
+
+    (0..32).map(|i| {
+        u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| "Encryption key must be hexadecimal.".to_string())
+    }).collect()
+}
+
+/// Encrypts chunks of output with a single key and a fresh random nonce per chunk,
+/// numbering them in order until `finish` writes the final one
+pub struct ChunkEncryptor {
+    cipher: Aes256Gcm,
+    rng: OsRng,
+    index: u64,
+    finished: bool
+}
+
+impl ChunkEncryptor {
+    pub fn new(key: &[u8]) -> Result<ChunkEncryptor, String> {
+        if key.len() != 32 {
+            return Err("Encryption key must be 32 bytes.".to_string());
+        }
+
+        Ok(ChunkEncryptor {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+            rng: OsRng::new().map_err(|e| e.to_string())?,
+            index: 0,
+            finished: false
+        })
+    }
+
+    /// Encrypts one chunk, returning the nonce, flag, length and ciphertext ready to be
+    /// written
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        self.encrypt_chunk(plaintext, false)
+    }
+
+    /// Encrypts the empty final chunk that ends the output.  Nothing may be encrypted after.
+    pub fn finish(&mut self) -> Result<Vec<u8>, String> {
+        self.encrypt_chunk(&[], true)
+    }
+
+    fn encrypt_chunk(&mut self, plaintext: &[u8], last: bool) -> Result<Vec<u8>, String> {
+        if self.finished {
+            return Err("Encrypted output was already finished.".to_string());
+        }
+        let mut nonce = [0u8; NONCE_SIZE];
+        self.rng.fill_bytes(&mut nonce);
+
+        let aad = chunk_aad(self.index, last);
+        let payload = Payload { msg: plaintext, aad: &aad };
+        let ciphertext = self.cipher.encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| "Failed to encrypt output.".to_string())?;
+        self.index += 1;
+        self.finished = last;
+
+        let length = ciphertext.len() as u32;
+        let mut chunk = Vec::with_capacity(NONCE_SIZE + FLAG_SIZE + LENGTH_SIZE +
+                                           ciphertext.len());
+        chunk.extend_from_slice(&nonce);
+        chunk.push(last as u8);
+        chunk.extend_from_slice(&[(length >> 24) as u8, (length >> 16) as u8,
+                                  (length >> 8) as u8, length as u8]);
+        chunk.extend_from_slice(&ciphertext);
+        Ok(chunk)
+    }
+}
+
+/// Decrypts output produced with `--encrypt_key`, header included.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::crypto::{decrypt, parse_key, ChunkEncryptor, HEADER};
+///
+/// # fn main() {
+/// let key = parse_key(&"0f".repeat(32)).unwrap();
+/// let mut encryptor = ChunkEncryptor::new(&key).unwrap();
+///
+/// let mut output = HEADER.to_vec();
+/// output.extend(encryptor.encrypt(b"1\tA\n2\tB\n").unwrap());
+/// let second = encryptor.encrypt(b"3\tC\n").unwrap();
+/// output.extend(second.clone());
+/// let complete = [output.clone(), encryptor.finish().unwrap()].concat();
+///
+/// assert_eq!(decrypt(&key, &complete).unwrap(), b"1\tA\n2\tB\n3\tC\n".to_vec());
+/// assert!(decrypt(&parse_key(&"f0".repeat(32)).unwrap(), &complete).is_err());
+///
+/// // Output cut off before its final chunk, or with a chunk repeated, is rejected
+/// assert!(decrypt(&key, &output).is_err());
+/// let mut repeated = output.clone();
+/// repeated.extend(second);
+/// assert!(decrypt(&key, &repeated).is_err());
+/// # }
+/// ```
+///
+/// Decrypting a file written by `generate_data`:
+///
+/// ```
+/// # extern crate fourree;
+/// use std::fs::File;
+/// use std::io::Read;
+///
+/// use fourree::config::{Config, OutputMode};
+/// use fourree::crypto::{decrypt, parse_key};
+/// use fourree::json::parse_json;
+/// use fourree::util::generate_data;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+/// ]}"#).unwrap();
+/// let key = parse_key(&"0f".repeat(32)).unwrap();
+/// let path = std::env::temp_dir().join("fourree-encrypted-output.txt");
+/// let config = Config {
+///     num_rows: 20,
+///     batch_size: 5,
+///     output_mode: OutputMode::File,
+///     output_file: Some(path.to_str().unwrap().to_string()),
+///     display_header: true,
+///     encrypt_key: Some(key.clone()),
+///     ..Default::default()
+/// };
+/// generate_data(&config, schema).unwrap();
+///
+/// let mut encrypted = Vec::new();
+/// File::open(&path).unwrap().read_to_end(&mut encrypted).unwrap();
+/// let rows = String::from_utf8(decrypt(&key, &encrypted).unwrap()).unwrap();
+/// assert_eq!(rows.lines().next(), Some("id"));
+/// assert_eq!(rows.lines().count(), 21);
+/// # }
+/// ```
+pub fn decrypt(key: &[u8], data: &[u8]) -> Result<Vec<u8>, String> {
+    if key.len() != 32 {
+        return Err("Encryption key must be 32 bytes.".to_string());
+    }
+    if !data.starts_with(HEADER) {
+        return Err("Data is not encrypted fourree output.".to_string());
+    }
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let mut plaintext = Vec::new();
+    let mut offset = HEADER.len();
+
+    let mut index = 0;
+    let mut finished = false;
+
+    while offset < data.len() {
+        if finished {
+            return Err("Encrypted output continues after its final chunk.".to_string());
+        }
+        if data.len() < offset + NONCE_SIZE + FLAG_SIZE + LENGTH_SIZE {
+            return Err("Encrypted chunk is truncated.".to_string());
+        }
+        let nonce = &data[offset..offset + NONCE_SIZE];
+        let last = match data[offset + NONCE_SIZE] {
+            0 => false,
+            1 => true,
+            _ => return Err("Encrypted chunk has an invalid flag.".to_string())
+        };
+        let length_offset = offset + NONCE_SIZE + FLAG_SIZE;
+        let l = &data[length_offset..length_offset + LENGTH_SIZE];
+        let length = ((l[0] as usize) << 24) | ((l[1] as usize) << 16) |
+                     ((l[2] as usize) << 8) | l[3] as usize;
+
+        let start = length_offset + LENGTH_SIZE;
+        if data.len() < start + length {
+            return Err("Encrypted chunk is truncated.".to_string());
+        }
+
+        let aad = chunk_aad(index, last);
+        let payload = Payload { msg: &data[start..start + length], aad: &aad };
+        let chunk = cipher.decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| format!("Failed to decrypt chunk {}, the key may be wrong or the \
+                                  chunks out of order.", index))?;
+        plaintext.extend(chunk);
+        offset = start + length;
+        index += 1;
+        finished = last;
+    }
+
+    if !finished {
+        return Err("Encrypted output is truncated, its final chunk is missing.".to_string());
+    }
+    Ok(plaintext)
+}
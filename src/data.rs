@@ -0,0 +1,69 @@
+//! Embedded word lists used by the generators that produce realistic looking text.
+
+pub static COMPANY_NAMES: &'static [&'static str] = &[
+    "Acme", "Globex", "Initech", "Umbrella", "Stark", "Wayne", "Wonka", "Hooli",
+    "Vandelay", "Soylent", "Cyberdyne", "Tyrell", "Aperture", "Gringotts", "Oscorp",
+    "Northwind", "Contoso", "Fabrikam", "Blue Ridge", "Silver Lake", "Red Rock",
+    "Evergreen", "Summit", "Pinnacle", "Horizon", "Keystone", "Lighthouse", "Ironwood",
+    "Riverbend", "Granite", "Meridian", "Atlas", "Beacon", "Cascade", "Harbor"
+];
+
+pub static COMPANY_SUFFIXES: &'static [&'static str] = &[
+    "Inc", "LLC", "Ltd", "Corp", "Co", "Group", "Holdings", "Partners"
+];
+
+pub static PRODUCT_ADJECTIVES: &'static [&'static str] = &[
+    "Ergonomic", "Rustic", "Sleek", "Intelligent", "Gorgeous", "Handcrafted",
+    "Practical", "Refined", "Generic", "Licensed", "Awesome", "Durable", "Compact",
+    "Deluxe", "Portable", "Classic"
+];
+
+pub static PRODUCT_MATERIALS: &'static [&'static str] = &[
+    "Steel", "Wooden", "Cotton", "Plastic", "Granite", "Rubber", "Leather", "Bronze",
+    "Concrete", "Aluminum", "Ceramic", "Wool", "Glass", "Marble"
+];
+
+pub static PRODUCT_NOUNS: &'static [&'static str] = &[
+    "Chair", "Table", "Lamp", "Keyboard", "Shoes", "Gloves", "Hat", "Bottle", "Wallet",
+    "Bag", "Clock", "Computer", "Towels", "Pants", "Shirt", "Bike", "Mug", "Desk"
+];
+
+pub static FIRST_NAMES: &'static [&'static str] = &[
+    "James", "Mary", "John", "Patricia", "Robert", "Jennifer", "Michael", "Linda", "David",
+    "Elizabeth", "William", "Barbara", "Richard", "Susan", "Joseph", "Jessica", "Thomas",
+    "Sarah", "Carlos", "Maria", "Wei", "Mei", "Ahmed", "Fatima", "Raj", "Priya", "Kenji",
+    "Yuki", "Olga", "Ivan", "Kwame", "Amara", "Liam", "Emma", "Noah", "Olivia"
+];
+
+pub static LAST_NAMES: &'static [&'static str] = &[
+    "Smith", "Johnson", "Williams", "Brown", "Jones", "Garcia", "Miller", "Davis",
+    "Rodriguez", "Martinez", "Hernandez", "Lopez", "Wilson", "Anderson", "Taylor", "Thomas",
+    "Moore", "Jackson", "Martin", "Lee", "Nguyen", "Chen", "Wang", "Kim", "Patel", "Singh",
+    "Khan", "Tanaka", "Sato", "Ivanov", "Novak", "Mensah", "Okafor", "Murphy", "Kelly"
+];
+
+pub static GENDER_LABELS: &'static [&'static str] = &[
+    "Female", "Male", "Non-binary"
+];
+
+/// Domains reserved for examples and testing, so generated addresses never reach anyone
+pub static EMAIL_DOMAINS: &'static [&'static str] = &[
+    "example.com", "example.org", "example.net", "mail.example", "inbox.test"
+];
+
+/// Filler words for the text generator, all distinct and lowercase
+pub static LOREM_WORDS: &'static [&'static str] = &[
+    "lorem", "ipsum", "dolor", "sit", "amet", "consectetur", "adipiscing", "elit", "sed",
+    "do", "eiusmod", "tempor", "incididunt", "ut", "labore", "et", "dolore", "magna",
+    "aliqua", "enim", "ad", "minim", "veniam", "quis", "nostrud", "exercitation", "ullamco",
+    "laboris", "nisi", "aliquip", "ex", "ea", "commodo", "consequat", "duis", "aute", "irure",
+    "in", "reprehenderit", "voluptate", "velit", "esse", "cillum", "eu", "fugiat", "nulla",
+    "pariatur", "excepteur", "sint", "occaecat", "cupidatat", "non", "proident", "sunt",
+    "culpa", "qui", "officia", "deserunt", "mollit", "anim", "id", "est", "laborum"
+];
+
+/// Inclusive ranges of Unicode code points used by the string generator's charsets
+pub static CYRILLIC_RANGES: &'static [(u32, u32)] = &[(0x0410, 0x044F)];
+pub static GREEK_RANGES: &'static [(u32, u32)] = &[(0x0391, 0x03A1), (0x03A3, 0x03A9), (0x03B1, 0x03C9)];
+pub static CJK_RANGES: &'static [(u32, u32)] = &[(0x4E00, 0x9FFF)];
+pub static EMOJI_RANGES: &'static [(u32, u32)] = &[(0x1F600, 0x1F64F)];
@@ -0,0 +1,603 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use schema::TypedValue;
+use generators::{days_from_civil, days_in_month};
+
+/// An arithmetic or string operator, from lowest to highest precedence: `||`, then `+`
+/// and `-`, then `*`, `/` and `%`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Operator {
+    Concat,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder
+}
+
+/// A function an expression may call, with the number of arguments it takes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Function {
+    /// Rounds to a number of decimal places, 0 when the second argument is left off
+    Round,
+    Floor,
+    Ceil,
+    Abs,
+    Min,
+    Max,
+    Upper,
+    Lower,
+    Length,
+    /// The year, month or day of a "MM/DD/YYYY" or "YYYY-MM-DD" date, the latter of which
+    /// may be followed by a time
+    Year,
+    Month,
+    Day,
+    /// Days from the first date to the second
+    DaysBetween,
+    /// Whole years from the first date to the second, such as an age from a birth date
+    YearsBetween
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        let function = match name {
+            "round" => Function::Round,
+            "floor" => Function::Floor,
+            "ceil" => Function::Ceil,
+            "abs" => Function::Abs,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            "upper" => Function::Upper,
+            "lower" => Function::Lower,
+            "length" => Function::Length,
+            "year" => Function::Year,
+            "month" => Function::Month,
+            "day" => Function::Day,
+            "days_between" => Function::DaysBetween,
+            "years_between" => Function::YearsBetween,
+            _ => return None
+        };
+        Some(function)
+    }
+
+    /// The fewest and most arguments the function takes
+    fn arity(&self) -> (usize, usize) {
+        match *self {
+            Function::Round => (1, 2),
+            Function::Min | Function::Max | Function::DaysBetween |
+            Function::YearsBetween => (2, 2),
+            _ => (1, 1)
+        }
+    }
+}
+
+/// A parsed expression, computing a value from other fields of the same row
+#[derive(Clone, PartialEq, Debug)]
+pub enum Expression {
+    Integer(i64),
+    Number(f64),
+    Text(String),
+    /// The value of the field with this name
+    Field(String),
+    Negate(Box<Expression>),
+    Binary { operator: Operator, left: Box<Expression>, right: Box<Expression> },
+    Call { function: Function, arguments: Vec<Expression> }
+}
+
+/// Parses an expression over other fields of a row.  Fields are referred to by name, text
+/// is written in single quotes (doubled to include one), and numbers may have a fractional
+/// part.  `+`, `-`, `*`, `/` and `%` do arithmetic, `||` joins values as text, and
+/// parentheses group.  The functions are `round(x, places)`, `floor`, `ceil`, `abs`,
+/// `min(a, b)`, `max(a, b)`, `upper`, `lower`, `length`, `year`, `month` and `day` of a
+/// date, and `days_between(from, to)` and `years_between(from, to)`.  Dates are read as
+/// "MM/DD/YYYY", like those of the date generator, or as "YYYY-MM-DD" with an optional time.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::expression::parse_expression;
+///
+/// # fn main() {
+/// assert!(parse_expression("price * quantity").is_ok());
+/// assert!(parse_expression("round(total * 1.08, 2)").is_ok());
+/// assert!(parse_expression("upper(last_name) || ', ' || first_name").is_ok());
+/// assert!(parse_expression("years_between(birth_date, '2024-01-01')").is_ok());
+/// assert!(parse_expression("price *").is_err());
+/// assert!(parse_expression("(price").is_err());
+/// assert!(parse_expression("sqrt(price)").err().unwrap().contains("Unknown function"));
+/// assert!(parse_expression("round(price, 1, 2)").is_err());
+/// # }
+/// ```
+pub fn parse_expression(expression: &str) -> Result<Expression, String> {
+    let mut parser = Parser { chars: expression.chars().collect(), pos: 0 };
+    let parsed = parser.concat()?;
+
+    parser.skip_whitespace();
+    match parser.peek() {
+        None => Ok(parsed),
+        Some(c) => Err(format!("Unexpected '{}' at position {} in expression {}.",
+                               c, parser.pos, expression))
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, |c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skips whitespace, then consumes the token if it comes next
+    fn accept(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let matches = token.chars().enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c));
+        if matches {
+            self.pos += token.chars().count();
+        }
+        matches
+    }
+
+    fn binary(operator: Operator, left: Expression, right: Expression) -> Expression {
+        Expression::Binary { operator: operator, left: Box::new(left), right: Box::new(right) }
+    }
+
+    fn concat(&mut self) -> Result<Expression, String> {
+        let mut left = self.additive()?;
+        while self.accept("||") {
+            left = Parser::binary(Operator::Concat, left, self.additive()?);
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> Result<Expression, String> {
+        let mut left = self.term()?;
+        loop {
+            let operator = if self.accept("+") {
+                Operator::Add
+            } else if self.accept("-") {
+                Operator::Subtract
+            } else {
+                return Ok(left);
+            };
+            left = Parser::binary(operator, left, self.term()?);
+        }
+    }
+
+    fn term(&mut self) -> Result<Expression, String> {
+        let mut left = self.unary()?;
+        loop {
+            let operator = if self.accept("*") {
+                Operator::Multiply
+            } else if self.accept("/") {
+                Operator::Divide
+            } else if self.accept("%") {
+                Operator::Remainder
+            } else {
+                return Ok(left);
+            };
+            left = Parser::binary(operator, left, self.unary()?);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        if self.accept("-") {
+            Ok(Expression::Negate(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expression, String> {
+        self.skip_whitespace();
+        let position = self.pos;
+        match self.peek() {
+            None => Err("Expression ends unexpectedly.".to_string()),
+            Some('(') => {
+                self.pos += 1;
+                let inner = self.concat()?;
+                if !self.accept(")") {
+                    return Err(format!("Unclosed parenthesis at position {}.", position));
+                }
+                Ok(inner)
+            }
+            Some('\'') => self.text(),
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let name = self.identifier();
+                if !self.accept("(") {
+                    return Ok(Expression::Field(name));
+                }
+                let function = Function::from_name(&name)
+                    .ok_or(format!("Unknown function {} in expression.", name))?;
+                let mut arguments = Vec::new();
+                if !self.accept(")") {
+                    loop {
+                        arguments.push(self.concat()?);
+                        if self.accept(")") {
+                            break;
+                        }
+                        if !self.accept(",") {
+                            return Err(format!("Expected ',' or ')' in the arguments of {} at \
+                                                position {}.", name, self.pos));
+                        }
+                    }
+                }
+                let (fewest, most) = function.arity();
+                if arguments.len() < fewest || arguments.len() > most {
+                    return Err(format!("{} takes {} arguments, not {}.", name,
+                                       if fewest == most { fewest.to_string() }
+                                       else { format!("{} or {}", fewest, most) },
+                                       arguments.len()));
+                }
+                Ok(Expression::Call { function: function, arguments: arguments })
+            }
+            Some(c) => Err(format!("Unexpected '{}' at position {} in expression.", c, position))
+        }
+    }
+
+    fn identifier(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().map_or(false, |c| c.is_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn number(&mut self) -> Result<Expression, String> {
+        let start = self.pos;
+        while self.peek().map_or(false, |c| c.is_ascii_digit() || c == '.') {
+            self.pos += 1;
+        }
+        let literal: String = self.chars[start..self.pos].iter().collect();
+        if let Ok(i) = literal.parse::<i64>() {
+            return Ok(Expression::Integer(i));
+        }
+        literal.parse::<f64>()
+            .map(Expression::Number)
+            .map_err(|_| format!("Invalid number {} in expression.", literal))
+    }
+
+    /// Parses text in single quotes, where two quotes in a row stand for one
+    fn text(&mut self) -> Result<Expression, String> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(format!("Unclosed text at position {}.", start)),
+                Some('\'') if self.chars.get(self.pos + 1) == Some(&'\'') => {
+                    text.push('\'');
+                    self.pos += 2;
+                }
+                Some('\'') => {
+                    self.pos += 1;
+                    return Ok(Expression::Text(text));
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+}
+
+/// A value partway through evaluation, which keeps the full precision of fractional
+/// numbers until the result is rendered
+#[derive(Clone, PartialEq, Debug)]
+enum Scalar {
+    Integer(i64),
+    Number(f64),
+    Text(String),
+    Null
+}
+
+impl Scalar {
+    fn from_value(value: &TypedValue) -> Scalar {
+        match *value {
+            TypedValue::Integer(i) => Scalar::Integer(i),
+            // Through the shortest text of the f32, so 0.1 does not become 0.10000000149
+            TypedValue::Float(x) => Scalar::Number(x.to_string().parse().unwrap_or(x as f64)),
+            TypedValue::Text(ref s) => Scalar::Text(s.clone()),
+            TypedValue::Null => Scalar::Null
+        }
+    }
+
+    fn into_value(self) -> Result<TypedValue, String> {
+        match self {
+            Scalar::Integer(i) => Ok(TypedValue::Integer(i)),
+            Scalar::Number(x) if !x.is_finite() => {
+                Err("Expression does not evaluate to a finite number.".to_string())
+            }
+            Scalar::Number(x) => Ok(TypedValue::Text(format_number(x))),
+            Scalar::Text(s) => Ok(TypedValue::Text(s)),
+            Scalar::Null => Ok(TypedValue::Null)
+        }
+    }
+
+    /// Reads the value as a number, parsing text such as the output of the decimal generator
+    fn numeric(self) -> Result<Scalar, String> {
+        match self {
+            Scalar::Text(s) => {
+                let trimmed = s.trim();
+                if let Ok(i) = trimmed.parse::<i64>() {
+                    Ok(Scalar::Integer(i))
+                } else {
+                    trimmed.parse::<f64>().map(Scalar::Number)
+                        .map_err(|_| format!("'{}' is not a number.", s))
+                }
+            }
+            other => Ok(other)
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match *self {
+            Scalar::Integer(i) => i as f64,
+            Scalar::Number(x) => x,
+            _ => unreachable!()
+        }
+    }
+
+    fn text(&self) -> String {
+        match *self {
+            Scalar::Integer(i) => i.to_string(),
+            Scalar::Number(x) => format_number(x),
+            Scalar::Text(ref s) => s.clone(),
+            Scalar::Null => String::new()
+        }
+    }
+
+    /// Parses the value as a date: either "MM/DD/YYYY", as the date generator writes them,
+    /// or "YYYY-MM-DD", which may be followed by a time
+    fn date(&self) -> Result<(i64, u32, u32), String> {
+        let text = self.text();
+        let invalid = || format!("'{}' is not a date.", text);
+        let date = text.get(..10).filter(|d| d.is_ascii()).ok_or_else(invalid)?;
+        let (year, month, day) = match (date.find('-'), date.find('/')) {
+            (Some(4), None) => (&date[..4], &date[5..7], &date[8..]),
+            (None, Some(2)) => (&date[6..], &date[..2], &date[3..5]),
+            _ => return Err(invalid())
+        };
+        let year = year.parse::<i64>().map_err(|_| invalid())?;
+        let month = month.parse::<u32>().map_err(|_| invalid())?;
+        let day = day.parse::<u32>().map_err(|_| invalid())?;
+        if month < 1 || month > 12 || day < 1 || day > days_in_month(year, month) {
+            return Err(invalid());
+        }
+        Ok((year, month, day))
+    }
+}
+
+impl Expression {
+    /// The names of the fields the expression refers to, each listed once
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// use fourree::expression::parse_expression;
+    ///
+    /// # fn main() {
+    /// let expression = parse_expression("round(price * quantity + price, 2)").unwrap();
+    /// assert_eq!(expression.inputs(), vec!["price".to_string(), "quantity".to_string()]);
+    /// # }
+    /// ```
+    pub fn inputs(&self) -> Vec<String> {
+        let mut inputs = Vec::new();
+        self.collect_inputs(&mut inputs);
+        inputs
+    }
+
+    fn collect_inputs(&self, inputs: &mut Vec<String>) {
+        match *self {
+            Expression::Field(ref name) => {
+                if !inputs.contains(name) {
+                    inputs.push(name.clone());
+                }
+            }
+            Expression::Negate(ref inner) => inner.collect_inputs(inputs),
+            Expression::Binary { ref left, ref right, .. } => {
+                left.collect_inputs(inputs);
+                right.collect_inputs(inputs);
+            }
+            Expression::Call { ref arguments, .. } => {
+                for argument in arguments.iter() {
+                    argument.collect_inputs(inputs);
+                }
+            }
+            Expression::Integer(_) | Expression::Number(_) | Expression::Text(_) => {}
+        }
+    }
+
+    /// Evaluates the expression against a row's values, keyed by field name.  Integers stay
+    /// integers through `+`, `-`, `*` and `%`, while `/` and fractional numbers give text
+    /// holding the number, to at most 10 decimal places.  A null input makes the whole
+    /// result null.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// use std::collections::HashMap;
+    /// use fourree::expression::parse_expression;
+    /// use fourree::schema::TypedValue;
+    ///
+    /// # fn main() {
+    /// let price = TypedValue::Text("19.99".to_string());
+    /// let quantity = TypedValue::Integer(3);
+    /// let birth_date = TypedValue::Text("06/15/1990".to_string());
+    /// let mut row = HashMap::new();
+    /// row.insert("price", &price);
+    /// row.insert("quantity", &quantity);
+    /// row.insert("birth_date", &birth_date);
+    ///
+    /// let evaluate = |e: &str| parse_expression(e).unwrap().evaluate(&row);
+    /// assert_eq!(evaluate("round(price * quantity, 2)"),
+    ///            Ok(TypedValue::Text("59.97".to_string())));
+    /// assert_eq!(evaluate("quantity * 2 - 1"), Ok(TypedValue::Integer(5)));
+    /// assert_eq!(evaluate("price * quantity / 3"), Ok(TypedValue::Text("19.99".to_string())));
+    /// assert_eq!(evaluate("years_between(birth_date, '2024-06-14')"),
+    ///            Ok(TypedValue::Integer(33)));
+    /// assert_eq!(evaluate("'x' || quantity"), Ok(TypedValue::Text("x3".to_string())));
+    /// assert!(evaluate("quantity / 0").is_err());
+    /// assert!(evaluate("missing + 1").is_err());
+    /// # }
+    /// ```
+    pub fn evaluate(&self, row: &HashMap<&str, &TypedValue>) -> Result<TypedValue, String> {
+        self.scalar(row)?.into_value()
+    }
+
+    fn scalar(&self, row: &HashMap<&str, &TypedValue>) -> Result<Scalar, String> {
+        match *self {
+            Expression::Integer(i) => Ok(Scalar::Integer(i)),
+            Expression::Number(x) => Ok(Scalar::Number(x)),
+            Expression::Text(ref s) => Ok(Scalar::Text(s.clone())),
+            Expression::Field(ref name) => {
+                row.get(name.as_str())
+                    .map(|value| Scalar::from_value(value))
+                    .ok_or(format!("Expression refers to field {}, which does not exist.", name))
+            }
+            Expression::Negate(ref inner) => {
+                match inner.scalar(row)?.numeric()? {
+                    Scalar::Integer(i) => i.checked_neg().map(Scalar::Integer)
+                        .ok_or("Integer overflow in expression.".to_string()),
+                    Scalar::Number(x) => Ok(Scalar::Number(-x)),
+                    other => Ok(other)
+                }
+            }
+            Expression::Binary { operator, ref left, ref right } => {
+                binary(operator, left.scalar(row)?, right.scalar(row)?)
+            }
+            Expression::Call { function, ref arguments } => {
+                let mut values = Vec::with_capacity(arguments.len());
+                for argument in arguments.iter() {
+                    values.push(argument.scalar(row)?);
+                }
+                call(function, values)
+            }
+        }
+    }
+}
+
+/// Writes a fractional number with at most 10 decimal places, so that the error of binary
+/// floating point, as in 36.66 / 3 = 12.219999999999999, is not written out
+fn format_number(x: f64) -> String {
+    let text = format!("{:.10}", x);
+    let trimmed = text.trim_end_matches('0').trim_end_matches('.');
+    if trimmed == "-0" { "0".to_string() } else { trimmed.to_string() }
+}
+
+fn binary(operator: Operator, left: Scalar, right: Scalar) -> Result<Scalar, String> {
+    if left == Scalar::Null || right == Scalar::Null {
+        return Ok(Scalar::Null);
+    }
+    if operator == Operator::Concat {
+        return Ok(Scalar::Text(left.text() + &right.text()));
+    }
+
+    let overflow = || "Integer overflow in expression.".to_string();
+    match (left.numeric()?, right.numeric()?) {
+        (Scalar::Integer(_), Scalar::Integer(0))
+                if operator == Operator::Divide || operator == Operator::Remainder => {
+            Err("Division by zero in expression.".to_string())
+        }
+        (Scalar::Integer(a), Scalar::Integer(b)) if operator != Operator::Divide => {
+            let result = match operator {
+                Operator::Add => a.checked_add(b),
+                Operator::Subtract => a.checked_sub(b),
+                Operator::Multiply => a.checked_mul(b),
+                _ => a.checked_rem(b)
+            };
+            result.map(Scalar::Integer).ok_or_else(overflow)
+        }
+        (a, b) => {
+            let (a, b) = (a.as_f64(), b.as_f64());
+            let result = match operator {
+                Operator::Add => a + b,
+                Operator::Subtract => a - b,
+                Operator::Multiply => a * b,
+                Operator::Divide if b == 0.0 => {
+                    return Err("Division by zero in expression.".to_string())
+                }
+                Operator::Divide => a / b,
+                _ => a % b
+            };
+            Ok(Scalar::Number(result))
+        }
+    }
+}
+
+fn call(function: Function, mut arguments: Vec<Scalar>) -> Result<Scalar, String> {
+    if arguments.contains(&Scalar::Null) {
+        return Ok(Scalar::Null);
+    }
+
+    match function {
+        Function::Upper => Ok(Scalar::Text(arguments[0].text().to_uppercase())),
+        Function::Lower => Ok(Scalar::Text(arguments[0].text().to_lowercase())),
+        Function::Length => Ok(Scalar::Integer(arguments[0].text().chars().count() as i64)),
+        Function::Year => Ok(Scalar::Integer(arguments[0].date()?.0)),
+        Function::Month => Ok(Scalar::Integer(arguments[0].date()?.1 as i64)),
+        Function::Day => Ok(Scalar::Integer(arguments[0].date()?.2 as i64)),
+        Function::DaysBetween => {
+            let (from, to) = (arguments[0].date()?, arguments[1].date()?);
+            Ok(Scalar::Integer(days_from_civil(to.0, to.1, to.2) -
+                               days_from_civil(from.0, from.1, from.2)))
+        }
+        Function::YearsBetween => {
+            let (from, to) = (arguments[0].date()?, arguments[1].date()?);
+            let mut years = to.0 - from.0;
+            // Not a whole year until the anniversary is reached
+            if years > 0 && (to.1, to.2) < (from.1, from.2) {
+                years -= 1;
+            } else if years < 0 && (to.1, to.2) > (from.1, from.2) {
+                years += 1;
+            }
+            Ok(Scalar::Integer(years))
+        }
+        Function::Min | Function::Max => {
+            let b = arguments.pop().unwrap().numeric()?;
+            let a = arguments.pop().unwrap().numeric()?;
+            let order = a.as_f64().partial_cmp(&b.as_f64()).unwrap_or(Ordering::Equal);
+            let pick_first = (order != Ordering::Greater) == (function == Function::Min);
+            Ok(if pick_first { a } else { b })
+        }
+        Function::Round => {
+            let places = match arguments.get(1) {
+                Some(p) => match p.clone().numeric()? {
+                    Scalar::Integer(p) if (0..=15).contains(&p) => p as usize,
+                    _ => return Err("round() takes 0 to 15 decimal places.".to_string())
+                },
+                None => 0
+            };
+            match arguments.swap_remove(0).numeric()? {
+                Scalar::Integer(i) if places == 0 => Ok(Scalar::Integer(i)),
+                x if places == 0 => Ok(Scalar::Integer(x.as_f64().round() as i64)),
+                x => Ok(Scalar::Text(format!("{:.*}", places, x.as_f64())))
+            }
+        }
+        Function::Floor | Function::Ceil | Function::Abs => {
+            match arguments.swap_remove(0).numeric()? {
+                Scalar::Integer(i) if function == Function::Abs => {
+                    i.checked_abs().map(Scalar::Integer)
+                        .ok_or("Integer overflow in expression.".to_string())
+                }
+                Scalar::Integer(i) => Ok(Scalar::Integer(i)),
+                x if function == Function::Abs => Ok(Scalar::Number(x.as_f64().abs())),
+                x if function == Function::Floor => Ok(Scalar::Integer(x.as_f64().floor() as i64)),
+                x => Ok(Scalar::Integer(x.as_f64().ceil() as i64))
+            }
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use schema::TypedValue;
+
+/// The kind of value a format spec renders, taken from its conversion character
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Conversion {
+    Integer,
+    Float,
+    Text
+}
+
+/// How a number is rounded to a format's precision
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Rounding {
+    /// Ties round away from zero
+    HalfUp,
+    /// Ties round to the even neighbour, also known as banker's rounding
+    HalfEven,
+    /// Always rounds towards negative infinity
+    Floor
+}
+
+/// Parses the name of a rounding mode
+pub fn parse_rounding(name: &str) -> Result<Rounding, String> {
+    match name {
+        "half_up" => Ok(Rounding::HalfUp),
+        "half_even" => Ok(Rounding::HalfEven),
+        "floor" => Ok(Rounding::Floor),
+        other => Err(format!(
+            "Unsupported rounding: {}, expected 'half_up', 'half_even' or 'floor'.", other))
+    }
+}
+
+/// Rounds a number to `precision` decimal places.  Ties are judged on the number's shortest
+/// decimal form, so 2.125 is a tie even though it is stored in binary.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::format::{round, Rounding};
+///
+/// # fn main() {
+/// assert_eq!(format!("{:.2}", round(2.125, 2, Rounding::HalfUp)), "2.13");
+/// assert_eq!(format!("{:.2}", round(2.125, 2, Rounding::HalfEven)), "2.12");
+/// assert_eq!(format!("{:.2}", round(2.135, 2, Rounding::HalfEven)), "2.14");
+/// assert_eq!(format!("{:.2}", round(2.129, 2, Rounding::Floor)), "2.12");
+/// assert_eq!(format!("{:.2}", round(-2.125, 2, Rounding::HalfUp)), "-2.13");
+/// assert_eq!(format!("{:.0}", round(-2.5, 0, Rounding::Floor)), "-3");
+/// # }
+/// ```
+pub fn round(value: f64, precision: usize, mode: Rounding) -> f64 {
+    let factor = 10f64.powi(precision as i32);
+    let scaled = value * factor;
+    let lower = scaled.floor();
+    let fraction = scaled - lower;
+    // Scaling can leave a decimal tie like 2.125 * 100 a hair away from .5
+    let tolerance = 1e-9 * scaled.abs().max(1.0);
+
+    let rounded = match mode {
+        Rounding::Floor => {
+            if 1.0 - fraction < tolerance { lower + 1.0 } else { lower }
+        }
+        _ if (fraction - 0.5).abs() < tolerance => {
+            match mode {
+                Rounding::HalfEven if lower % 2.0 == 0.0 => lower,
+                Rounding::HalfEven => lower + 1.0,
+                _ if scaled < 0.0 => lower,
+                _ => lower + 1.0
+            }
+        }
+        _ => scaled.round()
+    };
+    rounded / factor
+}
+
+/// A parsed printf-style format, such as "%08d", "%+.3f" or "ID-%s".  Supports the
+/// '-', '+', ' ' and '0' flags, a width, a precision and the d, i, f and s conversions,
+/// with any literal text (and "%%") around the single conversion.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FormatSpec {
+    prefix: String,
+    suffix: String,
+    left_align: bool,
+    plus_sign: bool,
+    space_sign: bool,
+    zero_pad: bool,
+    width: usize,
+    precision: Option<usize>,
+    rounding: Option<Rounding>,
+    pub conversion: Conversion
+}
+
+/// Parses a printf-style format string containing exactly one conversion.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::format::{parse_format, Conversion};
+///
+/// # fn main() {
+/// assert_eq!(parse_format("%08d").unwrap().conversion, Conversion::Integer);
+/// assert_eq!(parse_format("%+.3f").unwrap().conversion, Conversion::Float);
+/// assert!(parse_format("%q").is_err());
+/// assert!(parse_format("%d-%d").is_err());
+/// assert!(parse_format("no conversion").is_err());
+/// # }
+/// ```
+pub fn parse_format(spec: &str) -> Result<FormatSpec, String> {
+    let mut prefix = String::new();
+    let mut chars = spec.chars().peekable();
+
+    // Literal text up to the conversion
+    loop {
+        match chars.next() {
+            Some('%') => {
+                if chars.peek() == Some(&'%') {
+                    chars.next();
+                    prefix.push('%');
+                } else {
+                    break;
+                }
+            }
+            Some(c) => prefix.push(c),
+            None => return Err(format!("Format {} has no conversion, such as %d, %f or %s.", spec))
+        }
+    }
+
+    let mut format = FormatSpec {
+        prefix: prefix,
+        suffix: String::new(),
+        left_align: false,
+        plus_sign: false,
+        space_sign: false,
+        zero_pad: false,
+        width: 0,
+        precision: None,
+        rounding: None,
+        conversion: Conversion::Text
+    };
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            '-' => format.left_align = true,
+            '+' => format.plus_sign = true,
+            ' ' => format.space_sign = true,
+            '0' => format.zero_pad = true,
+            _ => break
+        }
+        chars.next();
+    }
+
+    while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+        format.width = format.width * 10 + d as usize;
+        chars.next();
+    }
+
+    if chars.peek() == Some(&'.') {
+        chars.next();
+        let mut precision = 0;
+        while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+            precision = precision * 10 + d as usize;
+            chars.next();
+        }
+        format.precision = Some(precision);
+    }
+
+    format.conversion = match chars.next() {
+        Some('d') | Some('i') => Conversion::Integer,
+        Some('f') => Conversion::Float,
+        Some('s') => Conversion::Text,
+        Some(c) => return Err(format!("Unsupported conversion %{} in format {}.", c, spec)),
+        None => return Err(format!("Format {} ends before its conversion.", spec))
+    };
+
+    // Literal text after the conversion
+    while let Some(c) = chars.next() {
+        if c == '%' && chars.next() != Some('%') {
+            return Err(format!("Format {} may only contain one conversion.", spec));
+        }
+        format.suffix.push(c);
+    }
+
+    Ok(format)
+}
+
+impl FormatSpec {
+    /// Rounds numbers to the format's precision using the given mode, instead of the
+    /// default which rounds the exact binary value
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// use fourree::format::{parse_format, Rounding};
+    /// use fourree::schema::TypedValue;
+    ///
+    /// # fn main() {
+    /// let half_up = parse_format("%.2f").unwrap().with_rounding(Rounding::HalfUp);
+    /// assert_eq!(half_up.apply(&TypedValue::Float(2.125)), "2.13");
+    /// let half_even = parse_format("%.2f").unwrap().with_rounding(Rounding::HalfEven);
+    /// assert_eq!(half_even.apply(&TypedValue::Float(2.125)), "2.12");
+    /// let floor = parse_format("%d").unwrap().with_rounding(Rounding::Floor);
+    /// assert_eq!(floor.apply(&TypedValue::Float(7.9)), "7");
+    /// # }
+    /// ```
+    pub fn with_rounding(self, rounding: Rounding) -> FormatSpec {
+        FormatSpec { rounding: Some(rounding), ..self }
+    }
+
+    /// Rounds a float to the given number of places, using the rounding mode if one is set
+    fn round(&self, x: f32, precision: usize) -> f64 {
+        match self.rounding {
+            // The shortest form of the f32, so 2.125 stays a tie once widened
+            Some(mode) => round(x.to_string().parse().unwrap_or(x as f64), precision, mode),
+            None if precision == 0 => (x as f64).round(),
+            None => x as f64
+        }
+    }
+
+    /// Renders a value with the format.  Integers may use a float conversion and floats
+    /// an integer one (rounding to the nearest whole number); anything may use %s.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// use fourree::format::parse_format;
+    /// use fourree::schema::TypedValue;
+    ///
+    /// # fn main() {
+    /// assert_eq!(parse_format("%05d").unwrap().apply(&TypedValue::Integer(42)), "00042");
+    /// assert_eq!(parse_format("%+d").unwrap().apply(&TypedValue::Integer(42)), "+42");
+    /// assert_eq!(parse_format("%-5d|").unwrap().apply(&TypedValue::Integer(-7)), "-7   |");
+    /// assert_eq!(parse_format("%06d").unwrap().apply(&TypedValue::Integer(-42)), "-00042");
+    /// assert_eq!(parse_format("%.2f").unwrap().apply(&TypedValue::Float(3.14159)), "3.14");
+    /// assert_eq!(parse_format("%.2f").unwrap().apply(&TypedValue::Float(2.71828)), "2.72");
+    /// assert_eq!(parse_format("%+08.3f").unwrap().apply(&TypedValue::Float(-1.5)), "-001.500");
+    /// assert_eq!(parse_format("ID-%.3s").unwrap().apply(&TypedValue::Text("ABCDE".to_string())),
+    ///            "ID-ABC");
+    /// # }
+    /// ```
+    pub fn apply(&self, value: &TypedValue) -> String {
+        let (sign, body) = match (self.conversion, value) {
+            (Conversion::Integer, &TypedValue::Integer(i)) => {
+                self.signed(i < 0, i.unsigned_abs().to_string())
+            }
+            (Conversion::Integer, &TypedValue::Float(x)) => {
+                let rounded = self.round(x, 0);
+                self.signed(rounded < 0.0, format!("{}", rounded.abs()))
+            }
+            (Conversion::Float, &TypedValue::Integer(i)) => {
+                self.signed(i < 0, format!("{:.*}", self.precision.unwrap_or(6), (i as f64).abs()))
+            }
+            (Conversion::Float, &TypedValue::Float(x)) => {
+                let precision = self.precision.unwrap_or(6);
+                let rounded = self.round(x, precision);
+                self.signed(rounded < 0.0, format!("{:.*}", precision, rounded.abs()))
+            }
+            (_, value) => {
+                let text = value.to_string();
+                match self.precision {
+                    Some(p) => (String::new(), text.chars().take(p).collect()),
+                    None => (String::new(), text)
+                }
+            }
+        };
+
+        let length = sign.chars().count() + body.chars().count();
+        let padding = self.width.saturating_sub(length);
+        let rendered = if self.left_align {
+            format!("{}{}{}", sign, body, " ".repeat(padding))
+        } else if self.zero_pad && self.conversion != Conversion::Text {
+            format!("{}{}{}", sign, "0".repeat(padding), body)
+        } else {
+            format!("{}{}{}", " ".repeat(padding), sign, body)
+        };
+
+        format!("{}{}{}", self.prefix, rendered, self.suffix)
+    }
+
+    fn signed(&self, negative: bool, body: String) -> (String, String) {
+        let sign = if negative {
+            "-"
+        } else if self.plus_sign {
+            "+"
+        } else if self.space_sign {
+            " "
+        } else {
+            ""
+        };
+        (sign.to_string(), body)
+    }
+}
@@ -1,10 +1,20 @@
 extern crate rand;
 extern crate num;
 
+use std::char;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
 use rand::Rng;
-use rand::distributions::{IndependentSample, Range, Normal};
+use rand::distributions::{IndependentSample, Range, Normal, Exp};
+use time;
 use pad::{PadStr, Alignment};
 
+use data::{COMPANY_NAMES, COMPANY_SUFFIXES, PRODUCT_ADJECTIVES, PRODUCT_MATERIALS,
+           PRODUCT_NOUNS, FIRST_NAMES, LAST_NAMES, CYRILLIC_RANGES, GREEK_RANGES, CJK_RANGES,
+           EMOJI_RANGES, LOREM_WORDS};
+
 static UPPERCASE_CHARS: &'static [char] = &['A','B','C','D','E','F','G','H','I','J','K','L','M','N','O','P','Q','R','S','T','U','V','W','X','Y','Z','0','1','2','3','4','5','6','7','8','9'];
 
 /// Convenience struct for representing a date in the form MM/DD/YYYY
@@ -22,15 +32,412 @@ impl ToString for Date {
     }
 }
 
+/// Selects which portion of a company name to generate
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CompanyPart {
+    Name,
+    Suffix,
+    Full
+}
+
+/// Selects which portion of a person's name to generate
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NamePart {
+    First,
+    Last,
+    Full
+}
+
+/// Selects how phone numbers are rendered
+#[derive(Clone, PartialEq, Debug)]
+pub enum PhoneStyle {
+    /// "+<country code><subscriber number>", digits only
+    E164 { country_codes: Vec<String>, min_length: usize, max_length: usize },
+    /// A template such as "(###) ###-####", where each '#' becomes a random digit
+    Mask { format: String }
+}
+
+/// A card network, which determines the leading digits and length of its card numbers
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CardIssuer {
+    Visa,
+    Mastercard,
+    Amex,
+    Discover
+}
+
+impl CardIssuer {
+    /// The prefixes the issuer's numbers start with, one of which is picked per number
+    pub fn prefixes(&self) -> &'static [&'static str] {
+        match *self {
+            CardIssuer::Visa => &["4"],
+            CardIssuer::Mastercard => &["51", "52", "53", "54", "55"],
+            CardIssuer::Amex => &["34", "37"],
+            CardIssuer::Discover => &["6011", "65"]
+        }
+    }
+
+    /// The number of digits in the issuer's numbers, check digit included
+    pub fn length(&self) -> usize {
+        match *self {
+            CardIssuer::Amex => 15,
+            _ => 16
+        }
+    }
+}
+
+/// Selects which coordinate of a point on the globe to generate
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GeoKind {
+    /// Degrees north of the equator, from -90 to 90
+    Latitude,
+    /// Degrees east of the prime meridian, from -180 to 180
+    Longitude
+}
+
+impl GeoKind {
+    /// The smallest and largest values the coordinate can take
+    pub fn bounds(&self) -> (f64, f64) {
+        match *self {
+            GeoKind::Latitude => (-90.0, 90.0),
+            GeoKind::Longitude => (-180.0, 180.0)
+        }
+    }
+}
+
+/// Selects how random bytes are rendered as text
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ByteEncoding {
+    /// Two hexadecimal digits per byte, e.g. "1f8b"
+    Hex { uppercase: bool },
+    /// Standard base64 with padding, e.g. "H4s="
+    Base64
+}
+
+/// Selects how a timestamp is rendered
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimestampFormat {
+    /// Seconds since the Unix epoch, e.g. "1577836800"
+    Epoch,
+    /// An ISO 8601 datetime in UTC, e.g. "2020-01-01T00:00:00Z"
+    Iso8601
+}
+
+/// A block of IP addresses: every address sharing the first `prefix` bits of `base`, as
+/// written in CIDR notation like "10.0.0.0/8"
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IpNetwork {
+    V4 { base: u32, prefix: u32 },
+    V6 { base: u128, prefix: u32 }
+}
+
+impl IpNetwork {
+    /// The number of bits that vary between the addresses of the block
+    pub fn host_bits(&self) -> u32 {
+        match *self {
+            IpNetwork::V4{ prefix, .. } => 32 - prefix,
+            IpNetwork::V6{ prefix, .. } => 128 - prefix
+        }
+    }
+}
+
+/// Parses a block of addresses in CIDR notation, either IPv4 ("10.0.0.0/8") or IPv6
+/// ("fd00::/8").  The prefix may be left off to mean a single address.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::generators::{parse_cidr, IpNetwork};
+///
+/// # fn main() {
+/// assert_eq!(parse_cidr("10.0.0.0/8"), Ok(IpNetwork::V4{ base: 10 << 24, prefix: 8 }));
+/// assert_eq!(parse_cidr("fd00::/8"), Ok(IpNetwork::V6{ base: 0xfd << 120, prefix: 8 }));
+/// assert!(parse_cidr("10.0.0.0/33").is_err());
+/// assert!(parse_cidr("10.0.0/8").is_err());
+/// # }
+/// ```
+pub fn parse_cidr(cidr: &str) -> Result<IpNetwork, String> {
+    let (address, prefix) = match cidr.find('/') {
+        Some(i) => (&cidr[..i], Some(&cidr[i + 1..])),
+        None => (cidr, None)
+    };
+    let invalid = || format!("Invalid CIDR block: {}", cidr);
+    let parse_prefix = |bits: u32| match prefix {
+        Some(p) => p.parse::<u32>().ok().filter(|&p| p <= bits).ok_or(invalid()),
+        None => Ok(bits)
+    };
+
+    if let Ok(v4) = address.parse::<Ipv4Addr>() {
+        Ok(IpNetwork::V4{ base: u32::from(v4), prefix: parse_prefix(32)? })
+    } else if let Ok(v6) = address.parse::<Ipv6Addr>() {
+        Ok(IpNetwork::V6{ base: u128::from(v6), prefix: parse_prefix(128)? })
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Selects how a duration is rendered
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DurationFormat {
+    /// The total number of seconds, e.g. "3661"
+    Seconds,
+    /// Hours, minutes and seconds, e.g. "01:01:01"
+    Hms,
+    /// An ISO 8601 duration, e.g. "PT1H1M1S"
+    Iso8601
+}
+
 /// Generates a random integer from min to max, inclusive
 ///
 /// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_integer;
 ///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
 /// let x = generate_integer(&mut rng, 0, 10000);
+/// assert!(x >= 0 && x <= 10000);
+///
+/// assert_eq!(generate_integer(&mut rng, 7, 7), 7);
+/// assert_eq!(generate_integer(&mut rng, i64::MAX, i64::MAX), i64::MAX);
 ///
+/// let bits: Vec<i64> = (0..100).map(|_| generate_integer(&mut rng, 0, 1)).collect();
+/// assert!(bits.contains(&0) && bits.contains(&1));
+/// # }
+/// ```
 pub fn generate_integer<R: Rng>(rng: &mut R, min: i64, max: i64) -> i64 {
-    let dist = Range::new(min, max);
-    dist.ind_sample(rng)
+    if max < i64::MAX {
+        Range::new(min, max + 1).ind_sample(rng)
+    } else if min > i64::MIN {
+        // max + 1 would overflow, so shift the range down by one instead
+        Range::new(min - 1, max).ind_sample(rng) + 1
+    } else {
+        rng.gen()
+    }
+}
+
+/// The smallest and largest multipliers of `step` whose multiples lie from min to max,
+/// inclusive, or None when no multiple of `step` does.  The step must be positive.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::generators::step_range;
+///
+/// # fn main() {
+/// assert_eq!(step_range(1, 100, 5), Some((1, 20)));
+/// assert_eq!(step_range(-12, -1, 5), Some((-2, -1)));
+/// assert_eq!(step_range(6, 9, 5), None);
+/// # }
+/// ```
+pub fn step_range(min: i64, max: i64, step: i64) -> Option<(i64, i64)> {
+    let low = min.div_euclid(step) + if min.rem_euclid(step) != 0 { 1 } else { 0 };
+    let high = max.div_euclid(step);
+    if low > high {
+        None
+    } else {
+        Some((low, high))
+    }
+}
+
+/// Generates a random multiple of `step` from min to max, inclusive, each equally likely.
+/// A step of 1 draws from every integer in the range, like `generate_integer`.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_stepped_integer;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let prices: Vec<i64> = (0..1000).map(|_| generate_stepped_integer(&mut rng, 1, 100, 5))
+///                                 .collect();
+/// assert!(prices.iter().all(|p| p % 5 == 0 && *p >= 5 && *p <= 100));
+/// assert!(prices.contains(&5) && prices.contains(&100));
+///
+/// assert_eq!(generate_stepped_integer(&mut rng, 7, 7, 1), 7);
+/// # }
+/// ```
+pub fn generate_stepped_integer<R: Rng>(rng: &mut R, min: i64, max: i64, step: i64) -> i64 {
+    if step == 1 {
+        return generate_integer(rng, min, max);
+    }
+    // Field::prepare checks that the range holds a multiple of the step
+    let (low, high) = step_range(min, max, step).unwrap();
+    generate_integer(rng, low, high) * step
+}
+
+/// Largest precision supported by decimal values, which are drawn as whole multiples of
+/// 10^-precision
+pub const DECIMAL_MAX_PRECISION: usize = 15;
+
+/// Scales a bound to whole multiples of 10^-precision, rounding up for the lower bound and
+/// down for the upper.  Bounds within floating point error of a multiple, like 0.07 * 100,
+/// count as that multiple.
+pub fn decimal_bound(x: f64, precision: usize, round_up: bool) -> i64 {
+    let scaled = x * 10f64.powi(precision as i32);
+    let nearest = scaled.round();
+    if (scaled - nearest).abs() < 1e-9 * scaled.abs().max(1.0) {
+        nearest as i64
+    } else if round_up {
+        scaled.ceil() as i64
+    } else {
+        scaled.floor() as i64
+    }
+}
+
+/// Formats a count of 10^-precision units as a decimal with exactly `precision` digits
+/// after the point, and no point at all when `precision` is 0
+pub fn format_decimal(units: i64, precision: usize) -> String {
+    if precision == 0 {
+        return units.to_string();
+    }
+    let scale = 10u64.pow(precision as u32);
+    let magnitude = units.unsigned_abs();
+    format!("{}{}.{:0width$}", if units < 0 { "-" } else { "" }, magnitude / scale,
+            magnitude % scale, width = precision)
+}
+
+/// Generates a decimal drawn uniformly from [min, max], with exactly `precision` digits after
+/// the point.  Every value with that precision in the range is equally likely, and the
+/// range must hold at least one of them.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_decimal;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+///
+/// let price = generate_decimal(&mut rng, 0.5, 99.99, 2);
+/// let (units, cents) = price.split_at(price.find('.').unwrap());
+/// assert_eq!(cents.len(), 3);
+/// let value: f64 = price.parse().unwrap();
+/// assert!(value >= 0.5 && value <= 99.99 && !units.is_empty());
+///
+/// assert!(!generate_decimal(&mut rng, 1.0, 10.0, 0).contains('.'));
+///
+/// // Negative ranges keep their bounds, rather than truncating towards zero
+/// for _ in 0..1000 {
+///     let value: f64 = generate_decimal(&mut rng, -1.25, -1.2, 2).parse().unwrap();
+///     assert!(value >= -1.25 && value <= -1.2);
+/// }
+/// assert_eq!(generate_decimal(&mut rng, -0.004, 0.004, 2), "0.00");
+/// assert_eq!(generate_decimal(&mut rng, -2.5, -1.5, 0), "-2");
+/// # }
+/// ```
+pub fn generate_decimal<R: Rng>(rng: &mut R, min: f64, max: f64, precision: usize) -> String {
+    let low = decimal_bound(min, precision, true);
+    let high = decimal_bound(max, precision, false);
+    let dist = Range::new(low, high + 1);
+    format_decimal(dist.ind_sample(rng), precision)
+}
+
+/// Generates a latitude from min to max degrees inclusive, with `precision` digits after the
+/// point, keeping the range within -90 to 90.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_latitude;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let latitude = generate_latitude(&mut rng, -90.0, 90.0, 6);
+/// assert_eq!(latitude.len() - latitude.find('.').unwrap(), 7);
+///
+/// let value: f64 = generate_latitude(&mut rng, 40.0, 120.0, 2).parse().unwrap();
+/// assert!(value >= 40.0 && value <= 90.0);
+/// # }
+/// ```
+pub fn generate_latitude<R: Rng>(rng: &mut R, min: f64, max: f64, precision: usize) -> String {
+    generate_decimal(rng, min.max(-90.0), max.min(90.0), precision)
+}
+
+/// Generates a longitude from min to max degrees inclusive, with `precision` digits after
+/// the point, keeping the range within -180 to 180.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_longitude;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let value: f64 = generate_longitude(&mut rng, -200.0, -170.0, 6).parse().unwrap();
+/// assert!(value >= -180.0 && value <= -170.0);
+/// # }
+/// ```
+pub fn generate_longitude<R: Rng>(rng: &mut R, min: f64, max: f64, precision: usize) -> String {
+    generate_decimal(rng, min.max(-180.0), max.min(180.0), precision)
+}
+
+/// Generates an amount of money from min to max inclusive, in whole cents, formatted by
+/// `format_currency`.  Every amount in the range is equally likely.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_currency;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let total = generate_currency(&mut rng, 1000.0, 9999.99, "$", true);
+/// assert!(total.starts_with('$') && total.contains(','));
+/// assert_eq!(generate_currency(&mut rng, 1234.56, 1234.56, "€", false), "€1234.56");
+/// # }
+/// ```
+pub fn generate_currency<R: Rng>(rng: &mut R, min: f64, max: f64, symbol: &str,
+                                 grouping: bool) -> String {
+    let low = decimal_bound(min, 2, true);
+    let high = decimal_bound(max, 2, false);
+    let dist = Range::new(low, high + 1);
+    format_currency(dist.ind_sample(rng), symbol, grouping)
+}
+
+/// Formats an amount in cents with two decimal places, preceded by the symbol, and with
+/// commas between each group of three digits when grouping.  Negative amounts put the
+/// sign ahead of the symbol.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// use fourree::generators::format_currency;
+///
+/// # fn main() {
+/// assert_eq!(format_currency(123456, "$", true), "$1,234.56");
+/// assert_eq!(format_currency(123456789, "£", true), "£1,234,567.89");
+/// assert_eq!(format_currency(-100000, "$", true), "-$1,000.00");
+/// assert_eq!(format_currency(5, "$", true), "$0.05");
+/// assert_eq!(format_currency(123456, "", false), "1234.56");
+/// # }
+/// ```
+pub fn format_currency(cents: i64, symbol: &str, grouping: bool) -> String {
+    let magnitude = cents.unsigned_abs();
+    let whole = (magnitude / 100).to_string();
+
+    let mut grouped = String::with_capacity(whole.len() + whole.len() / 3);
+    for (i, digit) in whole.chars().enumerate() {
+        if grouping && i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+
+    format!("{}{}{}.{:02}", if cents < 0 { "-" } else { "" }, symbol, grouped, magnitude % 100)
 }
 
 /// Generates a random string of 'length'.
@@ -57,26 +464,414 @@ pub fn generate_string<R: Rng>(rng: &mut R, length: usize) -> String {
     from_utf8(&bytes).unwrap().to_string()*/
 }
 
-/// Generates an integer from a normal (Gaussian) distribution
+/// The characters a string generator draws from
+#[derive(Clone, PartialEq, Debug)]
+pub enum Charset {
+    /// ASCII uppercase letters, the original behaviour
+    Latin,
+    /// ASCII lowercase letters
+    Lower,
+    /// ASCII letters of either case
+    Alpha,
+    /// ASCII letters and digits
+    Alphanumeric,
+    /// ASCII digits
+    Numeric,
+    Cyrillic,
+    Greek,
+    /// CJK Unified Ideographs
+    Cjk,
+    /// Emoticons
+    Emoji,
+    /// An explicit set of distinct characters
+    Custom(Vec<char>)
+}
+
+impl Charset {
+    /// The inclusive code point ranges of the charset, which are empty for a custom one
+    pub fn ranges(&self) -> &'static [(u32, u32)] {
+        match *self {
+            Charset::Latin => &[(0x41, 0x5A)],
+            Charset::Lower => &[(0x61, 0x7A)],
+            Charset::Alpha => &[(0x41, 0x5A), (0x61, 0x7A)],
+            Charset::Alphanumeric => &[(0x30, 0x39), (0x41, 0x5A), (0x61, 0x7A)],
+            Charset::Numeric => &[(0x30, 0x39)],
+            Charset::Cyrillic => CYRILLIC_RANGES,
+            Charset::Greek => GREEK_RANGES,
+            Charset::Cjk => CJK_RANGES,
+            Charset::Emoji => EMOJI_RANGES,
+            Charset::Custom(_) => &[]
+        }
+    }
+
+    /// The number of characters in the charset
+    pub fn size(&self) -> u64 {
+        match *self {
+            Charset::Custom(ref chars) => chars.len() as u64,
+            _ => self.ranges().iter().map(|&(low, high)| (high - low + 1) as u64).sum()
+        }
+    }
+}
+
+/// Generates a random string of `length` characters from the charset.  Outside of the
+/// ASCII charsets, every character takes more than one byte in UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::{generate_charset_string, Charset};
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let text = generate_charset_string(&mut rng, 12, &Charset::Cjk);
+/// assert_eq!(text.chars().count(), 12);
+/// assert!(text.chars().all(|c| c.len_utf8() > 1));
+/// assert_eq!(text.len(), 36);
+///
+/// let token = generate_charset_string(&mut rng, 32, &Charset::Alphanumeric);
+/// assert!(token.len() == 32 && token.chars().all(|c| c.is_ascii_alphanumeric()));
+///
+/// let pin = generate_charset_string(&mut rng, 4, &Charset::Numeric);
+/// assert!(pin.len() == 4 && pin.chars().all(|c| c.is_ascii_digit()));
+///
+/// let hex_digits = Charset::Custom("0123456789abcdef".chars().collect());
+/// let hex = generate_charset_string(&mut rng, 8, &hex_digits);
+/// assert!(hex.len() == 8 && hex.chars().all(|c| c.is_digit(16) && !c.is_uppercase()));
+/// # }
+/// ```
+pub fn generate_charset_string<R: Rng>(rng: &mut R, length: usize, charset: &Charset) -> String {
+    match *charset {
+        Charset::Latin => return generate_string(rng, length),
+        Charset::Custom(ref chars) => {
+            return (0..length).map(|_| *rng.choose(chars).unwrap()).collect()
+        },
+        _ => {}
+    }
+
+    let ranges = charset.ranges();
+    let dist = Range::new(0, charset.size() as u32);
+    let mut result = String::with_capacity(length * 4);
+
+    for _ in 0..length {
+        let mut index = dist.ind_sample(rng);
+        for &(low, high) in ranges.iter() {
+            let size = high - low + 1;
+            if index < size {
+                // Every range holds only assigned, non-surrogate code points
+                result.push(char::from_u32(low + index).unwrap());
+                break;
+            }
+            index -= size;
+        }
+    }
+    result
+}
+
+/// Generates `length` random bytes
 ///
 /// # Examples
 ///
-/// let x = generate_gauss(&mut rng, 10, 2);
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_bytes;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// assert_eq!(generate_bytes(&mut rng, 32).len(), 32);
+/// # }
+/// ```
+pub fn generate_bytes<R: Rng>(rng: &mut R, length: usize) -> Vec<u8> {
+    (0..length).map(|_| rng.gen::<u8>()).collect()
+}
+
+/// Renders bytes as text.  Hex takes two characters per byte, and base64 four characters
+/// for every three bytes or part thereof.
 ///
-pub fn generate_gauss<R: Rng>(rng: &mut R, mean: i32, std_dev: i32) -> i32 {
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// use fourree::generators::{encode_bytes, ByteEncoding};
+///
+/// # fn main() {
+/// let bytes = [0x1f, 0x8b, 0x08];
+/// assert_eq!(encode_bytes(&bytes, ByteEncoding::Hex{ uppercase: false }), "1f8b08");
+/// assert_eq!(encode_bytes(&bytes, ByteEncoding::Hex{ uppercase: true }), "1F8B08");
+/// assert_eq!(encode_bytes(&bytes, ByteEncoding::Base64), "H4sI");
+/// assert_eq!(encode_bytes(b"fo", ByteEncoding::Base64), "Zm8=");
+/// assert_eq!(encode_bytes(b"f", ByteEncoding::Base64), "Zg==");
+/// # }
+/// ```
+pub fn encode_bytes(bytes: &[u8], encoding: ByteEncoding) -> String {
+    match encoding {
+        ByteEncoding::Hex{ uppercase } => {
+            let digits: &[u8] = if uppercase { b"0123456789ABCDEF" } else { b"0123456789abcdef" };
+            let mut output = String::with_capacity(bytes.len() * 2);
+            for byte in bytes {
+                output.push(digits[(byte >> 4) as usize] as char);
+                output.push(digits[(byte & 0xf) as usize] as char);
+            }
+            output
+        }
+        ByteEncoding::Base64 => {
+            let alphabet = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+            let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+            for chunk in bytes.chunks(3) {
+                let group = chunk.iter().enumerate()
+                    .fold(0u32, |acc, (i, &byte)| acc | ((byte as u32) << (16 - 8 * i)));
+                for i in 0..4 {
+                    if i <= chunk.len() {
+                        output.push(alphabet[((group >> (18 - 6 * i)) & 0x3f) as usize] as char);
+                    } else {
+                        output.push('=');
+                    }
+                }
+            }
+            output
+        }
+    }
+}
+
+/// Generates an integer from a normal (Gaussian) distribution.  Values beyond `min` or
+/// `max` are moved onto that bound, and a bound of None leaves that side unbounded.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_gauss;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// for _ in 0..10000 {
+///     let age = generate_gauss(&mut rng, 30, 40, Some(0), Some(120));
+///     assert!(age >= 0 && age <= 120);
+/// }
+/// # }
+/// ```
+pub fn generate_gauss<R: Rng>(rng: &mut R, mean: i32, std_dev: i32,
+                              min: Option<i32>, max: Option<i32>) -> i32 {
     let dist = Normal::new(mean as f64, std_dev as f64);
-    dist.ind_sample(rng) as i32
+    let value = dist.ind_sample(rng) as i32;
+    let value = min.map_or(value, |min| value.max(min));
+    max.map_or(value, |max| value.min(max))
 }
 
-/// Generates an integer from a normal (Gaussian) distribution
+/// Generates a floating point number from a normal (Gaussian) distribution, bounded by
+/// `min` and `max` as in `generate_gauss`.
 ///
 /// # Examples
 ///
-/// let x = generate_gauss(&mut rng, 10, 2);
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_gauss_f32;
 ///
-pub fn generate_gauss_f32<R: Rng>(rng: &mut R, mean: f32, std_dev: f32) -> f32 {
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// for _ in 0..10000 {
+///     let price = generate_gauss_f32(&mut rng, 1.5, 10.0, Some(0.01), None);
+///     assert!(price >= 0.01);
+/// }
+/// # }
+/// ```
+pub fn generate_gauss_f32<R: Rng>(rng: &mut R, mean: f32, std_dev: f32,
+                                  min: Option<f32>, max: Option<f32>) -> f32 {
     let dist = Normal::new(mean as f64, std_dev as f64);
-    dist.ind_sample(rng) as f32
+    let value = dist.ind_sample(rng) as f32;
+    let value = min.map_or(value, |min| value.max(min));
+    max.map_or(value, |max| value.min(max))
+}
+
+/// Generates a pair of values from a bivariate normal distribution with the given means,
+/// standard deviations and correlation coefficient.  Two independent standard normal
+/// samples are combined using the Cholesky factor of the correlation matrix.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_bivariate_normal;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// // A perfect correlation moves both values together
+/// let (x, y) = generate_bivariate_normal(&mut rng, (0.0, 10.0), (1.0, 2.0), 1.0);
+/// assert!((y - (10.0 + 2.0 * x)).abs() < 1e-9);
+/// # }
+/// ```
+pub fn generate_bivariate_normal<R: Rng>(rng: &mut R, means: (f64, f64), std_devs: (f64, f64),
+                                         correlation: f64) -> (f64, f64) {
+    let standard = Normal::new(0.0, 1.0);
+    let z1 = standard.ind_sample(rng);
+    let z2 = standard.ind_sample(rng);
+
+    let x = means.0 + std_devs.0 * z1;
+    let y = means.1 + std_devs.1 * (correlation * z1 + (1.0 - correlation * correlation).sqrt() * z2);
+    (x, y)
+}
+
+/// Returns the next value of a cycle from start to end inclusive, wrapping back to start.
+/// The counter holds how many values have been produced, so the cycle can be shared by
+/// threads.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// use std::sync::atomic::AtomicU64;
+/// use fourree::generators::generate_cycle;
+///
+/// # fn main() {
+/// let counter = AtomicU64::new(0);
+/// let values: Vec<i64> = (0..6).map(|_| generate_cycle(&counter, 0, 2)).collect();
+/// assert_eq!(values, vec![0, 1, 2, 0, 1, 2]);
+/// # }
+/// ```
+pub fn generate_cycle(counter: &AtomicU64, start: i64, end: i64) -> i64 {
+    let count = counter.fetch_add(1, Ordering::Relaxed);
+    // A cycle over every i64 has a span too large for a u64, and never needs to wrap
+    match (end.wrapping_sub(start) as u64).checked_add(1) {
+        Some(span) => start.wrapping_add((count % span) as i64),
+        None => start.wrapping_add(count as i64)
+    }
+}
+
+/// Generates the next event time, in milliseconds since the epoch, for the given key.  Each
+/// key's events start one gap after `start_ms` and increase independently of other keys,
+/// with exponentially distributed gaps averaging `avg_gap_ms` (and never less than 1ms).
+/// The last time of every key is kept in `last_times`, which is shared by all threads.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use std::collections::HashMap;
+/// use std::sync::Mutex;
+/// use fourree::generators::generate_event_time;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let last_times = Mutex::new(HashMap::new());
+///
+/// let first = generate_event_time(&mut rng, &last_times, "alice", 1000, 50);
+/// let other = generate_event_time(&mut rng, &last_times, "bob", 1000, 50);
+/// let second = generate_event_time(&mut rng, &last_times, "alice", 1000, 50);
+/// assert!(first > 1000 && other > 1000);
+/// assert!(second > first);
+/// # }
+/// ```
+pub fn generate_event_time<R: Rng>(rng: &mut R, last_times: &Mutex<HashMap<String, i64>>,
+                                   key: &str, start_ms: i64, avg_gap_ms: u64) -> i64 {
+    let gap = Exp::new(1.0 / avg_gap_ms as f64).ind_sample(rng).round().max(1.0) as i64;
+
+    let mut last_times = last_times.lock().unwrap();
+    let time = last_times.get(key).cloned().unwrap_or(start_ms) + gap;
+    last_times.insert(key.to_string(), time);
+    time
+}
+
+/// Formats milliseconds since the epoch as a UTC timestamp with millisecond precision
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// use fourree::generators::format_event_time;
+///
+/// # fn main() {
+/// assert_eq!(format_event_time(1500000000123), "2017-07-14 02:40:00.123");
+/// # }
+/// ```
+pub fn format_event_time(ms: i64) -> String {
+    let seconds = time::at_utc(time::Timespec::new(ms.div_euclid(1000), 0));
+    let formatted = time::strftime("%Y-%m-%d %H:%M:%S", &seconds).unwrap();
+    format!("{}.{:03}", formatted, ms.rem_euclid(1000))
+}
+
+/// Generates the next value of a sequence, start + step * n for the nth call.  The counter
+/// is shared, so values stay unique across threads, but threads may write them out of
+/// order.  Values wrap around once they pass the limits of an i64.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// use std::sync::atomic::AtomicU64;
+/// use fourree::generators::generate_sequence;
+///
+/// # fn main() {
+/// let counter = AtomicU64::new(0);
+/// let values: Vec<i64> = (0..3).map(|_| generate_sequence(&counter, 10, -5)).collect();
+/// assert_eq!(values, vec![10, 5, 0]);
+/// # }
+/// ```
+pub fn generate_sequence(counter: &AtomicU64, start: i64, step: i64) -> i64 {
+    let count = counter.fetch_add(1, Ordering::Relaxed);
+    start.wrapping_add(step.wrapping_mul(count as i64))
+}
+
+/// Generates the next timestamp of a time series, in seconds since the epoch: the nth call
+/// returns start_epoch + step_seconds * n, moved by up to jitter_seconds either way.  Like a
+/// sequence, the counter is shared, so threads may write the timestamps out of order.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use std::sync::atomic::AtomicU64;
+/// use fourree::generators::generate_timestamp;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let counter = AtomicU64::new(0);
+/// let times: Vec<i64> = (0..3).map(|_| generate_timestamp(&mut rng, &counter, 100, 60, 0))
+///     .collect();
+/// assert_eq!(times, vec![100, 160, 220]);
+///
+/// let jittered = generate_timestamp(&mut rng, &counter, 100, 60, 5);
+/// assert!(jittered >= 275 && jittered <= 285);
+/// # }
+/// ```
+pub fn generate_timestamp<R: Rng>(rng: &mut R, counter: &AtomicU64, start_epoch: i64,
+                                  step_seconds: i64, jitter_seconds: i64) -> i64 {
+    let time = generate_sequence(counter, start_epoch, step_seconds);
+    // Only draw when there is jitter, so jitter-free series leave the rng untouched
+    if jitter_seconds > 0 {
+        time.wrapping_add(rng.gen_range(-jitter_seconds, jitter_seconds + 1))
+    } else {
+        time
+    }
+}
+
+/// Formats seconds since the epoch as a timestamp
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// use fourree::generators::{format_timestamp, TimestampFormat};
+///
+/// # fn main() {
+/// assert_eq!(format_timestamp(1500000000, TimestampFormat::Epoch), "1500000000");
+/// assert_eq!(format_timestamp(1500000000, TimestampFormat::Iso8601), "2017-07-14T02:40:00Z");
+/// # }
+/// ```
+pub fn format_timestamp(seconds: i64, format: TimestampFormat) -> String {
+    match format {
+        TimestampFormat::Epoch => seconds.to_string(),
+        TimestampFormat::Iso8601 => {
+            let time = time::at_utc(time::Timespec::new(seconds, 0));
+            time::strftime("%Y-%m-%dT%H:%M:%SZ", &time).unwrap()
+        }
+    }
 }
 
 /// Generates a date (as a string for now)
@@ -107,25 +902,733 @@ pub fn generate_date<R: Rng>(rng: &mut R) -> Date {
     }
 }
 
-/// Generate a value from an array of chars
+/// Returns the number of days in the month, accounting for leap years
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+/// Converts a calendar date to the number of days since 1970-01-01
 ///
 /// # Examples
 ///
-/// let x = vec!["A", "B", "C"];
-/// let y = generate_choice(&mut rng, &x, 1);
+/// ```
+/// # extern crate fourree;
+/// use fourree::generators::days_from_civil;
 ///
-pub fn generate_choice<R: Rng, T: ToString>(
-    rng: &mut R, choices: &[T],
-    choice_length: usize, length: usize
-) -> String {
-    let num_choices = if length > choice_length {
+/// # fn main() {
+/// assert_eq!(days_from_civil(1970, 1, 1), 0);
+/// assert_eq!(days_from_civil(2000, 3, 1) - days_from_civil(2000, 2, 28), 2);
+/// assert_eq!(days_from_civil(1969, 12, 31), -1);
+/// # }
+/// ```
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    // Months counted from March, so the leap day falls at the end of the year
+    let shifted_month = if month > 2 { month as i64 - 3 } else { month as i64 + 9 };
+    let day_of_year = (153 * shifted_month + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Converts a number of days since 1970-01-01 back into a calendar date
+fn civil_from_days(days: i64) -> Date {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    Date {
+        day: (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u8,
+        month: month as u8,
+        year: year as u16
+    }
+}
+
+/// Generates a date uniformly from the inclusive range of days, each given as the number
+/// of days since 1970-01-01
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::{days_from_civil, generate_date_between};
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let leap_day = days_from_civil(2004, 2, 29);
+/// assert_eq!(generate_date_between(&mut rng, leap_day, leap_day).to_string(), "02/29/2004");
+///
+/// let (min, max) = (days_from_civil(1950, 1, 1), days_from_civil(2005, 12, 31));
+/// for _ in 0..1000 {
+///     let date = generate_date_between(&mut rng, min, max).to_string();
+///     let year: u32 = date[6..].parse().unwrap();
+///     assert!(year >= 1950 && year <= 2005);
+/// }
+/// # }
+/// ```
+pub fn generate_date_between<R: Rng>(rng: &mut R, min: i64, max: i64) -> Date {
+    civil_from_days(Range::new(min, max + 1).ind_sample(rng))
+}
+
+/// Generates a date and time of day as "YYYY-MM-DD HH:MM:SS".  The date is drawn like
+/// `generate_date`'s, and the time uniformly from every second of the day.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_datetime;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// for _ in 0..1000 {
+///     let datetime = generate_datetime(&mut rng);
+///     assert_eq!(datetime.len(), 19);
+///     let parts: Vec<u32> = datetime.split(|c| c == '-' || c == ' ' || c == ':')
+///                                   .map(|p| p.parse().unwrap())
+///                                   .collect();
+///     assert!(parts[0] > 1900 && parts[0] <= 2016);
+///     assert!(parts[1] >= 1 && parts[1] <= 12 && parts[2] >= 1 && parts[2] <= 31);
+///     assert!(parts[3] < 24 && parts[4] < 60 && parts[5] < 60);
+/// }
+/// # }
+/// ```
+pub fn generate_datetime<R: Rng>(rng: &mut R) -> String {
+    let date = generate_date(rng);
+    let second_of_day = Range::new(0, 24 * 60 * 60).ind_sample(rng);
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", date.year, date.month, date.day,
+            second_of_day / 3600, second_of_day / 60 % 60, second_of_day % 60)
+}
+
+/// Returns how many choices of `choice_length` characters fit in a value of `length`
+/// characters, and so how many picks a choice field makes per value.  At least one pick is
+/// always made.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::generators::choice_count;
+///
+/// # fn main() {
+/// assert_eq!(choice_count(2, 6), 3);
+/// assert_eq!(choice_count(4, 2), 1);
+/// # }
+/// ```
+pub fn choice_count(choice_length: usize, length: usize) -> usize {
+    if length > choice_length {
         length / choice_length
     } else {
         1
-    };
-    let mut output = String::with_capacity(length);
-    for _ in 0..num_choices {
+    }
+}
+
+/// Generate a value by concatenating `count` picks from a list of choices
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_choice;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let x = vec!["A", "B", "C"];
+/// let y = generate_choice(&mut rng, &x, 1);
+/// assert!(x.contains(&y.as_str()));
+///
+/// let z = generate_choice(&mut rng, &x, 4);
+/// assert_eq!(z.len(), 4);
+/// assert!(z.chars().all(|c| "ABC".contains(c)));
+/// # }
+/// ```
+pub fn generate_choice<R: Rng, T: ToString>(rng: &mut R, choices: &[T], count: usize) -> String {
+    let mut output = String::new();
+    for _ in 0..count {
         output.push_str(&rng.choose(choices).unwrap().to_string());
     }
     output
 }
+
+/// Generate a value from a list of choices, where each choice is picked with the chance
+/// given by its cumulative weight: `cumulative[i]` is the total normalized weight of the
+/// choices up to and including i, so the last one is 1.0.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_weighted_choice;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let choices = vec!["US".to_string(), "CA".to_string(), "MX".to_string()];
+/// let cumulative = vec![0.8, 0.95, 1.0];
+///
+/// let us = (0..10000)
+///     .filter(|_| generate_weighted_choice(&mut rng, &choices, &cumulative, 1) == "US")
+///     .count();
+/// assert!(us > 7700 && us < 8300);
+/// assert_eq!(generate_weighted_choice(&mut rng, &choices, &[0.0, 0.0, 1.0], 3), "MXMXMX");
+/// # }
+/// ```
+pub fn generate_weighted_choice<R: Rng>(
+    rng: &mut R, choices: &[String], cumulative: &[f64], count: usize
+) -> String {
+    let last = choices.len() - 1;
+    let mut output = String::new();
+    for _ in 0..count {
+        let target = rng.gen::<f64>();
+        // Rounding can leave the last cumulative weight a hair under 1.0
+        let index = cumulative.partition_point(|&c| c <= target).min(last);
+        output.push_str(&choices[index]);
+    }
+    output
+}
+
+/// Picks an index at random, with each index's chance proportional to its weight.  The
+/// weights must not be negative, and at least one must be positive.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::weighted_index;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// assert_eq!(weighted_index(&mut rng, &[0.0, 2.0, 0.0]), 1);
+/// # }
+/// ```
+pub fn weighted_index<R: Rng>(rng: &mut R, weights: &[f64]) -> usize {
+    let total: f64 = weights.iter().sum();
+    let mut target = rng.gen::<f64>() * total;
+
+    for (i, weight) in weights.iter().enumerate() {
+        if target < *weight {
+            return i;
+        }
+        target -= *weight;
+    }
+
+    // Rounding can leave target just past the end, so settle on the last positive weight
+    weights.iter().rposition(|w| *w > 0.0).unwrap_or(0)
+}
+
+/// Generates a gender label, uniformly or according to the weights when given
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_gender;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let labels = vec!["F".to_string(), "M".to_string()];
+/// assert!(labels.contains(&generate_gender(&mut rng, &labels, None)));
+///
+/// // Weights of 3:1 produce "F" about 75% of the time
+/// let weights = vec![3.0, 1.0];
+/// let women = (0..10000).filter(|_| generate_gender(&mut rng, &labels, Some(&weights)) == "F")
+///                       .count();
+/// assert!(women > 7000 && women < 8000);
+/// # }
+/// ```
+pub fn generate_gender<R: Rng>(rng: &mut R, labels: &[String], weights: Option<&[f64]>) -> String {
+    match weights {
+        Some(w) => labels[weighted_index(rng, w)].clone(),
+        None => rng.choose(labels).unwrap().clone()
+    }
+}
+
+/// Generates a person's first name, last name, or both as "First Last"
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::data::{FIRST_NAMES, LAST_NAMES};
+/// use fourree::generators::{generate_name, NamePart};
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// assert!(FIRST_NAMES.contains(&generate_name(&mut rng, NamePart::First).as_str()));
+///
+/// let name = generate_name(&mut rng, NamePart::Full);
+/// let parts: Vec<&str> = name.split(' ').collect();
+/// assert_eq!(parts.len(), 2);
+/// assert!(FIRST_NAMES.contains(&parts[0]) && LAST_NAMES.contains(&parts[1]));
+/// # }
+/// ```
+pub fn generate_name<R: Rng>(rng: &mut R, part: NamePart) -> String {
+    match part {
+        NamePart::First => rng.choose(FIRST_NAMES).unwrap().to_string(),
+        NamePart::Last => rng.choose(LAST_NAMES).unwrap().to_string(),
+        NamePart::Full => format!("{} {}", rng.choose(FIRST_NAMES).unwrap(),
+                                  rng.choose(LAST_NAMES).unwrap())
+    }
+}
+
+/// Generates a company name, its legal suffix (e.g. "Inc"), or both
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::data::COMPANY_SUFFIXES;
+/// use fourree::generators::{generate_company, CompanyPart};
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let company = generate_company(&mut rng, CompanyPart::Full);
+/// assert!(COMPANY_SUFFIXES.iter().any(|s| company.ends_with(&format!(" {}", s))));
+/// # }
+/// ```
+pub fn generate_company<R: Rng>(rng: &mut R, kind: CompanyPart) -> String {
+    let name = rng.choose(COMPANY_NAMES).unwrap();
+    let suffix = rng.choose(COMPANY_SUFFIXES).unwrap();
+
+    match kind {
+        CompanyPart::Name => name.to_string(),
+        CompanyPart::Suffix => suffix.to_string(),
+        CompanyPart::Full => format!("{} {}", name, suffix)
+    }
+}
+
+/// Generates a product name in the form "Adjective Material Noun"
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_product;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let product = generate_product(&mut rng);
+/// assert!(!product.is_empty());
+/// # }
+/// ```
+pub fn generate_product<R: Rng>(rng: &mut R) -> String {
+    format!("{} {} {}",
+            rng.choose(PRODUCT_ADJECTIVES).unwrap(),
+            rng.choose(PRODUCT_MATERIALS).unwrap(),
+            rng.choose(PRODUCT_NOUNS).unwrap())
+}
+
+/// Generates a sentence of min_words to max_words lorem ipsum words, inclusive, with the
+/// first word capitalized and a closing period
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_text;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let text = generate_text(&mut rng, 3, 5);
+/// let words = text.split(' ').count();
+/// assert!(words >= 3 && words <= 5);
+/// assert!(text.chars().next().unwrap().is_uppercase());
+/// assert!(text.ends_with('.'));
+/// # }
+/// ```
+pub fn generate_text<R: Rng>(rng: &mut R, min_words: usize, max_words: usize) -> String {
+    let count = rng.gen_range(min_words, max_words + 1);
+    let mut text = String::new();
+
+    for i in 0..count {
+        let word = rng.choose(LOREM_WORDS).unwrap();
+        if i == 0 {
+            let mut chars = word.chars();
+            text.extend(chars.next().unwrap().to_uppercase());
+            text.push_str(chars.as_str());
+        } else {
+            text.push(' ');
+            text.push_str(word);
+        }
+    }
+    text.push('.');
+    text
+}
+
+/// Characters used in the local part of generated email addresses
+static EMAIL_LOCAL_CHARS: &'static [char] = &['a','b','c','d','e','f','g','h','i','j','k','l','m','n','o','p','q','r','s','t','u','v','w','x','y','z','0','1','2','3','4','5','6','7','8','9'];
+
+/// Number of characters that may appear in the local part of an email address
+pub const EMAIL_LOCAL_CHARS_COUNT: u64 = 36;
+
+/// Generates an email address: a lowercase alphanumeric local part of between min_length
+/// and max_length characters, an '@', and one of the domains.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_email;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let domains = vec!["example.com".to_string()];
+/// let email = generate_email(&mut rng, &domains, 3, 8);
+///
+/// let (local, domain) = email.split_at(email.find('@').unwrap());
+/// assert_eq!(domain, "@example.com");
+/// assert!(local.len() >= 3 && local.len() <= 8);
+/// assert!(local.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()));
+/// # }
+/// ```
+pub fn generate_email<R: Rng>(rng: &mut R, domains: &[String],
+                              min_length: usize, max_length: usize) -> String {
+    let length = Range::new(min_length, max_length + 1).ind_sample(rng);
+    let mut email = String::with_capacity(length + 20);
+    for _ in 0..length {
+        email.push(*rng.choose(EMAIL_LOCAL_CHARS).unwrap());
+    }
+    email.push('@');
+    email.push_str(rng.choose(domains).unwrap());
+    email
+}
+
+/// Generates a phone number in the given style
+pub fn generate_phone<R: Rng>(rng: &mut R, style: &PhoneStyle) -> String {
+    match *style {
+        PhoneStyle::E164{ ref country_codes, min_length, max_length } => {
+            generate_e164(rng, country_codes, min_length, max_length)
+        }
+        PhoneStyle::Mask{ ref format } => generate_masked_phone(rng, format)
+    }
+}
+
+/// Generates a phone number from a mask, replacing each '#' with a random digit and
+/// copying every other character as is.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_masked_phone;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let phone = generate_masked_phone(&mut rng, "(###) ###-####");
+///
+/// assert_eq!(phone.len(), 14);
+/// assert_eq!(&phone[0..1], "(");
+/// assert_eq!(&phone[4..6], ") ");
+/// assert_eq!(&phone[9..10], "-");
+/// assert!(phone.chars().filter(|c| c.is_digit(10)).count() == 10);
+/// # }
+/// ```
+pub fn generate_masked_phone<R: Rng>(rng: &mut R, mask: &str) -> String {
+    let digits = Range::new(0u8, 10);
+    mask.chars().map(|c| {
+        if c == '#' { (b'0' + digits.ind_sample(rng)) as char } else { c }
+    }).collect()
+}
+
+/// Generates an E.164 international number: a '+', one of the country codes, then a
+/// subscriber number of between min_length and max_length digits.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_e164;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let codes = vec!["1".to_string(), "44".to_string()];
+/// let phone = generate_e164(&mut rng, &codes, 9, 10);
+///
+/// assert!(phone.starts_with('+'));
+/// assert!(phone[1..].chars().all(|c| c.is_digit(10)));
+/// assert!(phone.len() >= 1 + 1 + 9 && phone.len() <= 1 + 2 + 10);
+/// # }
+/// ```
+pub fn generate_e164<R: Rng>(rng: &mut R, country_codes: &[String],
+                             min_length: usize, max_length: usize) -> String {
+    let country_code = rng.choose(country_codes).unwrap();
+    let length = Range::new(min_length, max_length + 1).ind_sample(rng);
+    let digits = Range::new(0, 10);
+
+    let mut output = String::with_capacity(1 + country_code.len() + length);
+    output.push('+');
+    output.push_str(country_code);
+    for _ in 0..length {
+        output.push_str(&digits.ind_sample(rng).to_string());
+    }
+    output
+}
+
+/// Generates a card number of `length` digits: the prefix, random digits, then a check
+/// digit chosen so that the whole number passes the Luhn checksum.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_credit_card;
+///
+/// # fn main() {
+/// fn luhn_valid(number: &str) -> bool {
+///     let sum: u32 = number.chars().rev().enumerate().map(|(i, c)| {
+///         let d = c.to_digit(10).unwrap();
+///         if i % 2 == 1 { if d * 2 > 9 { d * 2 - 9 } else { d * 2 } } else { d }
+///     }).sum();
+///     sum % 10 == 0
+/// }
+///
+/// let mut rng = rand::thread_rng();
+/// for _ in 0..1000 {
+///     let card = generate_credit_card(&mut rng, "4", 16);
+///     assert_eq!(card.len(), 16);
+///     assert!(card.starts_with('4'));
+///     assert!(luhn_valid(&card));
+/// }
+/// assert!(luhn_valid(&generate_credit_card(&mut rng, "37", 15)));
+/// # }
+/// ```
+pub fn generate_credit_card<R: Rng>(rng: &mut R, prefix: &str, length: usize) -> String {
+    let digits = Range::new(0u8, 10);
+    let mut number = String::with_capacity(length);
+    number.push_str(prefix);
+    while number.len() + 1 < length {
+        number.push((b'0' + digits.ind_sample(rng)) as char);
+    }
+
+    // The check digit will be rightmost, so doubling starts from the last digit so far
+    let sum: u32 = number.bytes().rev().enumerate().map(|(i, b)| {
+        let digit = (b - b'0') as u32;
+        if i % 2 == 0 {
+            let doubled = digit * 2;
+            if doubled > 9 { doubled - 9 } else { doubled }
+        } else {
+            digit
+        }
+    }).sum();
+    number.push((b'0' + ((10 - sum % 10) % 10) as u8) as char);
+    number
+}
+
+/// Generates an IP address within the network, in the usual form for its version
+pub fn generate_ip<R: Rng>(rng: &mut R, network: &IpNetwork) -> String {
+    match *network {
+        IpNetwork::V4{ base, prefix } => generate_ipv4(rng, base, prefix),
+        IpNetwork::V6{ base, prefix } => generate_ipv6(rng, base, prefix)
+    }
+}
+
+/// Generates an IPv4 address in dotted-quad form, sharing the first `prefix` bits of `base`
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use std::net::Ipv4Addr;
+/// use fourree::generators::generate_ipv4;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let address: Ipv4Addr = generate_ipv4(&mut rng, 0, 0).parse().unwrap();
+///
+/// let base = u32::from(Ipv4Addr::new(192, 168, 1, 0));
+/// let address: Ipv4Addr = generate_ipv4(&mut rng, base, 24).parse().unwrap();
+/// assert_eq!(&address.octets()[..3], &[192, 168, 1]);
+/// # }
+/// ```
+pub fn generate_ipv4<R: Rng>(rng: &mut R, base: u32, prefix: u32) -> String {
+    let mask = if prefix == 0 { 0 } else { !0u32 << (32 - prefix) };
+    let address = (base & mask) | (rng.gen::<u32>() & !mask);
+    Ipv4Addr::from(address).to_string()
+}
+
+/// Generates an IPv6 address in compressed colon-hex form, sharing the first `prefix` bits
+/// of `base`
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use std::net::Ipv6Addr;
+/// use fourree::generators::generate_ipv6;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let base = u128::from("2001:db8::".parse::<Ipv6Addr>().unwrap());
+/// let address: Ipv6Addr = generate_ipv6(&mut rng, base, 32).parse().unwrap();
+/// assert_eq!(&address.segments()[..2], &[0x2001, 0xdb8]);
+///
+/// assert_eq!(generate_ipv6(&mut rng, base, 128), "2001:db8::");
+/// # }
+/// ```
+pub fn generate_ipv6<R: Rng>(rng: &mut R, base: u128, prefix: u32) -> String {
+    let mask = if prefix == 0 { 0 } else { !0u128 << (128 - prefix) };
+    let random = (rng.gen::<u64>() as u128) << 64 | rng.gen::<u64>() as u128;
+    let address = (base & mask) | (random & !mask);
+    Ipv6Addr::from(address).to_string()
+}
+
+/// Generates a MAC address of six hex octets joined by the separator.  When `local` is set
+/// the address is a locally administered unicast one, as assigned to virtual machines and
+/// containers, rather than one from a manufacturer's block.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::generators::generate_mac;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let mac = generate_mac(&mut rng, ":", false, true);
+/// let octets: Vec<&str> = mac.split(':').collect();
+/// assert_eq!(octets.len(), 6);
+/// assert!(octets.iter().all(|o| o.len() == 2 && !o.chars().any(|c| c.is_uppercase())));
+/// assert_eq!(u8::from_str_radix(octets[0], 16).unwrap() & 0x03, 0x02);
+///
+/// assert_eq!(generate_mac(&mut rng, "", true, false).len(), 12);
+/// # }
+/// ```
+pub fn generate_mac<R: Rng>(rng: &mut R, separator: &str, uppercase: bool,
+                            local: bool) -> String {
+    let mut octets = generate_bytes(rng, 6);
+    if local {
+        octets[0] = (octets[0] | 0x02) & !0x01;
+    }
+    let encoding = ByteEncoding::Hex{ uppercase: uppercase };
+    octets.iter()
+        .map(|octet| encode_bytes(&[*octet], encoding))
+        .collect::<Vec<String>>()
+        .join(separator)
+}
+
+/// Generates a duration in seconds, from min_seconds to max_seconds inclusive
+pub fn generate_duration<R: Rng>(rng: &mut R, min_seconds: u64, max_seconds: u64) -> u64 {
+    Range::new(min_seconds, max_seconds + 1).ind_sample(rng)
+}
+
+/// Renders a number of seconds in the given duration format
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// use fourree::generators::{format_duration, DurationFormat};
+///
+/// # fn main() {
+/// assert_eq!(format_duration(3661, DurationFormat::Seconds), "3661");
+/// assert_eq!(format_duration(3661, DurationFormat::Hms), "01:01:01");
+/// assert_eq!(format_duration(3661, DurationFormat::Iso8601), "PT1H1M1S");
+/// assert_eq!(format_duration(0, DurationFormat::Iso8601), "PT0S");
+/// # }
+/// ```
+pub fn format_duration(seconds: u64, format: DurationFormat) -> String {
+    let hours = seconds / 3600;
+    let minutes = (seconds % 3600) / 60;
+    let secs = seconds % 60;
+
+    match format {
+        DurationFormat::Seconds => seconds.to_string(),
+        DurationFormat::Hms => format!("{:02}:{:02}:{:02}", hours, minutes, secs),
+        DurationFormat::Iso8601 => {
+            let mut output = "PT".to_string();
+            if hours > 0 {
+                output.push_str(&format!("{}H", hours));
+            }
+            if minutes > 0 {
+                output.push_str(&format!("{}M", minutes));
+            }
+            if secs > 0 || seconds == 0 {
+                output.push_str(&format!("{}S", secs));
+            }
+            output
+        }
+    }
+}
+
+/// Fills a template's `{0}`, `{1}`, ... placeholders with the matching parts, while `{{`
+/// and `}}` stand for literal braces.  Fails on a malformed placeholder, or one numbering a
+/// part that doesn't exist.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate fourree;
+/// use fourree::generators::fill_template;
+///
+/// # fn main() {
+/// let parts = vec!["ada".to_string(), "lovelace".to_string()];
+/// assert_eq!(fill_template("{0}.{1}@example.com", &parts).unwrap(),
+///            "ada.lovelace@example.com");
+/// assert_eq!(fill_template("{{{1}}}", &parts).unwrap(), "{lovelace}");
+/// assert!(fill_template("{2}", &parts).is_err());
+/// assert!(fill_template("{first}", &parts).is_err());
+/// # }
+/// ```
+pub fn fill_template(template: &str, parts: &[String]) -> Result<String, String> {
+    let mut output = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            },
+            '{' => {
+                let mut index = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') if !index.is_empty() => break,
+                        Some(d) if d.is_ascii_digit() => index.push(d),
+                        _ => return Err(format!(
+                            "Template {} has a malformed placeholder; use {{0}}, {{1}}, ... and \
+                             {{{{ or }}}} for literal braces.", template))
+                    }
+                }
+                match index.parse::<usize>().ok().and_then(|i| parts.get(i)) {
+                    Some(part) => output.push_str(part),
+                    None => return Err(format!(
+                        "Template {} uses placeholder {{{}}}, but has only {} parts.",
+                        template, index, parts.len()))
+                }
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            },
+            '}' => return Err(format!(
+                "Template {} has an unmatched '}}'; use }}}} for a literal brace.", template)),
+            other => output.push(other)
+        }
+    }
+    Ok(output)
+}
@@ -0,0 +1,156 @@
+//! Derives a Schema from the structure of an existing PostgreSQL table.
+
+use postgres::{Client, NoTls};
+
+use schema::{Schema, Field, FieldGenerator, Quoting};
+use util::DEDUPE_MAX_RETRIES;
+use generators::Charset;
+
+/// Splits a "connection:table" location on its final ':', since the connection string
+/// itself may contain colons (e.g. a port or password).
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::introspect::parse_table_location;
+///
+/// # fn main() {
+/// let (conn, table) = parse_table_location("postgresql://localhost:5432/db:users").unwrap();
+/// assert_eq!(conn, "postgresql://localhost:5432/db");
+/// assert_eq!(table, "users");
+/// assert!(parse_table_location("users").is_err());
+/// # }
+/// ```
+pub fn parse_table_location(location: &str) -> Result<(String, String), String> {
+    match location.rfind(':') {
+        Some(i) if i > 0 && i < location.len() - 1 => {
+            Ok((location[..i].to_string(), location[i + 1..].to_string()))
+        },
+        _ => Err("--from_table must follow the format connection:table".to_string())
+    }
+}
+
+/// Maps a column from information_schema.columns to a Field with a suitable generator.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::introspect::field_for_column;
+///
+/// # fn main() {
+/// let field = field_for_column("name", "character varying", Some(12), true).unwrap();
+/// assert_eq!(field.data_type, "varchar(12)");
+/// assert!(field_for_column("shape", "polygon", None, true).is_err());
+/// # }
+/// ```
+pub fn field_for_column(name: &str, data_type: &str, max_length: Option<i32>,
+                        nullable: bool) -> Result<Field, String> {
+    let (column_type, generator) = match data_type {
+        "smallint" => {
+            ("smallint".to_string(), FieldGenerator::Integer{ min: 0, max: 32767, step: 1 })
+        },
+        "integer" | "bigint" => {
+            (data_type.to_string(), FieldGenerator::Integer{ min: 0, max: 1000000, step: 1 })
+        },
+        "numeric" | "real" | "double precision" => {
+            (data_type.to_string(), FieldGenerator::GaussF32{ mean: 1000.0, std_dev: 100.0,
+                                                              min: None, max: None })
+        },
+        "character varying" | "character" | "text" => {
+            let length = max_length.unwrap_or(32) as usize;
+            let column_type = match data_type {
+                "text" => "text".to_string(),
+                "character" => format!("char({})", length),
+                _ => format!("varchar({})", length)
+            };
+            (column_type, FieldGenerator::String{ length: length, charset: Charset::Latin })
+        },
+        "date" => {
+            ("date".to_string(), FieldGenerator::Date{ range: None })
+        },
+        "timestamp without time zone" | "timestamp with time zone" => {
+            ("timestamp".to_string(), FieldGenerator::DateTime)
+        },
+        "boolean" => {
+            ("boolean".to_string(), FieldGenerator::Choice{
+                choices: vec!["t".to_string(), "f".to_string()],
+                choice_length: 1,
+                length: 1
+            })
+        },
+        other => return Err(format!("Unsupported column type {} for column {}.", other, name))
+    };
+
+    Ok(Field { nullable: nullable, ..Field::new(name, &column_type, generator) })
+}
+
+/// Connects to PostgreSQL and builds a Schema matching the columns of `table`, which may
+/// be qualified with its schema name (defaults to "public").
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate postgres;
+/// use postgres::{Client, NoTls};
+/// use fourree::introspect::schema_from_table;
+///
+/// # fn main() {
+/// // Requires a scratch database, e.g. FOURREE_TEST_DATABASE_URL=postgresql://localhost/test
+/// let conn = match std::env::var("FOURREE_TEST_DATABASE_URL") {
+///     Ok(c) => c,
+///     Err(_) => return
+/// };
+/// let mut client = Client::connect(&conn, NoTls).unwrap();
+/// client.batch_execute("DROP TABLE IF EXISTS fourree_introspect;
+///                       CREATE TABLE fourree_introspect (id integer, name varchar(10), born date)")
+///       .unwrap();
+///
+/// let schema = schema_from_table(&conn, "fourree_introspect").unwrap();
+/// assert_eq!(schema.fields.len(), 3);
+/// assert_eq!(schema.fields[0].data_type, "integer");
+/// assert_eq!(schema.fields[1].data_type, "varchar(10)");
+/// assert_eq!(schema.fields[2].data_type, "date");
+///
+/// client.batch_execute("DROP TABLE fourree_introspect").unwrap();
+/// # }
+/// ```
+pub fn schema_from_table(conn: &str, table: &str) -> Result<Schema, String> {
+    let (table_schema, table_name) = match table.find('.') {
+        Some(i) => (&table[..i], &table[i + 1..]),
+        None => ("public", table)
+    };
+
+    let mut client = Client::connect(conn, NoTls).map_err(|e| e.to_string())?;
+    let rows = client.query(
+        "SELECT column_name::text, data_type::text, character_maximum_length::int,
+                is_nullable::text
+         FROM information_schema.columns
+         WHERE table_schema = $1 AND table_name = $2
+         ORDER BY ordinal_position",
+        &[&table_schema, &table_name]
+    ).map_err(|e| e.to_string())?;
+
+    if rows.is_empty() {
+        return Err(format!("Table {} has no columns or does not exist.", table));
+    }
+
+    let mut schema = Schema {
+        table_name: table_name.to_string(),
+        delimiter: "\t".to_string(),
+        quoting: Quoting::None,
+        record_separator: "\n".to_string(),
+        fields: Vec::new(),
+        correlated: Vec::new(),
+        unique_max_retries: DEDUPE_MAX_RETRIES,
+        kafka_key: None
+    };
+
+    for row in rows.iter() {
+        let name: String = row.get(0);
+        let data_type: String = row.get(1);
+        let max_length: Option<i32> = row.get(2);
+        let is_nullable: String = row.get(3);
+        schema.add_field(field_for_column(&name, &data_type, max_length, is_nullable == "YES")?);
+    }
+    Ok(schema)
+}
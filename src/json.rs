@@ -1,12 +1,26 @@
 use std::io::prelude::*;
 use std::fs::File;
+use std::path::Path;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
 
 use serde_json::{Value, Map, from_str};
+use serde_yaml;
+use time;
 
-use schema::{Schema, Field, FieldGenerator};
+use schema::{Schema, Field, FieldGenerator, Quoting, Clamp, Correlation, UniqueValues};
+use util::DEDUPE_MAX_RETRIES;
+use format::{FormatSpec, Conversion, parse_format, parse_rounding};
+use regex::parse_pattern;
+use expression::parse_expression;
+use generators::{CompanyPart, NamePart, PhoneStyle, CardIssuer, GeoKind, ByteEncoding,
+                 DurationFormat, TimestampFormat, Charset, IpNetwork, DECIMAL_MAX_PRECISION,
+                 decimal_bound, days_from_civil, days_in_month, fill_template, parse_cidr};
+use data::{GENDER_LABELS, EMAIL_DOMAINS};
 
 /// Takes a filename as input, then parses it according to the Fourree format.
-/// Any parsing errors cause the process to abort.
+/// Any errors reading or parsing the file are returned.
 ///
 /// # Examples
 /// ```
@@ -28,12 +42,54 @@ pub fn load_schema_from_file<'input>(file_name: &'input str) -> Result<Schema, S
                 .map_err(|err| err.to_string())
         })
         .and_then(|_| {
-            parse_json(&raw_json)
+            parse_json_in(&raw_json, Path::new(file_name).parent().unwrap_or(Path::new("")))
+        })
+}
+
+/// Takes the name of a YAML file, then parses it according to the Fourree format.  The
+/// schema's fields and options are exactly those of a JSON schema; only the syntax differs,
+/// so that large schemas can be written more tersely and with comments.
+/// Any errors reading or parsing the file are returned.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use std::fs::File;
+/// use std::io::Write;
+/// use fourree::json::load_schema_from_yaml_file;
+///
+/// # fn main() {
+/// let path = std::env::temp_dir().join("fourree-schema.yaml");
+/// File::create(&path).unwrap().write_all(b"
+/// table_name: users
+/// fields:
+///   ## Surrogate key
+///   - name: id
+///     data_type: bigint
+///     generator: sequence
+/// ").unwrap();
+///
+/// let schema = load_schema_from_yaml_file(path.to_str().unwrap()).unwrap();
+/// assert_eq!(schema.table_name, "users");
+/// assert!(load_schema_from_yaml_file("missing.yaml").is_err());
+/// # }
+/// ```
+pub fn load_schema_from_yaml_file<'input>(file_name: &'input str) -> Result<Schema, String> {
+    let mut raw_yaml = String::new();
+
+    File::open(file_name)
+        .map_err(|err| err.to_string())
+        .and_then(|mut file| {
+            file.read_to_string(&mut raw_yaml)
+                .map_err(|err| err.to_string())
+        })
+        .and_then(|_| {
+            parse_yaml_in(&raw_yaml, Path::new(file_name).parent().unwrap_or(Path::new("")))
         })
 }
 
 /// Takes a string as input, then parses is according to the Fourree format.
-/// Any parsing errors cause the process to abort return the error.
+/// Any parsing errors, including malformed JSON, are returned rather than aborting.
 ///
 /// # Examples
 /// ```
@@ -41,19 +97,262 @@ pub fn load_schema_from_file<'input>(file_name: &'input str) -> Result<Schema, S
 /// use fourree::json::parse_json;
 ///
 /// # fn main() {
-/// let schema = parse_json("{\"table_name\": \"my_table\", \"fields\": []}".to_string());
+/// let schema = parse_json("{\"table_name\": \"my_table\", \"fields\": []}").unwrap();
+/// assert_eq!(schema.table_name, "my_table");
+///
+/// assert!(parse_json("{not json").err().unwrap().starts_with("Invalid JSON: "));
 /// # }
 /// ```
 pub fn parse_json<'input>(raw_json: &'input str) -> Result<Schema, String> {
-    let json_parsed: Value = from_str(&raw_json).expect("Invalid JSON string!");
+    parse_json_in(raw_json, Path::new(""))
+}
+
+/// Parses a schema like `parse_json`, resolving any relative "choices_file" paths against
+/// `base_dir`, normally the directory holding the schema file, rather than the working
+/// directory.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use std::fs::{self, File};
+/// use std::io::Write;
+/// use fourree::json::parse_json_in;
+///
+/// # fn main() {
+/// let dir = std::env::temp_dir().join("fourree-parse-json-in");
+/// fs::create_dir_all(&dir).unwrap();
+/// File::create(dir.join("colors.txt")).unwrap().write_all(b"red\n").unwrap();
+///
+/// let schema = parse_json_in(r#"{"table_name": "t", "fields": [
+///     {"name": "color", "data_type": "varchar(8)", "generator": "choice",
+///      "choices_file": "colors.txt"}
+/// ]}"#, &dir).unwrap();
+/// let mut rng = rand::thread_rng();
+/// assert_eq!(schema.generate_row(&mut rng).unwrap(), "red");
+/// # }
+/// ```
+pub fn parse_json_in<'input>(raw_json: &'input str, base_dir: &Path) -> Result<Schema, String> {
+    parse_value_in(read_json(raw_json)?, base_dir)
+}
+
+/// Parses a schema written in YAML rather than JSON, like `parse_json_in`.  The YAML is read
+/// into the same JSON values a JSON schema is, so every field is parsed the same way.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use std::path::Path;
+/// use fourree::json::parse_yaml_in;
+///
+/// # fn main() {
+/// let schema = parse_yaml_in("
+/// table_name: t
+/// fields:
+///   - {name: id, data_type: integer, generator: integer, min: 0, max: 10}
+/// ", Path::new("")).unwrap();
+/// assert_eq!(schema.fields.len(), 1);
+///
+/// assert!(parse_yaml_in("fields: [", Path::new("")).err().unwrap().starts_with("Invalid YAML: "));
+/// assert!(parse_yaml_in("- t", Path::new("")).is_err());
+/// # }
+/// ```
+pub fn parse_yaml_in<'input>(raw_yaml: &'input str, base_dir: &Path) -> Result<Schema, String> {
+    parse_value_in(read_yaml(raw_yaml)?, base_dir)
+}
+
+/// Reads JSON text into a value, without interpreting it as a schema yet
+fn read_json(raw_json: &str) -> Result<Value, String> {
+    from_str(raw_json).map_err(|e| format!("Invalid JSON: {}", e))
+}
+
+/// Reads YAML text into the same JSON value that the equivalent JSON would give
+fn read_yaml(raw_yaml: &str) -> Result<Value, String> {
+    serde_yaml::from_str(raw_yaml).map_err(|e| format!("Invalid YAML: {}", e))
+}
+
+/// Parses a schema file that may describe several tables: either an array of table
+/// objects, or an object whose "tables" key holds that array.  A single table object, as
+/// `parse_json` takes, gives a single schema.  Relative paths are resolved against
+/// `base_dir`, and no two tables may share a name.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate serde_json;
+/// # extern crate fourree;
+/// use std::path::Path;
+/// use fourree::json::parse_tables_in;
+///
+/// # fn main() {
+/// let users = json!({"table_name": "users", "fields": []});
+/// let orders = json!({"table_name": "orders", "fields": []});
+///
+/// let schemas = parse_tables_in(json!([users, orders]), Path::new("")).unwrap();
+/// assert_eq!(schemas.len(), 2);
+/// assert_eq!(schemas[1].table_name, "orders");
+///
+/// let schemas = parse_tables_in(json!({"tables": [users, orders]}), Path::new("")).unwrap();
+/// assert_eq!(schemas[0].table_name, "users");
+///
+/// assert_eq!(parse_tables_in(users.clone(), Path::new("")).unwrap().len(), 1);
+/// assert!(parse_tables_in(json!([users, users]), Path::new("")).err().unwrap()
+///     .contains("Duplicate table name"));
+/// assert!(parse_tables_in(json!({"tables": []}), Path::new("")).is_err());
+/// # }
+/// ```
+pub fn parse_tables_in(parsed: Value, base_dir: &Path) -> Result<Vec<Schema>, String> {
+    let tables = match parsed {
+        Value::Array(tables) => tables,
+        Value::Object(mut root) => match root.remove("tables") {
+            Some(Value::Array(tables)) => tables,
+            Some(_) => return Err("Tables must be an array of table objects.".to_string()),
+            None => return Ok(vec![parse_value_in(Value::Object(root), base_dir)?])
+        },
+        _ => return Err("The root of a schema must be an object or an array of tables."
+                        .to_string())
+    };
+    if tables.is_empty() {
+        return Err("A schema must describe at least one table.".to_string());
+    }
+
+    let mut schemas: Vec<Schema> = Vec::with_capacity(tables.len());
+    for (i, table) in tables.into_iter().enumerate() {
+        let schema = parse_value_in(table, base_dir)
+            .map_err(|e| format!("Table {}: {}", i + 1, e))?;
+        if schemas.iter().any(|s| s.table_name == schema.table_name) {
+            return Err(format!("Duplicate table name: {}", schema.table_name));
+        }
+        schemas.push(schema);
+    }
+    Ok(schemas)
+}
+
+/// Parses a schema that has been read into a JSON value, resolving relative paths against
+/// `base_dir`
+fn parse_value_in(mut parsed: Value, base_dir: &Path) -> Result<Schema, String> {
+    if let Some(fields) = parsed.get_mut("fields") {
+        resolve_choices_files(fields, base_dir);
+    }
 
-    json_parsed.as_object()
-        .ok_or("Root JSON value must be an object.".to_string())
+    parsed.as_object()
+        .ok_or("The root of a schema must be an object.".to_string())
         .and_then(|j| {
              parse_schema(j.clone())
         })
 }
 
+/// Whether a schema source names a YAML file, by its ".yaml" or ".yml" extension; anything
+/// else is read as JSON.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::json::is_yaml_source;
+///
+/// # fn main() {
+/// assert!(is_yaml_source("schemas/users.yaml"));
+/// assert!(is_yaml_source("https://example.com/users.YML"));
+/// assert!(!is_yaml_source("users.json"));
+/// assert!(!is_yaml_source("-"));
+/// # }
+/// ```
+pub fn is_yaml_source(source: &str) -> bool {
+    match Path::new(source).extension().and_then(|e| e.to_str()) {
+        Some(extension) => {
+            extension.eq_ignore_ascii_case("yaml") || extension.eq_ignore_ascii_case("yml")
+        },
+        None => false
+    }
+}
+
+/// Rewrites the relative "choices_file" paths of an array of fields, and of any template
+/// parts within them, to be relative to `base_dir`
+fn resolve_choices_files(fields: &mut Value, base_dir: &Path) {
+    let fields = match fields.as_array_mut() {
+        Some(f) => f,
+        None => return
+    };
+
+    for field in fields.iter_mut() {
+        if let Some(parts) = field.get_mut("parts") {
+            resolve_choices_files(parts, base_dir);
+        }
+        let resolved = match field.get("choices_file").and_then(|f| f.as_str()) {
+            Some(path) if Path::new(path).is_relative() => {
+                base_dir.join(path).to_string_lossy().into_owned()
+            },
+            _ => continue
+        };
+        field["choices_file"] = Value::String(resolved);
+    }
+}
+
+/// The directory that relative paths in a schema are resolved against, given the schema's
+/// source: the directory of a local file, or the working directory for a URL.
+fn source_dir(source: &str) -> &Path {
+    if source.starts_with("http") {
+        Path::new("")
+    } else {
+        Path::new(source).parent().unwrap_or(Path::new(""))
+    }
+}
+
+/// Parses several schemas, given as (source, contents) pairs.  Sources ending in ".yaml" or
+/// ".yml" are parsed as YAML, and all others as JSON.  A source may describe several tables,
+/// as `parse_tables_in` explains, each of which becomes a schema.  By default the first
+/// invalid source fails the whole load; with `collect_errors`, every source is parsed and
+/// the errors are returned alongside the valid schemas, each prefixed with its source.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::json::parse_schemas;
+///
+/// # fn main() {
+/// let inputs = vec![
+///     ("good.json".to_string(), r#"{"table_name": "good", "fields": []}"#.to_string()),
+///     ("bad.json".to_string(), r#"{"fields": []}"#.to_string()),
+///     ("good.yml".to_string(), "table_name: also_good\nfields: []".to_string()),
+///     ("many.yml".to_string(), "tables:\n  - {table_name: a, fields: []}\n  \
+///                                - {table_name: b, fields: []}".to_string())
+/// ];
+///
+/// assert!(parse_schemas(&inputs, false).err().unwrap().starts_with("bad.json:"));
+///
+/// let (schemas, errors) = parse_schemas(&inputs, true).unwrap();
+/// assert_eq!(schemas.len(), 4);
+/// assert_eq!(schemas[0].table_name, "good");
+/// assert_eq!(schemas[1].table_name, "also_good");
+/// assert_eq!(schemas[3].table_name, "b");
+/// assert_eq!(errors.len(), 1);
+/// assert!(errors[0].starts_with("bad.json:"));
+/// # }
+/// ```
+pub fn parse_schemas(inputs: &[(String, String)], collect_errors: bool)
+        -> Result<(Vec<Schema>, Vec<String>), String> {
+    let mut schemas = Vec::with_capacity(inputs.len());
+    let mut errors = Vec::new();
+
+    for &(ref source, ref contents) in inputs.iter() {
+        let parsed = if is_yaml_source(source) {
+            read_yaml(contents)
+        } else {
+            read_json(contents)
+        };
+        match parsed.and_then(|value| parse_tables_in(value, source_dir(source))) {
+            Ok(tables) => schemas.extend(tables),
+            Err(e) => {
+                let error = format!("{}: {}", source, e);
+                if !collect_errors {
+                    return Err(error);
+                }
+                errors.push(error);
+            }
+        }
+    }
+    Ok((schemas, errors))
+}
+
 /// Parses a given JSON Map formatted schema
 ///
 /// # Examples
@@ -64,6 +363,13 @@ pub fn parse_json<'input>(raw_json: &'input str) -> Result<Schema, String> {
 /// # fn main() {
 ///   let schema = json!({"table_name": "my_table", "fields": []});;
 ///   let result = parse_schema(schema.as_object().unwrap().clone());
+///
+///   // "kafka_key" must name one of the fields
+///   let keyed = parse_json(r#"{"table_name": "t", "kafka_key": "id", "fields": [
+///       {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10}
+///   ]}"#).unwrap();
+///   assert_eq!(keyed.kafka_key, Some("id".to_string()));
+///   assert!(parse_json(r#"{"table_name": "t", "kafka_key": "x", "fields": []}"#).is_err());
 /// # }
 /// ```
 pub fn parse_schema(json: Map<String, Value>) -> Result<Schema, String> {
@@ -83,9 +389,18 @@ pub fn parse_schema(json: Map<String, Value>) -> Result<Schema, String> {
             }
         };
 
+    let record_separator =
+        match json.get("record_separator") {
+            Some(r) => r.as_str().ok_or("Record separator must be a string!")?,
+            None => "\n"
+        };
+    if record_separator.is_empty() {
+        return Err("Record separator must not be empty!".to_string())
+    }
+
     // Now process all the fields in the schema
     // fields must be an array containing objects
-    json.get("fields")
+    let mut schema = json.get("fields")
         .ok_or("Fields must be provided!".to_string())
         .and_then(|fields| {
             fields.as_array()
@@ -93,10 +408,181 @@ pub fn parse_schema(json: Map<String, Value>) -> Result<Schema, String> {
         })
         .and_then(|fields| {
             parse_fields(fields.clone(), table_name, delimiter)
-        })
+        })?;
+    schema.record_separator = record_separator.to_string();
+    schema.quoting = parse_quoting(&json, delimiter)?;
+
+    if let Some(key) = json.get("kafka_key") {
+        let key = key.as_str().ok_or("Kafka key must be a string!")?;
+        if !schema.fields.iter().any(|f| f.name == key) {
+            return Err(format!("Kafka key {} is not a field of the schema.", key))
+        }
+        schema.kafka_key = Some(key.to_string());
+    }
+
+    if let Some(correlated) = json.get("correlated") {
+        schema.correlated = parse_correlated(correlated, &schema.fields)?;
+    }
+
+    // Every correlated field needs a pair to draw its values from
+    for (i, field) in schema.fields.iter().enumerate() {
+        if let FieldGenerator::Correlated = field.generator {
+            if !schema.correlated.iter().any(|c| c.fields.0 == i || c.fields.1 == i) {
+                return Err(format!("Field {} uses the correlated generator, but is not part \
+                                    of a 'correlated' pair.", field.name))
+            }
+        }
+    }
+    Ok(schema)
+}
+
+/// Parses the schema-level "quoting" mode for delimited output: "none" (the default),
+/// "minimal" to quote only fields containing the delimiter, a quote or a line break, or
+/// "all".  Quoted fields have their quotes doubled, as in RFC 4180.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "delimiter": ",", "quoting": "minimal",
+///   "fields": [
+///     {"name": "id", "data_type": "char(1)", "generator": "choice", "choices": ["1"]},
+///     {"name": "name", "data_type": "varchar(16)", "generator": "choice",
+///      "choices": ["Smith, \"Jo\""]}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+/// assert_eq!(schema.generate_row(&mut rng).unwrap(), "1,\"Smith, \"\"Jo\"\"\"");
+///
+/// assert!(parse_json(r#"{"table_name": "t", "delimiter": "fixed", "quoting": "all",
+///   "fields": []}"#).is_err());
+/// # }
+/// ```
+pub fn parse_quoting(json: &Map<String, Value>, delimiter: &str) -> Result<Quoting, String> {
+    let quoting = match json.get("quoting") {
+        Some(q) => match q.as_str().ok_or("Quoting must be a string!".to_string())? {
+            "none" => Quoting::None,
+            "minimal" => Quoting::Minimal,
+            "all" => Quoting::All,
+            other => return Err(format!(
+                "Unsupported quoting: {}, expected 'none', 'minimal' or 'all'.", other))
+        },
+        None => Quoting::None
+    };
+
+    if quoting != Quoting::None && delimiter == "fixed" {
+        return Err("Quoting is not supported for fixed width output.".to_string());
+    }
+    Ok(quoting)
+}
+
+/// Parses the schema-level "correlated" section, an array of pairs of fields that are
+/// drawn together from a bivariate normal distribution.  Each pair names two fields using
+/// the "correlated" generator, along with their "means", "std_devs" and the "correlation"
+/// coefficient between them.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "people", "delimiter": ",", "fields": [
+///     {"name": "height", "data_type": "double precision", "generator": "correlated"},
+///     {"name": "weight", "data_type": "double precision", "generator": "correlated"}
+///   ], "correlated": [
+///     {"fields": ["height", "weight"], "means": [170, 70], "std_devs": [10, 12],
+///      "correlation": 0.8}
+/// ]}"#).unwrap();
+///
+/// let mut rng = rand::thread_rng();
+/// let samples: Vec<(f64, f64)> = schema.rows(&mut rng).take(10000).map(|row| {
+///     let row = row.unwrap();
+///     let mut values = row.split(',').map(|v| v.parse::<f64>().unwrap());
+///     (values.next().unwrap(), values.next().unwrap())
+/// }).collect();
+///
+/// let n = samples.len() as f64;
+/// let mean_x = samples.iter().map(|s| s.0).sum::<f64>() / n;
+/// let mean_y = samples.iter().map(|s| s.1).sum::<f64>() / n;
+/// let cov = samples.iter().map(|s| (s.0 - mean_x) * (s.1 - mean_y)).sum::<f64>();
+/// let var_x = samples.iter().map(|s| (s.0 - mean_x).powi(2)).sum::<f64>();
+/// let var_y = samples.iter().map(|s| (s.1 - mean_y).powi(2)).sum::<f64>();
+/// let correlation = cov / (var_x * var_y).sqrt();
+///
+/// assert!((correlation - 0.8).abs() < 0.05);
+/// # }
+/// ```
+pub fn parse_correlated(value: &Value, fields: &[Field]) -> Result<Vec<Correlation>, String> {
+    let pairs = value.as_array().ok_or("Correlated must be an array!".to_string())?;
+    let mut correlated: Vec<Correlation> = Vec::with_capacity(pairs.len());
+
+    for pair in pairs.iter() {
+        let obj = pair.as_object().ok_or("Each correlated pair must be an object!".to_string())?;
+
+        let names = parse_pair(obj, "fields")?;
+        let mut indices = Vec::with_capacity(2);
+        for name in names.iter() {
+            let name = name.as_str().ok_or("Correlated field names must be strings!".to_string())?;
+            let index = fields.iter().position(|f| f.name == name)
+                .ok_or(format!("Correlated field {} does not exist.", name))?;
+            match fields[index].generator {
+                FieldGenerator::Correlated => {},
+                _ => return Err(format!("Correlated field {} must use the correlated generator.",
+                                        name))
+            }
+            if indices.contains(&index) ||
+                    correlated.iter().any(|c| c.fields.0 == index || c.fields.1 == index) {
+                return Err(format!("Field {} may only appear in one correlated pair.", name))
+            }
+            indices.push(index);
+        }
+
+        let means = parse_number_pair(obj, "means")?;
+        let std_devs = parse_number_pair(obj, "std_devs")?;
+        if std_devs.0 <= 0.0 || std_devs.1 <= 0.0 {
+            return Err("Correlated std_devs must be positive!".to_string())
+        }
+
+        let coefficient = obj.get("correlation")
+            .ok_or("Correlation is required for a correlated pair.".to_string())
+            .and_then(|c| c.as_f64().ok_or("Correlation must be a number!".to_string()))?;
+        if coefficient < -1.0 || coefficient > 1.0 {
+            return Err("Correlation must be between -1 and 1!".to_string())
+        }
+
+        correlated.push(Correlation {
+            fields: (indices[0], indices[1]),
+            means: means,
+            std_devs: std_devs,
+            coefficient: coefficient
+        });
+    }
+    Ok(correlated)
+}
+
+/// Returns the two element array stored under key
+fn parse_pair<'a>(obj: &'a Map<String, Value>, key: &str) -> Result<&'a Vec<Value>, String> {
+    obj.get(key)
+        .and_then(|v| v.as_array())
+        .and_then(|a| if a.len() == 2 { Some(a) } else { None })
+        .ok_or(format!("'{}' must be an array of two values for a correlated pair.", key))
+}
+
+/// Returns the two numbers stored under key
+fn parse_number_pair(obj: &Map<String, Value>, key: &str) -> Result<(f64, f64), String> {
+    let pair = parse_pair(obj, key)?;
+    match (pair[0].as_f64(), pair[1].as_f64()) {
+        (Some(a), Some(b)) => Ok((a, b)),
+        _ => Err(format!("'{}' must contain numbers.", key))
+    }
 }
 
 /// Loops through all the fields provided by the schema, and validates them.
+/// Field names must be unique within a schema.
 ///
 /// # Examples
 /// ```
@@ -108,13 +594,25 @@ pub fn parse_schema(json: Map<String, Value>) -> Result<Schema, String> {
 ///   let schema = json!({"table_name": "my_table", "fields": []});
 ///   let fields = schema.get("fields").unwrap().as_array().unwrap();
 ///   let result = parse_fields(fields.clone(), "my_table", "\t");
+///
+///   let duplicates = json!([
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10},
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10}
+///   ]);
+///   let result = parse_fields(duplicates.as_array().unwrap().clone(), "my_table", "\t");
+///   assert!(result.err().unwrap().contains("id"));
 /// # }
 /// ```
 pub fn parse_fields(fields: Vec<Value>, table_name: &str, delimiter: &str) -> Result<Schema, String> {
     let mut schema = Schema {
         table_name: table_name.to_string(),
         delimiter: delimiter.to_string(),
-        fields: Vec::new()
+        quoting: Quoting::None,
+        record_separator: "\n".to_string(),
+        fields: Vec::new(),
+        correlated: Vec::new(),
+        unique_max_retries: DEDUPE_MAX_RETRIES,
+        kafka_key: None
     };
 
     for field in fields.iter() {
@@ -123,18 +621,86 @@ pub fn parse_fields(fields: Vec<Value>, table_name: &str, delimiter: &str) -> Re
         if delimiter == "fixed" && field.length.is_none() {
             return Err("All fields must have a length if delimeter is 'fixed'.".to_string())
         }
+        if schema.fields.iter().any(|f| f.name == field.name) {
+            return Err(format!("Duplicate field name: {}", field.name))
+        }
+        if let FieldGenerator::PerKeyEventTime{ ref key_field, .. } = field.generator {
+            if !schema.fields.iter().any(|f| &f.name == key_field) {
+                return Err(format!("Key field {} must come before field {}.",
+                                   key_field, field.name))
+            }
+        }
         schema.add_field(field);
     }
+    check_derived(&schema.fields)?;
     Ok(schema)
 }
 
+/// Checks that every field a derived field refers to exists, and that no derived field
+/// depends on itself, directly or through other derived fields.  Derived fields may refer
+/// to fields declared after them, as they are evaluated once the rest of the row is.
+fn check_derived(fields: &[Field]) -> Result<(), String> {
+    let inputs_of = |name: &str| -> Option<&Vec<String>> {
+        fields.iter().find(|f| f.name == name).and_then(|f| match f.generator {
+            FieldGenerator::Derived{ ref inputs, .. } => Some(inputs),
+            _ => None
+        })
+    };
+
+    for field in fields.iter() {
+        let inputs = match inputs_of(&field.name) {
+            Some(inputs) => inputs,
+            None => continue
+        };
+        for input in inputs.iter() {
+            if !fields.iter().any(|f| &f.name == input) {
+                return Err(format!("Derived field {} refers to {}, which is not a field.",
+                                   field.name, input))
+            }
+        }
+
+        // Follow the inputs depth first, keeping the path to report any cycle found
+        let mut stack: Vec<(Vec<&str>, &str)> = inputs.iter()
+            .map(|i| (vec![field.name.as_str()], i.as_str()))
+            .collect();
+        while let Some((path, name)) = stack.pop() {
+            if name == field.name {
+                return Err(format!("Derived fields form a cycle: {} -> {}.",
+                                   path.join(" -> "), name))
+            }
+            if path.contains(&name) {
+                continue;
+            }
+            if let Some(next) = inputs_of(name) {
+                for input in next.iter() {
+                    let mut longer = path.clone();
+                    longer.push(name);
+                    stack.push((longer, input.as_str()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The generator names accepted by `parse_field`, listed when a schema names an unknown one.
+const GENERATORS: &'static [&'static str] = &[
+    "constant", "integer", "decimal", "currency", "geo_coord", "gauss", "gauss_f32", "string",
+    "bytes", "regex", "date", "datetime", "choice", "company", "name", "product", "text", "phone",
+    "ip", "mac", "credit_card", "email", "duration", "correlated", "gender", "cycle", "sequence",
+    "timestamp", "per_key_event_time", "template", "derived", "none"
+];
+
 /// Takes a Map of the metadata for a field, validates it, and returns a Field object.  the
 /// proper generator is selected at this time.
 ///
+/// An unknown generator is an error; use `"none"` for a field that is always empty.
+///
 /// # Examples
 /// ```
 /// # #[macro_use] extern crate serde_json;
 /// # extern crate fourree;
+/// # extern crate rand;
 /// use fourree::json::parse_field;
 ///
 /// fn main() {
@@ -146,6 +712,34 @@ pub fn parse_fields(fields: Vec<Value>, table_name: &str, delimiter: &str) -> Re
 ///     "std_dev": 100
 ///   });
 ///   let result = parse_field(field_data.as_object().unwrap());
+///
+///   let padded = json!({
+///     "name": "id",
+///     "data_type": "integer",
+///     "generator": "integer",
+///     "min": 7,
+///     "max": 7,
+///     "length": 5,
+///     "padding": "0"
+///   });
+///   let field = parse_field(padded.as_object().unwrap()).unwrap();
+///   assert_eq!(field.padding, Some('0'));
+///
+///   let mut wide = padded.clone();
+///   wide["padding"] = json!("00");
+///   assert!(parse_field(wide.as_object().unwrap()).is_err());
+///   wide["padding"] = json!("");
+///   assert!(parse_field(wide.as_object().unwrap()).is_err());
+///
+///   let mut unknown = padded.clone();
+///   unknown["generator"] = json!("integr");
+///   let err = parse_field(unknown.as_object().unwrap()).err().unwrap();
+///   assert!(err.starts_with("Unknown generator integr for field id"));
+///   assert!(err.contains("integer"));
+///
+///   let empty = json!({"name": "notes", "data_type": "string", "generator": "none"});
+///   let field = parse_field(empty.as_object().unwrap()).unwrap();
+///   assert_eq!(field.generate(&mut rand::thread_rng()).unwrap().to_string(), "");
 /// }
 /// ```
 pub fn parse_field<'a>(obj: &'a Map<String, Value>) -> Result<Field, String> {
@@ -169,7 +763,15 @@ pub fn parse_field<'a>(obj: &'a Map<String, Value>) -> Result<Field, String> {
     };
 
     let padding = match obj.get("padding") {
-        Some(p) => Some(p.as_str().ok_or("Padding must be a string!")?.chars().nth(0).unwrap()),
+        Some(p) => {
+            let padding = p.as_str().ok_or("Padding must be a string!")?;
+            let mut chars = padding.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(c),
+                _ => return Err(format!("Padding for field {} must be a single character.",
+                                        field_name))
+            }
+        },
         None => None
     };
 
@@ -180,62 +782,554 @@ pub fn parse_field<'a>(obj: &'a Map<String, Value>) -> Result<Field, String> {
                 .ok_or("Generator must be a string!".to_string())
         })?;
 
+    let nullable = match obj.get("nullable") {
+        Some(n) => n.as_bool().ok_or("Nullable must be a boolean!")?,
+        None => true
+    };
+
+    let primary_key = match obj.get("primary_key") {
+        Some(pk) => pk.as_bool().ok_or("Primary key must be a boolean!")?,
+        None => false
+    };
+
+    let unique = match obj.get("unique") {
+        Some(u) => u.as_bool().ok_or("Unique must be a boolean!")?,
+        None => false
+    };
+
+    let (null_probability, null_token) = parse_nulls(obj, nullable, primary_key, field_name)?;
+
+    let clamp = match obj.get("clamp") {
+        Some(c) => Some(parse_clamp(c)?),
+        None => None
+    };
+
     let generator = match generator_type {
+        "constant" => parse_constant(obj)?,
         "integer" => parse_integer(obj)?,
+        "decimal" => parse_decimal(obj)?,
+        "currency" => parse_currency(obj)?,
+        "geo_coord" => parse_geo_coord(obj)?,
         "gauss" => parse_gauss(obj)?,
+        "gauss_f32" => parse_gauss_f32(obj)?,
         "string" => parse_string(obj)?,
-        "date" => parse_date()?,
+        "bytes" => parse_bytes(obj)?,
+        "regex" => parse_regex(obj)?,
+        "date" => parse_date(obj)?,
+        "datetime" => parse_datetime()?,
         "choice" => parse_choice(obj)?,
-        _ => FieldGenerator::NoGen
+        "company" => parse_company(obj)?,
+        "name" => parse_name(obj)?,
+        "product" => parse_product()?,
+        "text" => parse_text(obj)?,
+        "phone" => parse_phone(obj)?,
+        "ip" => parse_ip(obj)?,
+        "mac" => parse_mac(obj)?,
+        "credit_card" => parse_credit_card(obj)?,
+        "email" => parse_email(obj)?,
+        "duration" => parse_duration(obj)?,
+        "correlated" => FieldGenerator::Correlated,
+        "gender" => parse_gender(obj)?,
+        "cycle" => parse_cycle(obj)?,
+        "sequence" => parse_sequence(obj)?,
+        "timestamp" => parse_timestamp(obj)?,
+        "per_key_event_time" => parse_per_key_event_time(obj)?,
+        "template" => parse_template(obj)?,
+        "derived" => parse_derived(obj)?,
+        "none" => FieldGenerator::NoGen,
+        other => return Err(format!("Unknown generator {} for field {}, expected one of: {}.",
+                                    other, field_name, GENERATORS.join(", ")))
     };
 
+    if clamp.is_some() {
+        match generator {
+            FieldGenerator::Integer{ .. } | FieldGenerator::Gauss{ .. } |
+            FieldGenerator::GaussF32{ .. } => {},
+            _ => return Err(format!("'clamp' is only supported for numeric generators, \
+                                     but field {} uses {}.", field_name, generator_type))
+        }
+    }
+
+    let format = match obj.get("format") {
+        // These generators' formats are options of their own, not printf-style formats
+        Some(_) if ["template", "duration", "timestamp", "phone"].contains(&generator_type) => None,
+        Some(f) => {
+            let spec = f.as_str().ok_or("Format must be a string!".to_string())?;
+            Some(parse_field_format(spec, &generator, data_type, field_name)?)
+        },
+        None => None
+    };
+
+    let format = match (obj.get("rounding"), format) {
+        (Some(r), Some(format)) => {
+            if format.conversion == Conversion::Text {
+                return Err(format!("'rounding' needs a numeric format such as %.2f for field {}.",
+                                   field_name))
+            }
+            let mode = r.as_str().ok_or("Rounding must be a string!".to_string())?;
+            Some(format.with_rounding(parse_rounding(mode)?))
+        },
+        (Some(_), None) => {
+            return Err(format!("'rounding' needs a numeric format such as %.2f for field {}.",
+                               field_name))
+        },
+        (None, format) => format
+    };
+
+    match generator {
+        FieldGenerator::Correlated if unique => {
+            return Err(format!("'unique' is not supported for correlated field {}.", field_name))
+        }
+        FieldGenerator::PerKeyEventTime{ .. } if unique => {
+            return Err(format!("'unique' is not supported for event time field {}.", field_name))
+        }
+        FieldGenerator::Derived{ .. } if unique => {
+            return Err(format!("'unique' is not supported for derived field {}.", field_name))
+        }
+        FieldGenerator::Correlated | FieldGenerator::PerKeyEventTime{ .. } |
+        FieldGenerator::Derived{ .. } if null_probability > 0.0 => {
+            return Err(format!("'null_probability' is not supported for field {}, which uses {}.",
+                               field_name, generator_type))
+        }
+        _ => {}
+    }
+
     Ok(Field{
         name: field_name.to_string(),
         data_type: data_type.to_string(),
         padding: padding,
         length: length,
+        clamp: clamp,
+        nullable: nullable,
+        null_probability: null_probability,
+        null_token: null_token,
+        primary_key: primary_key,
+        unique: if unique { Some(UniqueValues::new()) } else { None },
+        format: format,
         generator: generator
     })
 }
 
-/// Parses an integer field and creates the generator for it, which chooses a random value
-/// between min and max.
+/// Parses a field's "null_probability", the chance (0 to 1, default 0) of writing
+/// "null_token" (default "") instead of a value.  Nulls need the field to be nullable, and
+/// are padded to the field's length like any value in fixed width output.
+///
 /// # Examples
 /// ```
-/// # #[macro_use] extern crate serde_json;
 /// # extern crate fourree;
-/// use fourree::json::parse_integer;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
 ///
 /// # fn main() {
-///   let field_data = json!({
-///     "name": "myfield",
-///     "data_type": "integer",
-///     "generator": "integer",
-///     "min": 1,
-///     "max": 100
-/// # });
-/// let integer_generator = parse_integer(field_data.as_object().unwrap()).unwrap();
-/// }
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 1, "max": 10,
+///      "null_probability": 0.25, "null_token": "\\N"}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+/// let rows = schema.generate_rows(&mut rng, 10000).unwrap();
+/// let nulls = rows.lines().filter(|row| *row == "\\N").count();
+/// assert!(nulls > 2200 && nulls < 2800);
+///
+/// let fixed = parse_json(r#"{"table_name": "t", "delimiter": "fixed", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 1, "max": 10,
+///      "length": 3, "padding": " ", "null_probability": 1}
+/// ]}"#).unwrap();
+/// assert_eq!(fixed.generate_rows(&mut rng, 1).unwrap(), "   \n");
+///
+/// assert!(parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 1, "max": 10,
+///      "nullable": false, "null_probability": 0.1}
+/// ]}"#).is_err());
+/// # }
 /// ```
-pub fn parse_integer<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
-    let min = obj.get("min")
-        .ok_or("Min is required for an integer field.".to_string())
-        .and_then(|min| {
-            min.as_i64()
-                .ok_or("Min must be an integer!".to_string())
-        })?;
+pub fn parse_nulls(obj: &Map<String, Value>, nullable: bool, primary_key: bool,
+                   field_name: &str) -> Result<(f64, String), String> {
+    let null_probability = match obj.get("null_probability") {
+        Some(p) => p.as_f64().ok_or("Null probability must be a number!".to_string())?,
+        None => 0.0
+    };
+    if !(0.0..=1.0).contains(&null_probability) {
+        return Err("Null probability must be between 0 and 1!".to_string())
+    }
+    if null_probability > 0.0 && (!nullable || primary_key) {
+        return Err(format!("Field {} has a null_probability, but is not nullable.", field_name))
+    }
 
-    let max = obj.get("max")
-        .ok_or("Max is required for an integer field.".to_string())
-        .and_then(|max| {
-            max.as_i64()
-                .ok_or("Max must be an integer!".to_string())
+    let null_token = match obj.get("null_token") {
+        Some(t) => t.as_str().ok_or("Null token must be a string!".to_string())?.to_string(),
+        None => String::new()
+    };
+    Ok((null_probability, null_token))
+}
+
+/// Parses a field's printf-style "format", checking that a numeric conversion (%d or %f) is
+/// only used with a generator that produces numbers.  A numeric format may be paired with a
+/// field level "rounding" of "half_up", "half_even" or "floor".
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "code", "data_type": "integer", "generator": "integer", "min": 42, "max": 42,
+///      "format": "%05d"}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+/// assert_eq!(schema.generate_row(&mut rng).unwrap(), "00042");
+///
+/// let invalid = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "name", "data_type": "varchar(4)", "generator": "string", "length": 4,
+///      "format": "%05d"}
+/// ]}"#);
+/// assert!(invalid.is_err());
+/// # }
+/// ```
+pub fn parse_field_format(spec: &str, generator: &FieldGenerator, data_type: &str,
+                          field_name: &str) -> Result<FormatSpec, String> {
+    let format = parse_format(spec)?;
+
+    let numeric = match *generator {
+        FieldGenerator::Integer{ .. } | FieldGenerator::Gauss{ .. } |
+        FieldGenerator::GaussF32{ .. } | FieldGenerator::Correlated |
+        FieldGenerator::Cycle{ .. } | FieldGenerator::Sequence{ .. } => true,
+        FieldGenerator::Duration{ format: DurationFormat::Seconds, .. } => true,
+        _ => false
+    };
+    if !numeric && format.conversion != Conversion::Text {
+        return Err(format!("Format {} is numeric, but field {} of type {} does not generate \
+                            numbers; use %s instead.", spec, field_name, data_type))
+    }
+    Ok(format)
+}
+
+/// Parses the optional "clamp" object of a field, which bounds numeric values after they
+/// are generated.  Either bound may be omitted.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate serde_json;
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::{parse_clamp, parse_json};
+///
+/// # fn main() {
+///   let clamp = parse_clamp(&json!({"min": 0, "max": 100})).unwrap();
+///   assert_eq!(clamp.max, Some(100.0));
+///
+///   let schema = parse_json(r#"{"table_name": "t", "fields": [{
+///     "name": "spread", "data_type": "integer", "generator": "gauss",
+///     "mean": 0, "std_dev": 10000, "clamp": {"min": -5, "max": 5}
+///   }]}"#).unwrap();
+///   let mut rng = rand::thread_rng();
+///   for _ in 0..1000 {
+///     let value: i64 = schema.generate_row(&mut rng).unwrap().parse().unwrap();
+///     assert!(value >= -5 && value <= 5);
+///   }
+/// # }
+/// ```
+pub fn parse_clamp(value: &Value) -> Result<Clamp, String> {
+    let obj = value.as_object().ok_or("Clamp must be an object!".to_string())?;
+
+    let min = match obj.get("min") {
+        Some(m) => Some(m.as_f64().ok_or("Clamp min must be a number!".to_string())?),
+        None => None
+    };
+    let max = match obj.get("max") {
+        Some(m) => Some(m.as_f64().ok_or("Clamp max must be a number!".to_string())?),
+        None => None
+    };
+
+    if let (Some(min), Some(max)) = (min, max) {
+        if min > max {
+            return Err("Clamp min must not be greater than max!".to_string());
+        }
+    }
+
+    Ok(Clamp { min: min, max: max })
+}
+
+/// Parses an integer field and creates the generator for it, which chooses a random value
+/// between min and max.
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate serde_json;
+/// # extern crate fourree;
+/// use fourree::json::parse_integer;
+/// use fourree::schema::FieldGenerator;
+///
+/// # fn main() {
+///   let field_data = json!({
+///     "name": "myfield",
+///     "data_type": "integer",
+///     "generator": "integer",
+///     "min": 1,
+///     "max": 100
+/// # });
+/// let integer_generator = parse_integer(field_data.as_object().unwrap()).unwrap();
+///
+/// // An optional step keeps to its multiples, e.g. prices rounded to 5 cents
+/// let field_data = json!({
+///   "name": "price", "data_type": "integer", "generator": "integer",
+///   "min": 0, "max": 1000, "step": 5
+/// });
+/// match parse_integer(field_data.as_object().unwrap()).unwrap() {
+///     FieldGenerator::Integer{ step, .. } => assert_eq!(step, 5),
+///     _ => panic!("Expected an integer generator")
+/// }
+///
+/// let field_data = json!({
+///   "name": "price", "data_type": "integer", "generator": "integer",
+///   "min": 0, "max": 1000, "step": 0
+/// });
+/// assert!(parse_integer(field_data.as_object().unwrap()).is_err());
+/// }
+/// ```
+pub fn parse_integer<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let min = obj.get("min")
+        .ok_or("Min is required for an integer field.".to_string())
+        .and_then(|min| {
+            min.as_i64()
+                .ok_or("Min must be an integer!".to_string())
+        })?;
+
+    let max = obj.get("max")
+        .ok_or("Max is required for an integer field.".to_string())
+        .and_then(|max| {
+            max.as_i64()
+                .ok_or("Max must be an integer!".to_string())
         })?;
 
-    Ok(FieldGenerator::Integer{ min: min, max: max })
+    let step = match obj.get("step") {
+        Some(s) => match s.as_i64() {
+            Some(step) if step > 0 => step,
+            _ => return Err("Step must be a positive integer!".to_string())
+        },
+        None => 1
+    };
+
+    Ok(FieldGenerator::Integer{ min: min, max: max, step: step })
+}
+
+/// Parses a constant field, whose "value" is written on every row.  The value may be given
+/// as a string, a number or a boolean, and is written exactly as given.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "delimiter": "fixed", "fields": [
+///     {"name": "batch", "data_type": "char(6)", "generator": "constant", "value": 42,
+///      "length": 6, "padding": "0"},
+///     {"name": "source", "data_type": "char(4)", "generator": "constant", "value": "crm",
+///      "length": 4, "padding": " "}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+/// assert_eq!(schema.generate_row(&mut rng).unwrap(), "000042 crm");
+///
+/// assert!(parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "batch", "data_type": "text", "generator": "constant"}
+/// ]}"#).is_err());
+/// # }
+/// ```
+pub fn parse_constant<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let value = match obj.get("value") {
+        Some(&Value::String(ref s)) => s.clone(),
+        Some(v @ &Value::Number(_)) | Some(v @ &Value::Bool(_)) => v.to_string(),
+        Some(_) => return Err("Value must be a string, number or boolean!".to_string()),
+        None => return Err("Value is required for a constant field.".to_string())
+    };
+
+    Ok(FieldGenerator::Constant{ value: value })
+}
+
+/// Parses a decimal field and creates the generator for it, which chooses a random value
+/// between min and max (inclusive) with exactly "precision" digits after the point.
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate serde_json;
+/// # extern crate fourree;
+/// use fourree::json::parse_decimal;
+///
+/// # fn main() {
+///   let field_data = json!({
+///     "name": "price",
+///     "data_type": "numeric(6,2)",
+///     "generator": "decimal",
+///     "min": 0.99,
+///     "max": 9999.99,
+///     "precision": 2
+///   });
+///   let decimal_generator = parse_decimal(field_data.as_object().unwrap()).unwrap();
+///
+///   let empty = json!({"min": 0.001, "max": 0.009, "precision": 2});
+///   assert!(parse_decimal(empty.as_object().unwrap()).is_err());
+/// # }
+/// ```
+pub fn parse_decimal<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let min = obj.get("min")
+        .ok_or("Min is required for a decimal field.".to_string())
+        .and_then(|min| min.as_f64().ok_or("Min must be a number!".to_string()))?;
+
+    let max = obj.get("max")
+        .ok_or("Max is required for a decimal field.".to_string())
+        .and_then(|max| max.as_f64().ok_or("Max must be a number!".to_string()))?;
+
+    let precision = obj.get("precision")
+        .ok_or("Precision is required for a decimal field.".to_string())
+        .and_then(|p| p.as_u64().ok_or("Precision must be a positive integer!".to_string()))?
+        as usize;
+    if precision > DECIMAL_MAX_PRECISION {
+        return Err(format!("Precision must be at most {}!", DECIMAL_MAX_PRECISION))
+    }
+    check_decimal_bounds(min, max, precision)?;
+
+    Ok(FieldGenerator::Decimal{ min: min, max: max, precision: precision })
+}
+
+/// Checks that a range holds at least one value with `precision` digits after the point,
+/// and that its bounds can be scaled to whole units of the precision without losing digits
+fn check_decimal_bounds(min: f64, max: f64, precision: usize) -> Result<(), String> {
+    let limit = 2f64.powi(53);
+    let scale = 10f64.powi(precision as i32);
+    if (min * scale).abs() >= limit || (max * scale).abs() >= limit {
+        return Err("Min and max are too large for the decimal's precision!".to_string())
+    }
+    if decimal_bound(min, precision, true) > decimal_bound(max, precision, false) {
+        return Err(format!("No value with {} decimal places lies between min {} and max {}!",
+                           precision, min, max))
+    }
+    Ok(())
+}
+
+/// Parses a geo_coord field and creates the generator for it, which chooses a latitude
+/// ("kind": "lat") or longitude ("kind": "lon") with "precision" (default 6) digits after
+/// the point.  A bounding box of "min_lat", "max_lat", "min_lon" and "max_lon" keeps the
+/// points within a region; each field uses the pair for its own kind, so the latitude and
+/// longitude fields of a table can share the same box.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate serde_json;
+/// # extern crate fourree;
+/// use fourree::json::parse_geo_coord;
+///
+/// # fn main() {
+///   let field_data = json!({
+///     "name": "latitude",
+///     "data_type": "numeric(8,6)",
+///     "generator": "geo_coord",
+///     "kind": "lat",
+///     "min_lat": 24.5,
+///     "max_lat": 49.4,
+///     "min_lon": -124.8,
+///     "max_lon": -66.9
+///   });
+///   let latitude_generator = parse_geo_coord(field_data.as_object().unwrap()).unwrap();
+///
+///   let outside = json!({"kind": "lon", "min_lon": 170.0, "max_lon": 190.0});
+///   assert!(parse_geo_coord(outside.as_object().unwrap()).is_err());
+/// # }
+/// ```
+pub fn parse_geo_coord<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let (kind, min_key, max_key) = match obj.get("kind") {
+        Some(k) => match k.as_str().ok_or("Kind must be a string!".to_string())? {
+            "lat" => (GeoKind::Latitude, "min_lat", "max_lat"),
+            "lon" => (GeoKind::Longitude, "min_lon", "max_lon"),
+            other => return Err(format!("Unsupported coordinate kind: {}, expected 'lat' or \
+                                         'lon'.", other))
+        },
+        None => return Err("Kind is required for a geo_coord field.".to_string())
+    };
+
+    let (lowest, highest) = kind.bounds();
+    let bound = |key: &str, default: f64| -> Result<f64, String> {
+        match obj.get(key) {
+            Some(b) => {
+                let value = b.as_f64().ok_or(format!("{} must be a number!", key))?;
+                if value < lowest || value > highest {
+                    return Err(format!("{} must be between {} and {}!", key, lowest, highest))
+                }
+                Ok(value)
+            }
+            None => Ok(default)
+        }
+    };
+    let min = bound(min_key, lowest)?;
+    let max = bound(max_key, highest)?;
+
+    let precision = match obj.get("precision") {
+        Some(p) => p.as_u64().ok_or("Precision must be a positive integer!".to_string())? as usize,
+        None => 6
+    };
+    if precision > DECIMAL_MAX_PRECISION {
+        return Err(format!("Precision must be at most {}!", DECIMAL_MAX_PRECISION))
+    }
+    check_decimal_bounds(min, max, precision)?;
+
+    Ok(FieldGenerator::GeoCoord{ kind: kind, min: min, max: max, precision: precision })
+}
+
+/// Parses a currency field and creates the generator for it, which chooses a random amount
+/// between "min" and "max" (inclusive) in whole cents.  Amounts are preceded by "symbol"
+/// (default "$") and, unless "grouping" is false, have commas between thousands.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "invoices", "fields": [
+///     {"name": "total", "data_type": "varchar(16)", "generator": "currency",
+///      "min": 1234.56, "max": 1234.56},
+///     {"name": "fee", "data_type": "varchar(16)", "generator": "currency",
+///      "min": 1234.56, "max": 1234.56, "symbol": "€", "grouping": false}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+/// assert_eq!(schema.generate_row(&mut rng).unwrap(), "$1,234.56\t€1234.56");
+///
+/// let empty = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "x", "data_type": "text", "generator": "currency", "min": 0.001, "max": 0.009}
+/// ]}"#);
+/// assert!(empty.is_err());
+/// # }
+/// ```
+pub fn parse_currency<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let min = obj.get("min")
+        .ok_or("Min is required for a currency field.".to_string())
+        .and_then(|min| min.as_f64().ok_or("Min must be a number!".to_string()))?;
+
+    let max = obj.get("max")
+        .ok_or("Max is required for a currency field.".to_string())
+        .and_then(|max| max.as_f64().ok_or("Max must be a number!".to_string()))?;
+    check_decimal_bounds(min, max, 2)?;
+
+    let symbol = match obj.get("symbol") {
+        Some(s) => s.as_str().ok_or("Symbol must be a string!".to_string())?,
+        None => "$"
+    };
+    let grouping = match obj.get("grouping") {
+        Some(g) => g.as_bool().ok_or("Grouping must be true or false!".to_string())?,
+        None => true
+    };
+
+    Ok(FieldGenerator::Currency{
+        min: min,
+        max: max,
+        symbol: symbol.to_string(),
+        grouping: grouping
+    })
 }
 
-/// Takes the JSON representation of a Field and produces a Gaussian Generator.
+/// Takes the JSON representation of a Field and produces a Gaussian Generator.  The
+/// optional "min" and "max" bound its values.
 ///
 /// # Examples
 /// ```
@@ -243,6 +1337,7 @@ pub fn parse_integer<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator,
 /// # extern crate fourree;
 ///
 /// use fourree::json::parse_gauss;
+/// use fourree::schema::FieldGenerator;
 /// # fn main() {
 /// let field_data = json!({
 ///   "name": "myfield",
@@ -252,21 +1347,50 @@ pub fn parse_integer<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator,
 ///   "std_dev": 100
 /// });
 /// let gauss_generator = parse_gauss(field_data.as_object().unwrap()).unwrap();
+///
+/// let field_data = json!({
+///   "name": "myfield",
+///   "data_type": "integer",
+///   "generator": "gauss",
+///   "mean": 1000,
+///   "std_dev": 50
+/// });
+/// match parse_gauss(field_data.as_object().unwrap()).unwrap() {
+///     FieldGenerator::Gauss{ mean, std_dev, min, max } => {
+///         assert_eq!(mean, 1000);
+///         assert_eq!(std_dev, 50);
+///         assert_eq!((min, max), (None, None));
+///     },
+///     _ => panic!("Expected an integer gauss generator")
+/// }
+///
+/// let field_data = json!({
+///   "name": "age",
+///   "data_type": "integer",
+///   "generator": "gauss",
+///   "mean": 30,
+///   "std_dev": 40,
+///   "min": 0,
+///   "max": 120
+/// });
+/// match parse_gauss(field_data.as_object().unwrap()).unwrap() {
+///     FieldGenerator::Gauss{ min, max, .. } => assert_eq!((min, max), (Some(0), Some(120))),
+///     _ => panic!("Expected an integer gauss generator")
+/// }
+///
+/// let field_data = json!({
+///   "name": "myfield",
+///   "data_type": "integer",
+///   "generator": "gauss",
+///   "mean": 1000,
+///   "std_dev": -1
+/// });
+/// assert!(parse_gauss(field_data.as_object().unwrap()).is_err());
 /// # }
 /// ```
 pub fn parse_gauss<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
-    let mean = obj.get("mean")
-        .ok_or("Mean is required for a gauss distribution field.".to_string())
-        .and_then(|std_dev| {
-            std_dev.as_f64()
-                .ok_or("Mean must be a number!".to_string())
-        })?;
-    let std_dev = obj.get("std_dev")
-        .ok_or("Std deviation is required for a gauss distribution field.".to_string())
-        .and_then(|std_dev| {
-            std_dev.as_f64()
-                .ok_or("Std deviation must be a number!".to_string())
-        })?;
+    let (mean, std_dev) = parse_gauss_params(obj)?;
+    let (min, max) = parse_gauss_bounds(obj)?;
 
     let data_type = obj.get("data_type")
         .ok_or("Field must have a type.".to_string())
@@ -277,20 +1401,109 @@ pub fn parse_gauss<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, St
 
     // TODO: "double precision" should really be f64, plus other types should be added
     match data_type {
-        "integer" => Ok(FieldGenerator::Gauss{ mean: mean as i32, std_dev: std_dev as i32 }),
-        "double precision" => Ok(FieldGenerator::GaussF32{ mean: mean as f32, std_dev: std_dev as f32 }),
-        _ => Ok(FieldGenerator::Gauss{ mean: mean as i32, std_dev: std_dev as i32 })
+        "double precision" => parse_gauss_f32(obj),
+        _ => Ok(FieldGenerator::Gauss{
+            mean: mean as i32,
+            std_dev: std_dev as i32,
+            min: min.map(|m| m as i32),
+            max: max.map(|m| m as i32)
+        })
+    }
+}
+
+/// Takes the JSON representation of a Field and produces a floating point Gaussian
+/// Generator, whatever the field's data_type.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate serde_json;
+/// # extern crate fourree;
+///
+/// use fourree::json::parse_gauss_f32;
+/// use fourree::schema::FieldGenerator;
+/// # fn main() {
+/// let field_data = json!({
+///   "name": "reading",
+///   "data_type": "real",
+///   "generator": "gauss_f32",
+///   "mean": 20.5,
+///   "std_dev": 0.25
+/// });
+/// match parse_gauss_f32(field_data.as_object().unwrap()).unwrap() {
+///     FieldGenerator::GaussF32{ mean, std_dev, .. } => {
+///         assert_eq!(mean, 20.5);
+///         assert_eq!(std_dev, 0.25);
+///     },
+///     _ => panic!("Expected a floating point gauss generator")
+/// }
+///
+/// let field_data = json!({
+///   "name": "reading",
+///   "data_type": "real",
+///   "generator": "gauss_f32",
+///   "mean": 20.5
+/// });
+/// let error = parse_gauss_f32(field_data.as_object().unwrap()).err().unwrap();
+/// assert!(error.contains("Std deviation is required"));
+/// # }
+/// ```
+pub fn parse_gauss_f32<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let (mean, std_dev) = parse_gauss_params(obj)?;
+    let (min, max) = parse_gauss_bounds(obj)?;
+    Ok(FieldGenerator::GaussF32{
+        mean: mean as f32,
+        std_dev: std_dev as f32,
+        min: min.map(|m| m as f32),
+        max: max.map(|m| m as f32)
+    })
+}
+
+/// Reads the mean and standard deviation shared by both Gaussian generators.
+fn parse_gauss_params(obj: &Map<String, Value>) -> Result<(f64, f64), String> {
+    let mean = obj.get("mean")
+        .ok_or("Mean is required for a gauss distribution field.".to_string())
+        .and_then(|mean| {
+            mean.as_f64()
+                .ok_or("Mean must be a number!".to_string())
+        })?;
+    let std_dev = obj.get("std_dev")
+        .ok_or("Std deviation is required for a gauss distribution field.".to_string())
+        .and_then(|std_dev| {
+            std_dev.as_f64()
+                .ok_or("Std deviation must be a number!".to_string())
+        })?;
+    if std_dev < 0.0 {
+        return Err("Std deviation must not be negative!".to_string())
     }
+    Ok((mean, std_dev))
+}
+
+/// Reads the optional "min" and "max" that bound both Gaussian generators.
+fn parse_gauss_bounds(obj: &Map<String, Value>) -> Result<(Option<f64>, Option<f64>), String> {
+    let min = match obj.get("min") {
+        Some(m) => Some(m.as_f64().ok_or("Min must be a number!".to_string())?),
+        None => None
+    };
+    let max = match obj.get("max") {
+        Some(m) => Some(m.as_f64().ok_or("Max must be a number!".to_string())?),
+        None => None
+    };
+    Ok((min, max))
 }
 
 /// Takes a JSON represntation of a string field and returns a String Generator.
+/// "charset" may be "upper" (also "latin", the default), "lower", "alpha", "alphanumeric",
+/// "numeric", "cyrillic", "greek", "cjk" or "emoji".  Alternatively, "chars" gives the
+/// exact characters to draw from.  The length always counts characters, not bytes.
 ///
 /// # Examples
 /// ```
 /// # #[macro_use]
 /// # extern crate serde_json;
 /// # extern crate fourree;
-/// use fourree::json::parse_string;
+/// # extern crate rand;
+/// use fourree::json::{parse_string, parse_json};
+/// use fourree::schema::TypedValue;
 /// # fn main() {
 ///   let field_data = json!({
 ///     "name": "myfield",
@@ -299,6 +1512,35 @@ pub fn parse_gauss<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, St
 ///     "length": 6
 ///   });
 ///   let string_generator = parse_string(field_data.as_object().unwrap()).unwrap();
+///
+///   let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "word", "data_type": "varchar(8)", "generator": "string", "length": 8,
+///      "charset": "cjk"}
+///   ]}"#).unwrap();
+///   let mut rng = rand::thread_rng();
+///   match schema.generate_values(&mut rng).unwrap()[0] {
+///     TypedValue::Text(ref word) => {
+///       assert_eq!(word.chars().count(), 8);
+///       assert!(word.len() > 8);
+///     }
+///     ref other => panic!("Expected text, got {:?}", other)
+///   }
+///
+///   let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "code", "data_type": "char(6)", "generator": "string", "length": 6,
+///      "charset": "lower"},
+///     {"name": "dna", "data_type": "char(6)", "generator": "string", "length": 6,
+///      "chars": "ACGT"}
+///   ]}"#).unwrap();
+///   let row = schema.generate_row(&mut rng).unwrap();
+///   let values: Vec<&str> = row.split('\t').collect();
+///   assert!(values[0].chars().all(|c| c.is_ascii_lowercase()));
+///   assert!(values[1].chars().all(|c| "ACGT".contains(c)));
+///
+///   let klingon = json!({"name": "f", "generator": "string", "length": 6, "charset": "klingon"});
+///   assert!(parse_string(klingon.as_object().unwrap()).is_err());
+///   let empty = json!({"name": "f", "generator": "string", "length": 6, "chars": ""});
+///   assert!(parse_string(empty.as_object().unwrap()).is_err());
 /// # }
 /// ```
 pub fn parse_string<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
@@ -309,65 +1551,1262 @@ pub fn parse_string<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, S
                 .ok_or("Length must be a positive integer!".to_string())
         })?;
 
-    Ok(FieldGenerator::String{ length: length as usize })
-}
+    let charset = match (obj.get("charset"), obj.get("chars")) {
+        (Some(_), Some(_)) => {
+            return Err("A string field takes either 'charset' or 'chars', not both.".to_string())
+        },
+        (Some(c), None) => {
+            match c.as_str().ok_or("Charset must be a string!".to_string())? {
+                "upper" | "latin" => Charset::Latin,
+                "lower" => Charset::Lower,
+                "alpha" => Charset::Alpha,
+                "alphanumeric" => Charset::Alphanumeric,
+                "numeric" => Charset::Numeric,
+                "cyrillic" => Charset::Cyrillic,
+                "greek" => Charset::Greek,
+                "cjk" => Charset::Cjk,
+                "emoji" => Charset::Emoji,
+                other => return Err(format!(
+                    "Unsupported charset: {}, expected 'upper', 'lower', 'alpha', 'alphanumeric', \
+                     'numeric', 'cyrillic', 'greek', 'cjk' or 'emoji'.", other))
+            }
+        },
+        (None, Some(c)) => {
+            let mut chars: Vec<char> = Vec::new();
+            for ch in c.as_str().ok_or("Chars must be a string!".to_string())?.chars() {
+                if !chars.contains(&ch) {
+                    chars.push(ch);
+                }
+            }
+            if chars.is_empty() {
+                return Err("Chars must not be empty!".to_string())
+            }
+            Charset::Custom(chars)
+        },
+        (None, None) => Charset::Latin
+    };
 
-/// Returns a new data generator, which has no configuration options.
-fn parse_date() -> Result<FieldGenerator, String> {
-    Ok(FieldGenerator::Date)
+    Ok(FieldGenerator::String{ length: length as usize, charset: charset })
 }
 
-/// Takes a JSON representation of a choice field and returns a Choice generator,
-/// which is used for generating strings from a list of options.
+/// Parses a bytes field and creates the generator for it, which renders "length" random
+/// bytes as "hex" (the default) or "base64" text, per "encoding".  Hex output is always
+/// 2 * length characters, lowercase unless "uppercase" is true; base64 output is
+/// 4 * ceil(length / 3) characters, padding included.  Size fixed-width columns to match.
+///
 /// # Examples
 /// ```
-/// # #[macro_use]
-/// # extern crate serde_json;
+/// # #[macro_use] extern crate serde_json;
 /// # extern crate fourree;
-/// use fourree::json::parse_choice;
+/// use fourree::json::parse_bytes;
 ///
 /// # fn main() {
 ///   let field_data = json!({
-///     "name": "myfield",
-///     "data_type": "varchar(3)",
-///     "generator": "choice",
-///     "choices": ["1", "2", "3"]
+///     "name": "sha256",
+///     "data_type": "char(64)",
+///     "generator": "bytes",
+///     "length": 32,
+///     "uppercase": true
 ///   });
-///   let choice_generator = parse_choice(field_data.as_object().unwrap()).unwrap();
+///   let hash_generator = parse_bytes(field_data.as_object().unwrap()).unwrap();
+///
+///   let upper_base64 = json!({"length": 16, "encoding": "base64", "uppercase": true});
+///   assert!(parse_bytes(upper_base64.as_object().unwrap()).is_err());
 /// # }
 /// ```
-pub fn parse_choice<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
-    let length = match obj.get("length") {
-        Some(length) => {
+pub fn parse_bytes<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let length = obj.get("length")
+        .ok_or("Length is required for a bytes field.".to_string())
+        .and_then(|length| {
+            length.as_u64()
+                .ok_or("Length must be a positive integer!".to_string())
+        })?;
+
+    let uppercase = match obj.get("uppercase") {
+        Some(u) => Some(u.as_bool().ok_or("Uppercase must be true or false!".to_string())?),
+        None => None
+    };
+
+    let encoding = match obj.get("encoding") {
+        Some(e) => e.as_str().ok_or("Encoding must be a string!".to_string())?,
+        None => "hex"
+    };
+    let encoding = match encoding {
+        "hex" => ByteEncoding::Hex{ uppercase: uppercase.unwrap_or(false) },
+        "base64" if uppercase.is_some() => {
+            return Err("Uppercase only applies to the 'hex' encoding.".to_string())
+        },
+        "base64" => ByteEncoding::Base64,
+        other => return Err(format!(
+            "Unsupported encoding: {}, expected 'hex' or 'base64'.", other))
+    };
+
+    Ok(FieldGenerator::Bytes{ length: length as usize, encoding: encoding })
+}
+
+/// Takes a JSON representation of a regex field and returns a Regex generator, which
+/// produces strings matching "pattern".  Patterns that cannot be generated, such as those
+/// with backreferences or lookaround, are rejected here.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "sku", "data_type": "char(8)", "generator": "regex",
+///      "pattern": "[A-Z]{3}-\\d{4}"}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+/// let sku = schema.generate_row(&mut rng).unwrap();
+/// assert_eq!(sku.len(), 8);
+/// assert_eq!(&sku[3..4], "-");
+///
+/// let invalid = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "twice", "data_type": "text", "generator": "regex", "pattern": "(a)\\1"}
+/// ]}"#);
+/// assert!(invalid.err().unwrap().contains("twice"));
+/// # }
+/// ```
+pub fn parse_regex<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let pattern = obj.get("pattern")
+        .ok_or("Pattern is required for a regex field.".to_string())
+        .and_then(|p| p.as_str().ok_or("Pattern must be a string!".to_string()))?;
+
+    let node = parse_pattern(pattern).map_err(|e| {
+        let name = obj.get("name").and_then(|n| n.as_str()).unwrap_or("");
+        format!("Invalid pattern for field {}: {}", name, e)
+    })?;
+    Ok(FieldGenerator::Regex{ pattern: pattern.to_string(), node: node })
+}
+
+/// Takes a JSON representation of a date field and returns a Date generator.  With
+/// "min_date" and/or "max_date" ("YYYY-MM-DD", inclusive), every day in the range is equally
+/// likely; a missing bound defaults to 1901-01-01 or 2016-12-31.  Without either, dates
+/// follow the original distribution.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "born", "data_type": "date", "generator": "date",
+///      "min_date": "1950-01-01", "max_date": "2005-12-31"}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+/// for row in schema.rows(&mut rng).take(1000) {
+///     let year: u32 = row.unwrap()[6..].parse().unwrap();
+///     assert!(year >= 1950 && year <= 2005);
+/// }
+///
+/// assert!(parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "born", "data_type": "date", "generator": "date", "min_date": "2001-02-29"}
+/// ]}"#).is_err());
+/// assert!(parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "born", "data_type": "date", "generator": "date",
+///      "min_date": "2005-01-01", "max_date": "1950-01-01"}
+/// ]}"#).is_err());
+/// # }
+/// ```
+pub fn parse_date<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let bound = |key: &str| -> Result<Option<i64>, String> {
+        match obj.get(key) {
+            Some(d) => {
+                let date = d.as_str().ok_or(format!("{} must be a string!", key))?;
+                Ok(Some(parse_iso_date(date)?))
+            }
+            None => Ok(None)
+        }
+    };
+    let (min, max) = (bound("min_date")?, bound("max_date")?);
+
+    if min.is_none() && max.is_none() {
+        return Ok(FieldGenerator::Date{ range: None });
+    }
+
+    let min = min.unwrap_or(days_from_civil(1901, 1, 1));
+    let max = max.unwrap_or(days_from_civil(2016, 12, 31));
+    if min > max {
+        return Err("min_date must not be after max_date!".to_string())
+    }
+    Ok(FieldGenerator::Date{ range: Some((min, max)) })
+}
+
+/// Parses a "YYYY-MM-DD" date into the number of days since 1970-01-01
+fn parse_iso_date(date: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid date {}, expected YYYY-MM-DD.", date);
+    let parts: Vec<&str> = date.split('-').collect();
+    if parts.len() != 3 || parts[0].len() != 4 || parts[1].len() != 2 || parts[2].len() != 2 {
+        return Err(invalid());
+    }
+
+    let year = parts[0].parse::<i64>().map_err(|_| invalid())?;
+    let month = parts[1].parse::<u32>().map_err(|_| invalid())?;
+    let day = parts[2].parse::<u32>().map_err(|_| invalid())?;
+    if year < 1 || !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return Err(invalid());
+    }
+    Ok(days_from_civil(year, month, day))
+}
+
+/// Returns a new date and time generator, which has no configuration options.
+fn parse_datetime() -> Result<FieldGenerator, String> {
+    Ok(FieldGenerator::DateTime)
+}
+
+/// Takes a JSON representation of a choice field and returns a Choice generator,
+/// which is used for generating strings from a list of options.  With an optional
+/// "weights" array, one weight per choice, it returns a WeightedChoice generator instead,
+/// which picks each choice in proportion to its weight; the weights need not sum to 1.
+/// Instead of "choices", "choices_file" names a text file holding one choice per line;
+/// blank lines are skipped.
+/// # Examples
+/// ```
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # extern crate fourree;
+/// use std::fs::File;
+/// use std::io::Write;
+/// use fourree::json::parse_choice;
+/// use fourree::schema::FieldGenerator;
+///
+/// # fn main() {
+///   let field_data = json!({
+///     "name": "myfield",
+///     "data_type": "varchar(3)",
+///     "generator": "choice",
+///     "choices": ["1", "2", "3"]
+///   });
+///   let choice_generator = parse_choice(field_data.as_object().unwrap()).unwrap();
+///
+///   let weighted = json!({"choices": ["US", "CA", "MX"], "weights": [80, 15, 5]});
+///   match parse_choice(weighted.as_object().unwrap()).unwrap() {
+///     FieldGenerator::WeightedChoice{ weights, .. } => {
+///       assert!((weights[0] - 0.8).abs() < 1e-9 && (weights[1] - 0.95).abs() < 1e-9);
+///       assert_eq!(weights[2], 1.0);
+///     }
+///     _ => panic!("Expected a weighted choice")
+///   }
+///
+///   let mismatched = json!({"choices": ["US", "CA", "MX"], "weights": [80, 20]});
+///   assert!(parse_choice(mismatched.as_object().unwrap()).is_err());
+///
+///   let path = std::env::temp_dir().join("fourree-choices.txt");
+///   File::create(&path).unwrap().write_all(b"Paris\nSan Francisco\r\n\nRome\n").unwrap();
+///   let from_file = json!({"choices_file": path.to_str().unwrap()});
+///   match parse_choice(from_file.as_object().unwrap()).unwrap() {
+///     FieldGenerator::Choice{ choices, choice_length, .. } => {
+///       assert_eq!(choices, vec!["Paris", "San Francisco", "Rome"]);
+///       assert_eq!(choice_length, 13);
+///     }
+///     _ => panic!("Expected a choice")
+///   }
+///
+///   let both = json!({"choices": ["Oslo"], "choices_file": path.to_str().unwrap()});
+///   assert!(parse_choice(both.as_object().unwrap()).is_err());
+/// # }
+/// ```
+pub fn parse_choice<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let length = match obj.get("length") {
+        Some(length) => {
             let l = length.as_u64().ok_or("Length must be a positive integer!".to_string()).ok().unwrap();
             l as usize
         }
         _ => 1 as usize
     };
 
-    obj.get("choices")
-       .ok_or("A Choice field must have choices!".to_string())
-       .and_then(|a| {
-            a.as_array()
-             .ok_or("Choices field must be an array!".to_string())
-       })
-       .and_then(|array| {
-            let mut choices = Vec::new();
-
-            let mut choice_length = 0;
+    let choices = match (obj.get("choices"), obj.get("choices_file")) {
+        (Some(_), Some(_)) => {
+            return Err("A Choice field takes either choices or a choices_file, not both."
+                       .to_string())
+        },
+        (None, Some(file)) => {
+            read_choices_file(file.as_str().ok_or("Choices file must be a string!".to_string())?)?
+        },
+        (choices, None) => {
+            let array = choices.ok_or("A Choice field must have choices!".to_string())
+                .and_then(|a| a.as_array().ok_or("Choices field must be an array!".to_string()))?;
 
+            let mut choices = Vec::new();
             for choice in array.iter() {
                 let c = choice.as_str().ok_or("All choices must be strings.".to_string())?;
-                if c.len() > choice_length {
-                    choice_length = c.len()
-                }
-
                 choices.push(c.to_string());
             }
-            Ok(FieldGenerator::Choice{
+            choices
+        }
+    };
+    let choice_length = choices.iter().map(|c| c.len()).max().unwrap_or(0);
+
+    match parse_weights(obj, choices.len(), "choice")? {
+        Some(weights) => {
+            let total: f64 = weights.iter().sum();
+            let mut cumulative = 0.0;
+            let mut cumulative_weights: Vec<f64> = weights.iter()
+                .map(|w| { cumulative += w / total; cumulative })
+                .collect();
+            if let Some(last) = cumulative_weights.last_mut() {
+                *last = 1.0;
+            }
+
+            Ok(FieldGenerator::WeightedChoice{
                 choices: choices,
+                weights: cumulative_weights,
                 choice_length: choice_length,
                 length: length
             })
-       })
+        }
+        None => Ok(FieldGenerator::Choice{
+            choices: choices,
+            choice_length: choice_length,
+            length: length
+        })
+    }
+}
+
+/// Reads the choices for a choice field from a text file, one per line
+fn read_choices_file(path: &str) -> Result<Vec<String>, String> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|e| format!("Could not read choices file {}: {}", path, e))?;
+
+    let choices: Vec<String> = contents.lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect();
+    if choices.is_empty() {
+        return Err(format!("Choices file {} has no choices.", path))
+    }
+    Ok(choices)
+}
+
+/// Parses the optional "weights" array, which must hold one non-negative number for each
+/// of the `count` values, at least one of them positive
+fn parse_weights(obj: &Map<String, Value>, count: usize, per: &str)
+        -> Result<Option<Vec<f64>>, String> {
+    let array = match obj.get("weights") {
+        Some(w) => w.as_array().ok_or("Weights must be an array!".to_string())?,
+        None => return Ok(None)
+    };
+
+    let mut weights = Vec::with_capacity(array.len());
+    for weight in array.iter() {
+        let weight = weight.as_f64().ok_or("All weights must be numbers.".to_string())?;
+        if weight < 0.0 {
+            return Err("Weights must not be negative.".to_string())
+        }
+        weights.push(weight);
+    }
+    if weights.len() != count {
+        return Err(format!("Expected {} weights, one per {}, but found {}.",
+                           count, per, weights.len()))
+    }
+    if !weights.iter().any(|w| *w > 0.0) {
+        return Err("At least one weight must be positive.".to_string())
+    }
+    Ok(Some(weights))
+}
+
+/// Takes a JSON representation of a company field and returns a Company generator.  The
+/// optional "kind" selects "name", "suffix" or "full" (the default).
+///
+/// # Examples
+/// ```
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # extern crate fourree;
+/// use fourree::json::parse_company;
+///
+/// # fn main() {
+///   let field_data = json!({
+///     "name": "vendor",
+///     "data_type": "varchar(64)",
+///     "generator": "company",
+///     "kind": "name"
+///   });
+///   let company_generator = parse_company(field_data.as_object().unwrap()).unwrap();
+/// # }
+/// ```
+pub fn parse_company<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let kind = match obj.get("kind") {
+        Some(k) => {
+            match k.as_str().ok_or("Kind must be a string!".to_string())? {
+                "name" => CompanyPart::Name,
+                "suffix" => CompanyPart::Suffix,
+                "full" => CompanyPart::Full,
+                other => return Err(format!(
+                    "Unsupported company kind: {}, expected 'name', 'suffix' or 'full'.", other))
+            }
+        }
+        None => CompanyPart::Full
+    };
+
+    Ok(FieldGenerator::Company{ kind: kind })
+}
+
+/// Takes a JSON representation of a name field and returns a Name generator.  The optional
+/// "part" is "first", "last" or "full" (the default), which renders as "First Last".
+///
+/// # Examples
+/// ```
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # extern crate fourree;
+/// use fourree::json::parse_name;
+///
+/// # fn main() {
+///   let field_data = json!({
+///     "name": "first_name",
+///     "data_type": "varchar(32)",
+///     "generator": "name",
+///     "part": "first"
+///   });
+///   let name_generator = parse_name(field_data.as_object().unwrap()).unwrap();
+/// # }
+/// ```
+pub fn parse_name<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let part = match obj.get("part") {
+        Some(p) => {
+            match p.as_str().ok_or("Part must be a string!".to_string())? {
+                "first" => NamePart::First,
+                "last" => NamePart::Last,
+                "full" => NamePart::Full,
+                other => return Err(format!(
+                    "Unsupported name part: {}, expected 'first', 'last' or 'full'.", other))
+            }
+        }
+        None => NamePart::Full
+    };
+
+    Ok(FieldGenerator::Name{ part: part })
+}
+
+/// Takes a JSON representation of a gender field and returns a Gender generator.  The
+/// optional "labels" default to "Female", "Male" and "Non-binary", and are picked uniformly
+/// unless "weights" gives one relative weight per label.
+///
+/// # Examples
+/// ```
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # extern crate fourree;
+/// use fourree::json::parse_gender;
+///
+/// # fn main() {
+///   let field_data = json!({
+///     "name": "sex",
+///     "data_type": "char(1)",
+///     "generator": "gender",
+///     "labels": ["F", "M"],
+///     "weights": [0.51, 0.49]
+///   });
+///   let gender_generator = parse_gender(field_data.as_object().unwrap()).unwrap();
+///
+///   let mismatched = json!({"labels": ["F", "M"], "weights": [1]});
+///   assert!(parse_gender(mismatched.as_object().unwrap()).is_err());
+/// # }
+/// ```
+pub fn parse_gender<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let labels = match obj.get("labels") {
+        Some(l) => {
+            let array = l.as_array().ok_or("Labels must be an array!".to_string())?;
+            let mut labels = Vec::with_capacity(array.len());
+            for label in array.iter() {
+                let label = label.as_str().ok_or("All labels must be strings.".to_string())?;
+                labels.push(label.to_string());
+            }
+            labels
+        }
+        None => GENDER_LABELS.iter().map(|l| l.to_string()).collect()
+    };
+    if labels.is_empty() {
+        return Err("A gender field needs at least one label.".to_string())
+    }
+
+    let weights = parse_weights(obj, labels.len(), "label")?;
+
+    Ok(FieldGenerator::Gender{ labels: labels, weights: weights })
+}
+
+/// Takes a JSON representation of a cycle field and returns a Cycle generator, which counts
+/// from "start" to "end" inclusive and then wraps back around to "start".  The cycle is
+/// shared by all threads, so each value is used once per lap across the whole run.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "shard_id", "data_type": "integer", "generator": "cycle", "start": 0, "end": 2}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+///
+/// let rows = schema.generate_rows(&mut rng, 6).unwrap();
+/// assert_eq!(rows, "0\n1\n2\n0\n1\n2\n");
+/// # }
+/// ```
+pub fn parse_cycle<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let start = obj.get("start")
+        .ok_or("Start is required for a cycle field.".to_string())
+        .and_then(|start| start.as_i64().ok_or("Start must be an integer!".to_string()))?;
+    let end = obj.get("end")
+        .ok_or("End is required for a cycle field.".to_string())
+        .and_then(|end| end.as_i64().ok_or("End must be an integer!".to_string()))?;
+
+    if start > end {
+        return Err("Cycle start must not be greater than end!".to_string())
+    }
+
+    Ok(FieldGenerator::Cycle{ start: start, end: end, counter: AtomicU64::new(0) })
+}
+
+/// Takes a JSON representation of a sequence field and returns a Sequence generator, which
+/// counts from "start" (default 1) in increments of "step" (default 1), for surrogate keys.
+/// The sequence is shared by all threads, so its values are unique across the whole run,
+/// but with several threads they are not written out in order.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use std::collections::HashSet;
+/// use fourree::json::parse_json;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "bigint", "generator": "sequence"},
+///     {"name": "even", "data_type": "bigint", "generator": "sequence", "start": 0, "step": 2}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+/// assert_eq!(schema.generate_rows(&mut rng, 3).unwrap(), "1\t0\n2\t2\n3\t4\n");
+///
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "bigint", "generator": "sequence"}
+/// ]}"#).unwrap();
+/// let schema = std::sync::Arc::new(schema);
+/// let threads: Vec<_> = (0..4).map(|_| {
+///     let schema = schema.clone();
+///     std::thread::spawn(move || schema.generate_rows(&mut rand::thread_rng(), 250).unwrap())
+/// }).collect();
+/// let ids: HashSet<String> = threads.into_iter()
+///     .flat_map(|t| t.join().unwrap().lines().map(|l| l.to_string()).collect::<Vec<_>>())
+///     .collect();
+/// assert_eq!(ids.len(), 1000);
+/// # }
+/// ```
+pub fn parse_sequence<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let start = match obj.get("start") {
+        Some(s) => s.as_i64().ok_or("Start must be an integer!".to_string())?,
+        None => 1
+    };
+    let step = match obj.get("step") {
+        Some(s) => s.as_i64().ok_or("Step must be an integer!".to_string())?,
+        None => 1
+    };
+
+    if step == 0 {
+        return Err("Sequence step must not be zero!".to_string())
+    }
+
+    Ok(FieldGenerator::Sequence{ start: start, step: step, counter: AtomicU64::new(0) })
+}
+
+/// Takes a JSON representation of a timestamp field and returns a Timestamp generator, for
+/// time series.  Rows are stamped "step_seconds" (default 1) apart from "start_epoch", each
+/// moved by up to "jitter_seconds" (default 0) either way, which must be under half the step
+/// so the times keep increasing.  "format" may be "epoch" (the default) for seconds since
+/// the epoch, or "iso8601".  Like a sequence, the series is shared by all threads.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "at", "data_type": "bigint", "generator": "timestamp",
+///      "start_epoch": 1577836800, "step_seconds": 60},
+///     {"name": "iso", "data_type": "timestamp", "generator": "timestamp",
+///      "start_epoch": 1577836800, "step_seconds": 3600, "format": "iso8601"}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+/// assert_eq!(schema.generate_rows(&mut rng, 2).unwrap(),
+///            "1577836800\t2020-01-01T00:00:00Z\n1577836860\t2020-01-01T01:00:00Z\n");
+///
+/// let too_jittery = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "at", "data_type": "bigint", "generator": "timestamp",
+///      "start_epoch": 0, "step_seconds": 10, "jitter_seconds": 5}
+/// ]}"#);
+/// assert!(too_jittery.err().unwrap().contains("less than half"));
+/// # }
+/// ```
+pub fn parse_timestamp<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let start_epoch = obj.get("start_epoch")
+        .ok_or("Start epoch is required for a timestamp field.".to_string())
+        .and_then(|s| s.as_i64().ok_or("Start epoch must be an integer!".to_string()))?;
+
+    let step_seconds = match obj.get("step_seconds") {
+        Some(s) => s.as_i64().ok_or("Step seconds must be an integer!".to_string())?,
+        None => 1
+    };
+    if step_seconds <= 0 {
+        return Err("Step seconds must be a positive integer!".to_string())
+    }
+
+    let jitter_seconds = match obj.get("jitter_seconds") {
+        Some(j) => j.as_i64().filter(|&j| j >= 0)
+            .ok_or("Jitter seconds must be a positive integer!".to_string())?,
+        None => 0
+    };
+    if jitter_seconds > 0 && jitter_seconds.saturating_mul(2) >= step_seconds {
+        return Err("Jitter seconds must be less than half of step seconds, so that \
+                    timestamps keep increasing.".to_string())
+    }
+
+    let format = match obj.get("format") {
+        Some(f) => {
+            match f.as_str().ok_or("Format must be a string!".to_string())? {
+                "epoch" => TimestampFormat::Epoch,
+                "iso8601" => TimestampFormat::Iso8601,
+                other => return Err(format!(
+                    "Unsupported timestamp format: {}, expected 'epoch' or 'iso8601'.", other))
+            }
+        }
+        None => TimestampFormat::Epoch
+    };
+
+    Ok(FieldGenerator::Timestamp{
+        start_epoch: start_epoch,
+        step_seconds: step_seconds,
+        jitter_seconds: jitter_seconds,
+        format: format,
+        counter: AtomicU64::new(0)
+    })
+}
+
+/// Takes a JSON representation of a per key event time field and returns a PerKeyEventTime
+/// generator.  "key_field" names an earlier field, and each of its values gets its own
+/// sequence of increasing timestamps, starting after "start" ("YYYY-MM-DD HH:MM:SS", in UTC)
+/// and separated by gaps averaging "avg_gap_ms".  The sequences are shared by all threads.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use std::collections::HashMap;
+/// use fourree::json::parse_json;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "events", "fields": [
+///     {"name": "user", "data_type": "varchar(1)", "generator": "choice",
+///      "choices": ["a", "b", "c"]},
+///     {"name": "at", "data_type": "timestamp", "generator": "per_key_event_time",
+///      "key_field": "user", "start": "2020-01-01 00:00:00", "avg_gap_ms": 500}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+///
+/// let mut last_seen: HashMap<String, String> = HashMap::new();
+/// for row in schema.rows(&mut rng).take(300) {
+///     let row = row.unwrap();
+///     let mut values = row.split('\t');
+///     let (user, at) = (values.next().unwrap(), values.next().unwrap().to_string());
+///     assert!(at.as_str() > "2020-01-01 00:00:00.000");
+///     if let Some(previous) = last_seen.get(user) {
+///         assert!(at > *previous);
+///     }
+///     last_seen.insert(user.to_string(), at);
+/// }
+///
+/// let key_after = parse_json(r#"{"table_name": "events", "fields": [
+///     {"name": "at", "data_type": "timestamp", "generator": "per_key_event_time",
+///      "key_field": "user", "start": "2020-01-01 00:00:00", "avg_gap_ms": 500},
+///     {"name": "user", "data_type": "varchar(1)", "generator": "choice", "choices": ["a"]}
+/// ]}"#);
+/// assert!(key_after.err().unwrap().contains("must come before"));
+/// # }
+/// ```
+pub fn parse_per_key_event_time<'a>(obj: &'a Map<String, Value>)
+        -> Result<FieldGenerator, String> {
+    let key_field = obj.get("key_field")
+        .ok_or("Key field is required for a per key event time field.".to_string())
+        .and_then(|k| k.as_str().ok_or("Key field must be a string!".to_string()))?;
+
+    let start = obj.get("start")
+        .ok_or("Start is required for a per key event time field.".to_string())
+        .and_then(|s| s.as_str().ok_or("Start must be a string!".to_string()))?;
+    let start_ms = time::strptime(&start.replacen('T', " ", 1), "%Y-%m-%d %H:%M:%S")
+        .map(|tm| tm.to_timespec().sec * 1000)
+        .map_err(|_| format!("Start must be a timestamp like 2020-01-01 00:00:00, got {}.",
+                             start))?;
+
+    let avg_gap_ms = obj.get("avg_gap_ms")
+        .ok_or("Avg gap ms is required for a per key event time field.".to_string())
+        .and_then(|gap| gap.as_u64().ok_or("Avg gap ms must be a positive integer!".to_string()))?;
+    if avg_gap_ms == 0 {
+        return Err("Avg gap ms must be a positive integer!".to_string())
+    }
+
+    Ok(FieldGenerator::PerKeyEventTime{
+        key_field: key_field.to_string(),
+        start: start.to_string(),
+        avg_gap_ms: avg_gap_ms,
+        start_ms: start_ms,
+        last_times: Mutex::new(HashMap::new())
+    })
+}
+
+/// Takes a JSON representation of a derived field and returns a Derived generator, which
+/// computes each value from other fields of the same row with its "expression", such as
+/// "round(price * quantity, 2)" or "years_between(birth_date, '2024-01-01')".  See
+/// `parse_expression` for the operators and functions available.  Derived fields may not
+/// be unique or have a null_probability, but are null whenever an input is.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "people", "fields": [
+///     {"name": "birth_date", "data_type": "date", "generator": "date",
+///      "min_date": "1950-01-01", "max_date": "2005-12-31"},
+///     {"name": "age", "data_type": "integer", "generator": "derived",
+///      "expression": "years_between(birth_date, '2024-01-01')"},
+///     {"name": "label", "data_type": "text", "generator": "derived",
+///      "expression": "'age ' || age"}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+/// let row = schema.generate_row(&mut rng).unwrap();
+/// let values: Vec<&str> = row.split('\t').collect();
+/// let born: i64 = values[0][6..].parse().unwrap();
+/// let age: i64 = values[1].parse().unwrap();
+/// assert!(age == 2023 - born || age == 2024 - born);
+/// assert_eq!(values[2], format!("age {}", age));
+///
+/// let cycle = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "a", "data_type": "integer", "generator": "derived", "expression": "b + 1"},
+///     {"name": "b", "data_type": "integer", "generator": "derived", "expression": "a * 2"}
+/// ]}"#);
+/// assert!(cycle.err().unwrap().contains("a -> b -> a"));
+///
+/// let missing = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "a", "data_type": "integer", "generator": "derived", "expression": "b + 1"}
+/// ]}"#);
+/// assert!(missing.err().unwrap().contains("not a field"));
+/// # }
+/// ```
+pub fn parse_derived<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let expression = obj.get("expression")
+        .ok_or("Expression is required for a derived field.".to_string())
+        .and_then(|e| e.as_str().ok_or("Expression must be a string!".to_string()))?;
+    let expression = parse_expression(expression)?;
+    let inputs = expression.inputs();
+
+    Ok(FieldGenerator::Derived{ expression: expression, inputs: inputs })
+}
+
+/// Takes a JSON representation of a template field and returns a Template generator.  Its
+/// "format" string holds `{0}`, `{1}`, ... placeholders, each filled with the value of the
+/// matching entry of "parts", an array of field definitions.  Parts are rendered like
+/// fields, with their own format, padding and nulls, but may not be unique or depend on
+/// other fields.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "users", "fields": [
+///     {"name": "email", "data_type": "varchar(64)", "generator": "template",
+///      "format": "{0}.{1}@example.com", "parts": [
+///         {"name": "first", "data_type": "text", "generator": "choice", "choices": ["ada"]},
+///         {"name": "id", "data_type": "integer", "generator": "integer", "min": 7, "max": 7,
+///          "format": "%03d"}
+///     ]}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+/// assert_eq!(schema.generate_row(&mut rng).unwrap(), "ada.007@example.com");
+///
+/// let missing_part = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "code", "data_type": "text", "generator": "template", "format": "{0}-{1}",
+///      "parts": [
+///         {"name": "a", "data_type": "text", "generator": "choice", "choices": ["x"]}
+///     ]}
+/// ]}"#);
+/// assert!(missing_part.err().unwrap().contains("only 1 parts"));
+/// # }
+/// ```
+pub fn parse_template<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let format = obj.get("format")
+        .ok_or("Format is required for a template field.".to_string())
+        .and_then(|f| f.as_str().ok_or("Format must be a string!".to_string()))?;
+
+    let array = obj.get("parts")
+        .ok_or("Parts are required for a template field.".to_string())
+        .and_then(|p| p.as_array().ok_or("Parts must be an array!".to_string()))?;
+
+    let mut parts = Vec::with_capacity(array.len());
+    for part in array.iter() {
+        let part = parse_field(part.as_object()
+            .ok_or("Each template part must be an object!".to_string())?)?;
+        match part.generator {
+            FieldGenerator::Correlated | FieldGenerator::PerKeyEventTime{ .. } |
+            FieldGenerator::Derived{ .. } => {
+                return Err(format!("Template part {} can't use a generator that depends on \
+                                    other fields.", part.name))
+            },
+            _ if part.unique.is_some() => {
+                return Err(format!("'unique' is not supported for template part {}.",
+                                   part.name))
+            },
+            _ => {}
+        }
+        parts.push(part);
+    }
+
+    // Every placeholder must have a part to fill it
+    fill_template(format, &vec![String::new(); parts.len()])?;
+
+    Ok(FieldGenerator::Template{ format: format.to_string(), parts: parts })
+}
+
+/// Returns a new product name generator, which has no configuration options.
+fn parse_product() -> Result<FieldGenerator, String> {
+    Ok(FieldGenerator::Product)
+}
+
+/// Takes a JSON representation of a text field and returns a Text generator, which fills
+/// it with sentences of "min_words" (default 5) to "max_words" (default 15) lorem ipsum
+/// words.
+///
+/// # Examples
+/// ```
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # extern crate fourree;
+/// use fourree::json::parse_text;
+///
+/// # fn main() {
+///   let field_data = json!({
+///     "name": "description",
+///     "data_type": "text",
+///     "generator": "text",
+///     "min_words": 2,
+///     "max_words": 4
+///   });
+///   assert!(parse_text(field_data.as_object().unwrap()).is_ok());
+///
+///   let backwards = json!({"min_words": 8, "max_words": 4});
+///   assert!(parse_text(backwards.as_object().unwrap()).is_err());
+///   let empty = json!({"min_words": 0, "max_words": 0});
+///   assert!(parse_text(empty.as_object().unwrap()).is_err());
+/// # }
+/// ```
+pub fn parse_text<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let min_words = match obj.get("min_words") {
+        Some(w) => w.as_u64().ok_or("Min words must be a positive integer!")? as usize,
+        None => 5
+    };
+    let max_words = match obj.get("max_words") {
+        Some(w) => w.as_u64().ok_or("Max words must be a positive integer!")? as usize,
+        None => min_words.max(15)
+    };
+    if min_words == 0 || min_words > max_words {
+        return Err("Min words must be at least 1 and not greater than max words.".to_string());
+    }
+
+    Ok(FieldGenerator::Text{ min_words: min_words, max_words: max_words })
+}
+
+/// Takes a JSON representation of an email field and returns an Email generator.  The
+/// address's domain is drawn from "domains" (default: a few reserved example domains), and
+/// its local part is "min_length" (default 5) to "max_length" (default 12) lowercase letters
+/// and digits.
+///
+/// # Examples
+/// ```
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # extern crate fourree;
+/// use fourree::json::parse_email;
+///
+/// # fn main() {
+///   let field_data = json!({
+///     "name": "email",
+///     "data_type": "varchar(64)",
+///     "generator": "email",
+///     "domains": ["acme.test", "corp.example"],
+///     "min_length": 4,
+///     "max_length": 10
+///   });
+///   let email_generator = parse_email(field_data.as_object().unwrap()).unwrap();
+///
+///   let no_tld = json!({"domains": ["localhost"]});
+///   assert!(parse_email(no_tld.as_object().unwrap()).is_err());
+///   let backwards = json!({"min_length": 8, "max_length": 4});
+///   assert!(parse_email(backwards.as_object().unwrap()).is_err());
+/// # }
+/// ```
+pub fn parse_email<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let domains = match obj.get("domains") {
+        Some(d) => {
+            let array = d.as_array().ok_or("Domains must be an array!".to_string())?;
+            let mut domains = Vec::with_capacity(array.len());
+            for domain in array.iter() {
+                let d = domain.as_str().ok_or("All domains must be strings.".to_string())?;
+                let valid = d.contains('.') && !d.starts_with('.') && !d.ends_with('.') &&
+                    d.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+                if !valid {
+                    return Err(format!("Invalid email domain: {}, expected e.g. example.com", d));
+                }
+                domains.push(d.to_string());
+            }
+            domains
+        }
+        None => EMAIL_DOMAINS.iter().map(|d| d.to_string()).collect()
+    };
+    if domains.is_empty() {
+        return Err("At least one email domain is required.".to_string());
+    }
+
+    let min_length = match obj.get("min_length") {
+        Some(l) => l.as_u64().ok_or("Min length must be a positive integer!")? as usize,
+        None => 5
+    };
+    let max_length = match obj.get("max_length") {
+        Some(l) => l.as_u64().ok_or("Max length must be a positive integer!")? as usize,
+        None => min_length.max(12)
+    };
+    if min_length == 0 || min_length > max_length {
+        return Err("Min length must be at least 1 and not greater than max length.".to_string());
+    }
+    // The most an address's local part may hold
+    if max_length > 64 {
+        return Err("Max length must not exceed 64.".to_string());
+    }
+
+    Ok(FieldGenerator::Email{ domains: domains, min_length: min_length, max_length: max_length })
+}
+
+/// Takes a JSON representation of an IP address field and returns an IpAddress generator.
+/// "version" is 4 (the default) or 6, and an optional "cidr" block such as "10.0.0.0/8"
+/// keeps the addresses within a subnet, whose version it implies.
+///
+/// # Examples
+/// ```
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::{parse_ip, parse_json};
+///
+/// # fn main() {
+///   let field_data = json!({"name": "host", "data_type": "inet", "generator": "ip",
+///                           "version": 6});
+///   assert!(parse_ip(field_data.as_object().unwrap()).is_ok());
+///
+///   let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "host", "data_type": "inet", "generator": "ip", "cidr": "10.1.0.0/16"}
+///   ]}"#).unwrap();
+///   let mut rng = rand::thread_rng();
+///   assert!(schema.generate_row(&mut rng).unwrap().starts_with("10.1."));
+///
+///   assert!(parse_ip(json!({"cidr": "10.1.0.0/40"}).as_object().unwrap()).is_err());
+///   assert!(parse_ip(json!({"version": 6, "cidr": "10.1.0.0/16"}).as_object().unwrap())
+///       .is_err());
+///   assert!(parse_ip(json!({"version": 5}).as_object().unwrap()).is_err());
+/// # }
+/// ```
+pub fn parse_ip<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let version = match obj.get("version") {
+        Some(v) => match v.as_u64() {
+            Some(4) => Some(4),
+            Some(6) => Some(6),
+            _ => return Err("IP version must be 4 or 6!".to_string())
+        },
+        None => None
+    };
+
+    let network = match obj.get("cidr") {
+        Some(c) => {
+            let cidr = c.as_str().ok_or("CIDR must be a string!".to_string())?;
+            let network = parse_cidr(cidr)?;
+            let cidr_version = match network {
+                IpNetwork::V4{ .. } => 4,
+                IpNetwork::V6{ .. } => 6
+            };
+            if version.map_or(false, |v| v != cidr_version) {
+                return Err(format!("CIDR block {} is not an IPv{} network.", cidr,
+                                   version.unwrap()));
+            }
+            network
+        },
+        None if version == Some(6) => IpNetwork::V6{ base: 0, prefix: 0 },
+        None => IpNetwork::V4{ base: 0, prefix: 0 }
+    };
+
+    Ok(FieldGenerator::IpAddress{ network: network })
+}
+
+/// Takes a JSON representation of a MAC address field and returns a MacAddress generator.
+/// Octets are joined by "separator" (default ":") and are lowercase unless "uppercase" is
+/// true.  Setting "locally_administered" marks the addresses as locally administered
+/// unicast ones, like those given to virtual machines.
+///
+/// # Examples
+/// ```
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # extern crate fourree;
+/// use fourree::json::parse_mac;
+///
+/// # fn main() {
+///   let field_data = json!({"name": "mac", "data_type": "macaddr", "generator": "mac",
+///                           "separator": "-", "uppercase": true,
+///                           "locally_administered": true});
+///   let mac_generator = parse_mac(field_data.as_object().unwrap()).unwrap();
+///
+///   assert!(parse_mac(json!({"separator": 1}).as_object().unwrap()).is_err());
+/// # }
+/// ```
+pub fn parse_mac<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let separator = match obj.get("separator") {
+        Some(s) => s.as_str().ok_or("Separator must be a string!".to_string())?,
+        None => ":"
+    };
+    let uppercase = match obj.get("uppercase") {
+        Some(u) => u.as_bool().ok_or("Uppercase must be true or false!".to_string())?,
+        None => false
+    };
+    let local = match obj.get("locally_administered") {
+        Some(l) => l.as_bool().ok_or("Locally administered must be true or false!".to_string())?,
+        None => false
+    };
+
+    Ok(FieldGenerator::MacAddress{
+        separator: separator.to_string(),
+        uppercase: uppercase,
+        local: local
+    })
+}
+
+/// Takes a JSON representation of a phone field and returns a Phone generator.  The
+/// "e164" style accepts "country_codes" (default ["1"]) and the subscriber number's
+/// "min_length"/"max_length" (default 10), which together may not exceed 15 digits.
+/// The "mask" style takes a "format" such as "+44 #### ######", where each '#' becomes a
+/// random digit.  Without a "style", a "format" implies "mask" and no format at all falls
+/// back to the North American "(###) ###-####".
+///
+/// # Examples
+/// ```
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # extern crate fourree;
+/// use fourree::json::parse_phone;
+///
+/// # fn main() {
+///   let field_data = json!({
+///     "name": "phone",
+///     "data_type": "varchar(16)",
+///     "generator": "phone",
+///     "style": "e164",
+///     "country_codes": ["1", "44"],
+///     "min_length": 9,
+///     "max_length": 10
+///   });
+///   let phone_generator = parse_phone(field_data.as_object().unwrap()).unwrap();
+///
+///   let too_long = json!({"style": "e164", "country_codes": ["44"], "max_length": 14});
+///   assert!(parse_phone(too_long.as_object().unwrap()).is_err());
+///
+///   let masked = json!({"generator": "phone", "format": "+44 #### ######"});
+///   assert!(parse_phone(masked.as_object().unwrap()).is_ok());
+///
+///   let no_digits = json!({"generator": "phone", "format": "(555) 555-0100"});
+///   assert!(parse_phone(no_digits.as_object().unwrap()).is_err());
+/// # }
+/// ```
+pub fn parse_phone<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let style = match obj.get("style") {
+        Some(style) => style.as_str().ok_or("Style must be a string!".to_string())?,
+        None => "mask"
+    };
+    let format = match obj.get("format") {
+        Some(f) => Some(f.as_str().ok_or("Format must be a string!".to_string())?),
+        None => None
+    };
+
+    match style {
+        "mask" => {
+            let format = format.unwrap_or("(###) ###-####");
+            if !format.contains('#') {
+                return Err("A phone format must contain at least one '#'.".to_string());
+            }
+            Ok(FieldGenerator::Phone{ style: PhoneStyle::Mask{ format: format.to_string() } })
+        }
+        "e164" if format.is_some() => {
+            Err("A format only applies to the \"mask\" phone style.".to_string())
+        }
+        "e164" => {
+            let country_codes = match obj.get("country_codes") {
+                Some(codes) => {
+                    let array = codes.as_array()
+                        .ok_or("Country codes must be an array!".to_string())?;
+                    let mut country_codes = Vec::with_capacity(array.len());
+                    for code in array.iter() {
+                        let c = code.as_str()
+                            .ok_or("All country codes must be strings.".to_string())?;
+                        if c.is_empty() || c.len() > 3 || !c.chars().all(|ch| ch.is_digit(10)) {
+                            return Err(format!("Invalid country code: {}", c));
+                        }
+                        country_codes.push(c.to_string());
+                    }
+                    country_codes
+                }
+                None => vec!["1".to_string()]
+            };
+            if country_codes.is_empty() {
+                return Err("At least one country code is required.".to_string());
+            }
+
+            let min_length = match obj.get("min_length") {
+                Some(l) => l.as_u64().ok_or("Min length must be a positive integer!")? as usize,
+                None => 10
+            };
+            let max_length = match obj.get("max_length") {
+                Some(l) => l.as_u64().ok_or("Max length must be a positive integer!")? as usize,
+                None => min_length.max(10)
+            };
+            if min_length > max_length {
+                return Err("Min length must not be greater than max length.".to_string());
+            }
+
+            let longest_code = country_codes.iter().map(|c| c.len()).max().unwrap();
+            if longest_code + max_length > 15 {
+                return Err("E.164 numbers may not exceed 15 digits.".to_string());
+            }
+
+            Ok(FieldGenerator::Phone{ style: PhoneStyle::E164 {
+                country_codes: country_codes,
+                min_length: min_length,
+                max_length: max_length
+            }})
+        }
+        other => Err(format!("Unsupported phone style: {}", other))
+    }
+}
+
+/// Takes a JSON representation of a credit card field and returns a CreditCard generator,
+/// whose numbers pass the Luhn checksum.  The optional "issuer" is one of "visa" (the
+/// default), "mastercard", "amex" or "discover", and sets the numbers' prefix and length.
+///
+/// # Examples
+/// ```
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # extern crate fourree;
+/// use fourree::json::parse_credit_card;
+///
+/// # fn main() {
+///   let field_data = json!({
+///     "name": "card_number",
+///     "data_type": "varchar(16)",
+///     "generator": "credit_card",
+///     "issuer": "amex"
+///   });
+///   let card_generator = parse_credit_card(field_data.as_object().unwrap()).unwrap();
+///
+///   assert!(parse_credit_card(json!({"issuer": "diners"}).as_object().unwrap()).is_err());
+/// # }
+/// ```
+pub fn parse_credit_card<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let issuer = match obj.get("issuer") {
+        Some(i) => {
+            match i.as_str().ok_or("Issuer must be a string!".to_string())? {
+                "visa" => CardIssuer::Visa,
+                "mastercard" => CardIssuer::Mastercard,
+                "amex" => CardIssuer::Amex,
+                "discover" => CardIssuer::Discover,
+                other => return Err(format!(
+                    "Unsupported card issuer: {}, expected 'visa', 'mastercard', 'amex' or \
+                     'discover'.", other))
+            }
+        }
+        None => CardIssuer::Visa
+    };
+
+    Ok(FieldGenerator::CreditCard{ issuer: issuer })
+}
+
+/// Takes a JSON representation of a duration field and returns a Duration generator.
+/// "max_seconds" is required, "min_seconds" defaults to 0 and "format" may be "seconds"
+/// (the default), "hms" or "iso8601".
+///
+/// # Examples
+/// ```
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # extern crate fourree;
+/// use fourree::json::parse_duration;
+///
+/// # fn main() {
+///   let field_data = json!({
+///     "name": "session_length",
+///     "data_type": "varchar(8)",
+///     "generator": "duration",
+///     "min_seconds": 60,
+///     "max_seconds": 7200,
+///     "format": "hms"
+///   });
+///   let duration_generator = parse_duration(field_data.as_object().unwrap()).unwrap();
+/// # }
+/// ```
+pub fn parse_duration<'a>(obj: &'a Map<String, Value>) -> Result<FieldGenerator, String> {
+    let min_seconds = match obj.get("min_seconds") {
+        Some(m) => m.as_u64().ok_or("Min seconds must be a positive integer!".to_string())?,
+        None => 0
+    };
+
+    let max_seconds = obj.get("max_seconds")
+        .ok_or("Max seconds is required for a duration field.".to_string())
+        .and_then(|max| {
+            max.as_u64()
+                .ok_or("Max seconds must be a positive integer!".to_string())
+        })?;
+
+    if min_seconds > max_seconds {
+        return Err("Min seconds must not be greater than max seconds.".to_string());
+    }
+
+    let format = match obj.get("format") {
+        Some(f) => {
+            match f.as_str().ok_or("Format must be a string!".to_string())? {
+                "seconds" => DurationFormat::Seconds,
+                "hms" => DurationFormat::Hms,
+                "iso8601" => DurationFormat::Iso8601,
+                other => return Err(format!(
+                    "Unsupported duration format: {}, expected 'seconds', 'hms' or 'iso8601'.",
+                    other))
+            }
+        }
+        None => DurationFormat::Seconds
+    };
+
+    Ok(FieldGenerator::Duration{
+        min_seconds: min_seconds,
+        max_seconds: max_seconds,
+        format: format
+    })
 }
@@ -2,11 +2,18 @@ extern crate pad;
 extern crate rand;
 extern crate serde;
 extern crate serde_json;
+extern crate serde_yaml;
 extern crate time;
 extern crate getopts;
 extern crate rusoto_core;
 extern crate rusoto_s3;
 extern crate reqwest;
+extern crate aes_gcm;
+extern crate rayon;
+#[cfg(feature = "kafka")]
+extern crate rdkafka;
+#[cfg(feature = "postgres")]
+extern crate postgres;
 
 #[macro_use]
 extern crate log;
@@ -17,6 +24,15 @@ pub mod schema;
 pub mod logger;
 pub mod util;
 pub mod config;
+pub mod data;
+pub mod crypto;
+pub mod sql;
+pub mod format;
+pub mod regex;
+pub mod expression;
+pub mod server;
+#[cfg(feature = "postgres")]
+pub mod introspect;
 
 /// Macro for taking the result of many generators and building a string
 ///
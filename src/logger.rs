@@ -2,7 +2,7 @@ use std::fs::File;
 use std::io::{self, Write};
 
 use log;
-use log::{LogRecord, LogLevel, LogLevelFilter, LogMetadata, SetLoggerError};
+use log::{LogRecord, LogLevelFilter, LogMetadata, SetLoggerError};
 
 pub enum LoggerError {
     Io(io::Error),
@@ -21,11 +21,13 @@ impl From<SetLoggerError> for LoggerError {
     }
 }
 
-struct ConsoleLogger;
+struct ConsoleLogger {
+    level: LogLevelFilter
+}
 
 impl log::Log for ConsoleLogger {
     fn enabled(&self, metadata: &LogMetadata) -> bool {
-        metadata.level() <= LogLevel::Debug
+        metadata.level() <= self.level
     }
 
     fn log(&self, record: &LogRecord) {
@@ -36,22 +38,24 @@ impl log::Log for ConsoleLogger {
 }
 
 struct FileLogger {
-    handle: File
+    handle: File,
+    level: LogLevelFilter
 }
 
 impl FileLogger {
-    pub fn new(p: String) -> Result<FileLogger, io::Error> {
+    pub fn new(p: String, level: LogLevelFilter) -> Result<FileLogger, io::Error> {
         let file = File::create(p.clone())?;
 
         Ok(FileLogger {
-            handle: file
+            handle: file,
+            level: level
         })
     }
 }
 
 impl log::Log for FileLogger {
     fn enabled(&self, metadata: &LogMetadata) -> bool {
-        metadata.level() <= LogLevel::Debug
+        metadata.level() <= self.level
     }
 
     fn log(&self, record: &LogRecord) {
@@ -66,9 +70,9 @@ impl log::Log for FileLogger {
 pub fn init_logger(level: LogLevelFilter, log_path: Option<String>) -> Result<(), LoggerError> {
     let logger: Box<log::Log> = match log_path {
         Some(ref path) => {
-            Box::new(FileLogger::new(path.clone())?)
+            Box::new(FileLogger::new(path.clone(), level)?)
         },
-        None => Box::new(ConsoleLogger)
+        None => Box::new(ConsoleLogger{ level: level })
     };
 
     Ok(log::set_logger(|l| {
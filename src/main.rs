@@ -1,4 +1,3 @@
-extern crate rand;
 extern crate fourree;
 extern crate time;
 
@@ -6,43 +5,177 @@ extern crate time;
 extern crate log;
 
 use std::env;
+use std::process;
+use std::net::TcpListener;
 
 use fourree::config;
-use fourree::json::{parse_json};
-use fourree::util::{generate_data};
+use fourree::config::{Config, OutputMode};
+use fourree::json::{parse_schemas};
+use fourree::schema::Schema;
+use fourree::server::serve;
+use fourree::util::{generate_data, dry_run_report, validation_summary, worker_rng,
+                    table_output_template};
+
+/// Loads the schemas from the input files, or from a live table when --from_table is given,
+/// along with how many input files were invalid
+#[cfg(feature = "postgres")]
+fn load_schemas(config: &Config) -> Result<(Vec<Schema>, usize), String> {
+    use fourree::introspect::{parse_table_location, schema_from_table};
+
+    match config.from_table {
+        Some(ref location) => {
+            let (conn, table) = parse_table_location(location)?;
+            Ok((vec![schema_from_table(&conn, &table)?], 0))
+        },
+        None => parse_input_files(config)
+    }
+}
+
+#[cfg(not(feature = "postgres"))]
+fn load_schemas(config: &Config) -> Result<(Vec<Schema>, usize), String> {
+    match config.from_table {
+        Some(_) => Err("--from_table requires fourree to be built with the 'postgres' feature.".to_string()),
+        None => parse_input_files(config)
+    }
+}
+
+/// Parses every input file, logging the invalid and unreadable ones when collecting errors
+fn parse_input_files(config: &Config) -> Result<(Vec<Schema>, usize), String> {
+    let (schemas, parse_errors) = parse_schemas(&config.input_files, config.collect_errors)?;
+    let errors: Vec<&String> = config.input_errors.iter().chain(parse_errors.iter()).collect();
+    for error in errors.iter() {
+        error!("{}", error);
+    }
+    if !errors.is_empty() {
+        error!("{} of {} schemas are invalid.", errors.len(),
+               config.input_files.len() + config.input_errors.len());
+    }
+    Ok((schemas, errors.len()))
+}
+
+/// Several schemas written to the same file or S3 key would overwrite each other, so
+/// unless an --output_template names each file by {table}, every table is written to the
+/// output file with its name inserted before the extension
+fn check_outputs(config: &mut Config, schemas: &[Schema]) -> Result<(), String> {
+    if schemas.len() < 2 {
+        return Ok(());
+    }
+
+    let templated = config.output_template.as_ref().map_or(false, |t| t.contains("{table}"));
+    match config.output_mode {
+        OutputMode::File | OutputMode::SqlFile if !templated => {
+            match (config.output_template.as_ref(), config.output_file.as_ref()) {
+                (None, Some(file)) => {
+                    config.output_template = Some(table_output_template(file));
+                    Ok(())
+                },
+                _ => Err("Several schemas need an --output_template containing {table}."
+                         .to_string())
+            }
+        },
+        OutputMode::S3 => Err("Only one schema may be written to S3 at a time.".to_string()),
+        _ => Ok(())
+    }
+}
 
 fn main() {
     // Configure based on command line parameters
     let args: Vec<String> = env::args().collect();
 
-    let config = match config::load(args) {
+    let mut config = match config::load(args) {
         Ok(config) => config,
+        // -h prints the usage and returns an empty error
+        Err(ref error) if error.is_empty() => return,
         Err(error) => {
             error!("{}", error);
-            return
+            eprintln!("{}", error);
+            process::exit(1);
         }
     };
 
-    // Load schema from source file
-    debug!("Loading schema from: {:?}", config.input_file);
+    if let Some(port) = config.serve {
+        info!("Serving schema validation on port {}.", port);
+        let result = TcpListener::bind(("0.0.0.0", port))
+            .map_err(|e| e.to_string())
+            .and_then(serve);
+        if let Err(e) = result {
+            error!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Load schemas from source files
+    for &(ref source, _) in config.input_files.iter() {
+        debug!("Loading schema from: {}", source);
+    }
     let start_time = time::precise_time_s();
 
     // Load and generate the data, sending it to OutputMode
-    let schema = match parse_json(&config.input_file) {
-        Ok(s) => s,
+    let loaded = load_schemas(&config)
+        .and_then(|(s, invalid)| check_outputs(&mut config, &s).map(|_| (s, invalid)));
+    let (schemas, invalid) = match loaded {
+        Ok(loaded) => loaded,
         Err(err) => {
             error!("{}", err);
-            return;
+            eprintln!("{}", err);
+            process::exit(1);
         }
     };
 
-    // Generate the data based on configuration and schema
-    info!("Beginning data generation.");
-    match generate_data(&config, schema) {
-        Ok(_) => info!("Data successfully generated."),
-        Err(e) => error!("{}", e)
-    };
+    if config.validate {
+        let mut valid = invalid == 0;
+        for schema in schemas.iter() {
+            match validation_summary(schema) {
+                Ok(summary) => print!("{}", summary),
+                Err(e) => {
+                    error!("{}: {}", schema, e);
+                    eprintln!("{}: {}", schema, e);
+                    valid = false;
+                }
+            };
+        }
+        if !valid {
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Schemas skipped by --collect_errors still fail the run once the rest are written
+    let mut failed = invalid > 0;
+    if config.dry_run {
+        let mut rng = worker_rng(config.seed, 0);
+        for schema in schemas.iter() {
+            match dry_run_report(&config, schema, &mut rng, &config.count_fields) {
+                Ok(report) => print!("{}", report),
+                Err(e) => {
+                    error!("{}", e);
+                    failed = true;
+                }
+            };
+        }
+        if failed {
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Generate the data based on configuration and each schema
+    for schema in schemas {
+        info!("Beginning data generation for {}.", schema);
+        match generate_data(&config, schema) {
+            Ok(_) => info!("Data successfully generated."),
+            Err(e) => {
+                error!("{}", e);
+                failed = true;
+            }
+        };
+    }
 
     let end_time = time::precise_time_s();
     info!("Elapsed time: {} s", end_time-start_time);
+
+    if failed {
+        process::exit(1);
+    }
 }
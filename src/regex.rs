@@ -0,0 +1,365 @@
+use std::char;
+use rand::Rng;
+use rand::distributions::{IndependentSample, Range};
+
+/// How many extra repetitions `*`, `+` and `{n,}` may add beyond their minimum
+pub const REGEX_UNBOUNDED_REPEAT: u32 = 8;
+
+/// Largest repetition count a pattern may ask for
+pub const REGEX_MAX_REPEAT: u32 = 1000;
+
+/// Printable ASCII, which `.` and negated classes draw from
+const PRINTABLE: (char, char) = (' ', '~');
+
+/// A parsed pattern, ready to generate strings that match it
+#[derive(Clone, PartialEq, Debug)]
+pub enum RegexNode {
+    Literal(char),
+    /// Inclusive ranges of characters, each character equally likely
+    Class(Vec<(char, char)>),
+    Concat(Vec<RegexNode>),
+    /// Branches, each equally likely
+    Alternation(Vec<RegexNode>),
+    Repeat { node: Box<RegexNode>, min: u32, max: u32 }
+}
+
+/// Parses a regular expression into a generator.  Supports literals, `.`, classes such
+/// as `[A-Z0-9_]` and `[^,]`, the `\d`, `\w` and `\s` escapes, groups, alternation, and the
+/// `?`, `*`, `+`, `{n}`, `{n,}` and `{n,m}` quantifiers.  Anchors are only allowed at the
+/// ends of the pattern, and constructs that cannot be generated directly, such as
+/// backreferences and lookaround, are rejected.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::regex::parse_pattern;
+///
+/// # fn main() {
+/// assert!(parse_pattern(r"[A-Z]{3}-\d{4}").is_ok());
+/// assert!(parse_pattern(r"^(ab|cd)+$").is_ok());
+/// assert!(parse_pattern(r"(a)\1").err().unwrap().contains("Backreferences"));
+/// assert!(parse_pattern(r"a(?=b)").err().unwrap().contains("Lookaround"));
+/// assert!(parse_pattern(r"[a-").is_err());
+/// assert!(parse_pattern(r"*a").is_err());
+/// # }
+/// ```
+pub fn parse_pattern(pattern: &str) -> Result<RegexNode, String> {
+    let mut parser = Parser { chars: pattern.chars().collect(), pos: 0 };
+
+    if parser.peek() == Some('^') {
+        parser.pos += 1;
+    }
+    let node = parser.alternation()?;
+    if parser.peek() == Some('$') && parser.pos + 1 == parser.chars.len() {
+        parser.pos += 1;
+    }
+
+    match parser.peek() {
+        None => Ok(node),
+        Some(')') => Err(format!("Unmatched ')' at position {} in pattern {}.",
+                                 parser.pos, pattern)),
+        Some(c) => Err(format!("Anchors are only supported at the ends of a pattern, but {} \
+                                has '{}' at position {}.", pattern, c, parser.pos))
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        let c = self.peek().ok_or("Pattern ends unexpectedly.".to_string())?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn alternation(&mut self) -> Result<RegexNode, String> {
+        let mut branches = vec![self.concat()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            branches.push(self.concat()?);
+        }
+
+        if branches.len() == 1 {
+            Ok(branches.pop().unwrap())
+        } else {
+            Ok(RegexNode::Alternation(branches))
+        }
+    }
+
+    fn concat(&mut self) -> Result<RegexNode, String> {
+        let mut nodes = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some('|') | Some(')') => break,
+                Some('$') if self.pos + 1 == self.chars.len() => break,
+                _ => {
+                    let atom = self.atom()?;
+                    nodes.push(self.quantifier(atom)?);
+                }
+            }
+        }
+
+        if nodes.len() == 1 {
+            Ok(nodes.pop().unwrap())
+        } else {
+            Ok(RegexNode::Concat(nodes))
+        }
+    }
+
+    fn atom(&mut self) -> Result<RegexNode, String> {
+        let position = self.pos;
+        match self.next()? {
+            '(' => {
+                if self.peek() == Some('?') {
+                    self.pos += 1;
+                    match self.next()? {
+                        ':' => {},
+                        '=' | '!' | '<' => return Err(
+                            "Lookaround is not supported in regex patterns.".to_string()),
+                        c => return Err(format!("Unsupported group (?{} in regex pattern.", c))
+                    }
+                }
+                let node = self.alternation()?;
+                if self.next()? != ')' {
+                    return Err(format!("Unclosed group at position {}.", position));
+                }
+                Ok(node)
+            }
+            '[' => self.class(),
+            '.' => Ok(RegexNode::Class(vec![PRINTABLE])),
+            '\\' => self.escape(false),
+            c @ '*' | c @ '+' | c @ '?' | c @ '{' => {
+                Err(format!("Nothing for '{}' to repeat at position {}.", c, position))
+            }
+            '^' | '$' => Err(format!(
+                "Anchors are only supported at the ends of a pattern, not at position {}.",
+                position)),
+            c => Ok(RegexNode::Literal(c))
+        }
+    }
+
+    /// Parses an escape, after the backslash
+    fn escape(&mut self, in_class: bool) -> Result<RegexNode, String> {
+        let ranges = match self.next()? {
+            'd' => vec![('0', '9')],
+            'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+            's' => vec![(' ', ' '), ('\t', '\t')],
+            'D' => complement(&[('0', '9')]),
+            'W' => complement(&[('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')]),
+            'S' => complement(&[(' ', ' ')]),
+            'n' => return Ok(RegexNode::Literal('\n')),
+            't' => return Ok(RegexNode::Literal('\t')),
+            'r' => return Ok(RegexNode::Literal('\r')),
+            '1'..='9' | 'k' => return Err(
+                "Backreferences are not supported in regex patterns.".to_string()),
+            'b' if in_class => return Ok(RegexNode::Literal('\u{8}')),
+            c if c.is_ascii_alphanumeric() => return Err(format!(
+                "Unsupported escape \\{} in regex pattern.", c)),
+            c => return Ok(RegexNode::Literal(c))
+        };
+        Ok(RegexNode::Class(ranges))
+    }
+
+    /// Parses a character class, after the opening bracket
+    fn class(&mut self) -> Result<RegexNode, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next().map_err(|_| "Unclosed character class.".to_string())?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let low = if c == '\\' {
+                match self.escape(true)? {
+                    RegexNode::Literal(l) => l,
+                    RegexNode::Class(r) => {
+                        ranges.extend(r);
+                        continue;
+                    }
+                    _ => unreachable!()
+                }
+            } else {
+                c
+            };
+
+            // A '-' before the closing bracket is a literal
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']') {
+                self.pos += 1;
+                let high = match self.next().map_err(|_| "Unclosed character class.".to_string())? {
+                    '\\' => match self.escape(true)? {
+                        RegexNode::Literal(h) => h,
+                        _ => return Err("A class range must end with a single character."
+                                        .to_string())
+                    },
+                    h => h
+                };
+                if high < low {
+                    return Err(format!("Invalid class range {}-{} in regex pattern.", low, high));
+                }
+                ranges.push((low, high));
+            } else {
+                ranges.push((low, low));
+            }
+        }
+
+        let ranges = if negated { complement(&ranges) } else { ranges };
+        if ranges.is_empty() {
+            return Err("Character class matches no printable characters.".to_string());
+        }
+        Ok(RegexNode::Class(ranges))
+    }
+
+    fn quantifier(&mut self, node: RegexNode) -> Result<RegexNode, String> {
+        let (min, max) = match self.peek() {
+            Some('?') => (0, 1),
+            Some('*') => (0, REGEX_UNBOUNDED_REPEAT),
+            Some('+') => (1, 1 + REGEX_UNBOUNDED_REPEAT),
+            Some('{') => {
+                let close = self.chars[self.pos..].iter().position(|&c| c == '}')
+                    .ok_or(format!("Unclosed quantifier at position {}.", self.pos))?;
+                let body: String = self.chars[self.pos + 1..self.pos + close].iter().collect();
+                let invalid = || format!("Invalid quantifier {{{}}} in regex pattern.", body);
+                let count = |s: &str| s.trim().parse::<u32>().map_err(|_| invalid());
+
+                let bounds = match body.find(',') {
+                    None => { let n = count(&body)?; (n, n) }
+                    Some(i) if body[i + 1..].trim().is_empty() => {
+                        let n = count(&body[..i])?;
+                        (n, n.saturating_add(REGEX_UNBOUNDED_REPEAT))
+                    }
+                    Some(i) => (count(&body[..i])?, count(&body[i + 1..])?)
+                };
+                if bounds.0 > bounds.1 {
+                    return Err(invalid());
+                }
+                if bounds.1 > REGEX_MAX_REPEAT + REGEX_UNBOUNDED_REPEAT {
+                    return Err(format!("Quantifier {{{}}} repeats more than {} times.",
+                                       body, REGEX_MAX_REPEAT));
+                }
+                self.pos += close;
+                bounds
+            }
+            _ => return Ok(node)
+        };
+        self.pos += 1;
+
+        // Lazy quantifiers match the same strings
+        if self.peek() == Some('?') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some('*') | Some('+') | Some('?') | Some('{') => {
+                Err(format!("Nothing for '{}' to repeat at position {}.", self.peek().unwrap(),
+                            self.pos))
+            }
+            _ => Ok(RegexNode::Repeat { node: Box::new(node), min: min, max: max })
+        }
+    }
+}
+
+/// Returns the printable ASCII characters outside of the ranges
+fn complement(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut result = Vec::new();
+    let mut start = PRINTABLE.0 as u32;
+    let mut sorted = ranges.to_vec();
+    sorted.sort();
+
+    for &(low, high) in sorted.iter() {
+        let (low, high) = (low as u32, high as u32);
+        if low > start {
+            result.push((start, (low - 1).min(PRINTABLE.1 as u32)));
+        }
+        start = start.max(high + 1);
+    }
+    if start <= PRINTABLE.1 as u32 {
+        result.push((start, PRINTABLE.1 as u32));
+    }
+
+    result.into_iter()
+        .filter(|&(low, high)| low <= high)
+        .map(|(low, high)| (char::from_u32(low).unwrap(), char::from_u32(high).unwrap()))
+        .collect()
+}
+
+impl RegexNode {
+    /// Generates a string matching the pattern
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// # extern crate rand;
+    /// use fourree::regex::parse_pattern;
+    ///
+    /// # fn main() {
+    /// let mut rng = rand::thread_rng();
+    /// let sku = parse_pattern(r"[A-Z]{3}-\d{4}").unwrap();
+    /// for _ in 0..100 {
+    ///     let value = sku.generate(&mut rng);
+    ///     let chars: Vec<char> = value.chars().collect();
+    ///     assert_eq!(chars.len(), 8);
+    ///     assert!(chars[..3].iter().all(|c| c.is_ascii_uppercase()));
+    ///     assert_eq!(chars[3], '-');
+    ///     assert!(chars[4..].iter().all(|c| c.is_ascii_digit()));
+    /// }
+    ///
+    /// let choice = parse_pattern(r"(red|green)[^a-z]?").unwrap();
+    /// let value = choice.generate(&mut rng);
+    /// assert!(value.starts_with("red") || value.starts_with("green"));
+    /// assert!(value.chars().all(|c| !c.is_ascii_lowercase() || "redgn".contains(c)));
+    /// # }
+    /// ```
+    pub fn generate<R: Rng>(&self, rng: &mut R) -> String {
+        let mut output = String::new();
+        self.generate_into(rng, &mut output);
+        output
+    }
+
+    fn generate_into<R: Rng>(&self, rng: &mut R, output: &mut String) {
+        match *self {
+            RegexNode::Literal(c) => output.push(c),
+            RegexNode::Class(ref ranges) => {
+                let size: u32 = ranges.iter().map(|&(l, h)| h as u32 - l as u32 + 1).sum();
+                let mut index = Range::new(0, size).ind_sample(rng);
+                for &(low, high) in ranges.iter() {
+                    let span = high as u32 - low as u32 + 1;
+                    if index < span {
+                        // A range spanning the surrogate gap has holes that are skipped
+                        if let Some(c) = char::from_u32(low as u32 + index) {
+                            output.push(c);
+                        }
+                        break;
+                    }
+                    index -= span;
+                }
+            }
+            RegexNode::Concat(ref nodes) => {
+                for node in nodes.iter() {
+                    node.generate_into(rng, output);
+                }
+            }
+            RegexNode::Alternation(ref branches) => {
+                rng.choose(branches).unwrap().generate_into(rng, output);
+            }
+            RegexNode::Repeat { ref node, min, max } => {
+                let count = Range::new(min, max + 1).ind_sample(rng);
+                for _ in 0..count {
+                    node.generate_into(rng, output);
+                }
+            }
+        }
+    }
+}
@@ -1,21 +1,290 @@
 use std::fmt;
+use std::iter;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use rand;
 use pad::{PadStr, Alignment};
+use serde_json::{Value, Number};
 
 use generators::*;
+use format::FormatSpec;
+use regex::RegexNode;
+use expression::Expression;
+use data::{COMPANY_NAMES, COMPANY_SUFFIXES, PRODUCT_ADJECTIVES, PRODUCT_MATERIALS, PRODUCT_NOUNS,
+           FIRST_NAMES, LAST_NAMES, LOREM_WORDS};
 
-trait Generator {
-    fn generate<R: rand::Rng>(&self, rng: &mut R) -> String;
+/// Supplies the values of a field with your own logic, through `FieldGenerator::Custom`.
+/// Closures taking the rng and returning a `TypedValue` are generators too.
+///
+/// The generator is kept as a `Box<dyn Generator>`, so the trait is object safe: it takes
+/// the rng as a `&mut dyn Rng` rather than as a type parameter, and any methods added to an
+/// implementation must not be generic or return `Self` if they are to be called through
+/// the box.  Every generator thread shares the one generator, hence `Send + Sync`; keep any
+/// mutable state behind a `Mutex` or an atomic.  `Rng`'s generic methods, such as
+/// `gen_range`, need a sized rng, so call them on `&mut rng`.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use rand::Rng;
+/// use fourree::schema::{Generator, TypedValue};
+///
+/// struct Dice;
+///
+/// impl Generator for Dice {
+///     fn generate(&self, mut rng: &mut dyn Rng) -> TypedValue {
+///         TypedValue::Integer((&mut rng).gen_range(1, 7))
+///     }
+/// }
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// match Dice.generate(&mut rng) {
+///     TypedValue::Integer(roll) => assert!(roll >= 1 && roll <= 6),
+///     other => panic!("unexpected value {:?}", other)
+/// }
+/// # }
+/// ```
+pub trait Generator: Send + Sync {
+    fn generate(&self, rng: &mut dyn rand::Rng) -> TypedValue;
+}
+
+impl<F> Generator for F where F: Fn(&mut dyn rand::Rng) -> TypedValue + Send + Sync {
+    fn generate(&self, rng: &mut dyn rand::Rng) -> TypedValue {
+        self(rng)
+    }
+}
+
+/// A single generated value, prior to being rendered into a row
+#[derive(Clone, PartialEq, Debug)]
+pub enum TypedValue {
+    Integer(i64),
+    Float(f32),
+    Text(String),
+    /// A missing value, written out as the field's null token
+    Null
+}
+
+impl fmt::Display for TypedValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TypedValue::Integer(i) => write!(f, "{}", i),
+            TypedValue::Float(x) => write!(f, "{}", x),
+            TypedValue::Text(ref s) => write!(f, "{}", s),
+            TypedValue::Null => Ok(())
+        }
+    }
+}
+
+/// The number of distinct values a generator can theoretically produce
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cardinality {
+    Finite(u64),
+    Unbounded
+}
+
+impl Cardinality {
+    fn product(self, other: Cardinality) -> Cardinality {
+        match (self, other) {
+            (Cardinality::Finite(a), Cardinality::Finite(b)) => {
+                a.checked_mul(b).map_or(Cardinality::Unbounded, Cardinality::Finite)
+            }
+            _ => Cardinality::Unbounded
+        }
+    }
+
+    fn power(base: u64, exponent: usize) -> Cardinality {
+        (0..exponent).fold(Cardinality::Finite(1), |acc, _| acc.product(Cardinality::Finite(base)))
+    }
+}
+
+impl fmt::Display for Cardinality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Cardinality::Finite(n) => write!(f, "{}", n),
+            Cardinality::Unbounded => write!(f, "unbounded")
+        }
+    }
+}
+
+/// Optional bounds applied to numeric values after they are generated
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Clamp {
+    pub min: Option<f64>,
+    pub max: Option<f64>
+}
+
+impl Clamp {
+    /// Restricts numeric values to the configured bounds, leaving text untouched
+    pub fn apply(&self, value: TypedValue) -> TypedValue {
+        match value {
+            TypedValue::Integer(mut i) => {
+                if let Some(min) = self.min {
+                    i = i.max(min.ceil() as i64);
+                }
+                if let Some(max) = self.max {
+                    i = i.min(max.floor() as i64);
+                }
+                TypedValue::Integer(i)
+            }
+            TypedValue::Float(mut x) => {
+                if let Some(min) = self.min {
+                    x = x.max(min as f32);
+                }
+                if let Some(max) = self.max {
+                    x = x.min(max as f32);
+                }
+                TypedValue::Float(x)
+            }
+            text => text
+        }
+    }
 }
 
 pub enum FieldGenerator {
     NoGen,
-    Integer { min: i64, max: i64 },
-    Gauss { mean: i32, std_dev: i32 },
-    GaussF32 { mean: f32, std_dev: f32},
-    Date,
-    String { length: usize },
-    Choice { choices: Vec<String>, choice_length: usize, length: usize }
+    /// The same value on every row
+    Constant { value: String },
+    /// Multiples of step from min to max, inclusive
+    Integer { min: i64, max: i64, step: i64 },
+    Decimal { min: f64, max: f64, precision: usize },
+    /// Amounts of money from min to max, in whole cents, like "$1,234.56"
+    Currency { min: f64, max: f64, symbol: String, grouping: bool },
+    /// Latitudes or longitudes from min to max degrees, with precision digits after the point
+    GeoCoord { kind: GeoKind, min: f64, max: f64, precision: usize },
+    /// Normally distributed values, moved onto min or max when they fall beyond either
+    Gauss { mean: i32, std_dev: i32, min: Option<i32>, max: Option<i32> },
+    GaussF32 { mean: f32, std_dev: f32, min: Option<f32>, max: Option<f32> },
+    /// Dates within an inclusive range of days since 1970-01-01, or from the original
+    /// 1901-2016 distribution when there is no range
+    Date { range: Option<(i64, i64)> },
+    DateTime,
+    String { length: usize, charset: Charset },
+    /// Random bytes, rendered as text in the encoding
+    Bytes { length: usize, encoding: ByteEncoding },
+    /// Strings matching a regular expression, kept alongside its parsed form
+    Regex { pattern: String, node: RegexNode },
+    Choice { choices: Vec<String>, choice_length: usize, length: usize },
+    /// Choices picked by weight, where `weights` holds each choice's cumulative weight,
+    /// normalized so the last is 1.0
+    WeightedChoice { choices: Vec<String>, weights: Vec<f64>, choice_length: usize, length: usize },
+    Company { kind: CompanyPart },
+    Name { part: NamePart },
+    Product,
+    /// Sentences of lorem ipsum filler text
+    Text { min_words: usize, max_words: usize },
+    Phone { style: PhoneStyle },
+    /// Card numbers of the issuer that pass the Luhn checksum
+    CreditCard { issuer: CardIssuer },
+    /// IP addresses within a network, which is the whole address space when no CIDR
+    /// block was given
+    IpAddress { network: IpNetwork },
+    /// MAC addresses, locally administered ones when `local` is set
+    MacAddress { separator: String, uppercase: bool, local: bool },
+    Email { domains: Vec<String>, min_length: usize, max_length: usize },
+    Duration { min_seconds: u64, max_seconds: u64, format: DurationFormat },
+    Correlated,
+    Cycle { start: i64, end: i64, counter: AtomicU64 },
+    /// Counts up by step from start, one value per row across all threads
+    Sequence { start: i64, step: i64, counter: AtomicU64 },
+    /// Times that advance by step_seconds per row, with jitter, across all threads
+    Timestamp {
+        start_epoch: i64,
+        step_seconds: i64,
+        jitter_seconds: i64,
+        format: TimestampFormat,
+        counter: AtomicU64
+    },
+    Gender { labels: Vec<String>, weights: Option<Vec<f64>> },
+    /// Timestamps that increase within each value of an earlier key field, but not across keys
+    PerKeyEventTime {
+        key_field: String,
+        start: String,
+        avg_gap_ms: u64,
+        start_ms: i64,
+        last_times: Mutex<HashMap<String, i64>>
+    },
+    /// Computed from the values of other fields of the row, named by `inputs`, once they
+    /// have been generated
+    Derived { expression: Expression, inputs: Vec<String> },
+    /// Fills the `{0}`, `{1}`, ... placeholders of `format` with the rendered values of its
+    /// parts, which are generated like fields of their own
+    Template { format: String, parts: Vec<Field> },
+    /// Values from a generator supplied by a library user, rather than from a schema
+    Custom(Box<dyn Generator>)
+}
+
+impl FieldGenerator {
+    /// The name the generator is given in a schema
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FieldGenerator::NoGen => "none",
+            FieldGenerator::Constant{ .. } => "constant",
+            FieldGenerator::Integer{ .. } => "integer",
+            FieldGenerator::Decimal{ .. } => "decimal",
+            FieldGenerator::Currency{ .. } => "currency",
+            FieldGenerator::GeoCoord{ .. } => "geo_coord",
+            FieldGenerator::Gauss{ .. } => "gauss",
+            FieldGenerator::GaussF32{ .. } => "gauss_f32",
+            FieldGenerator::Date{ .. } => "date",
+            FieldGenerator::DateTime => "datetime",
+            FieldGenerator::String{ .. } => "string",
+            FieldGenerator::Bytes{ .. } => "bytes",
+            FieldGenerator::Regex{ .. } => "regex",
+            FieldGenerator::Choice{ .. } | FieldGenerator::WeightedChoice{ .. } => "choice",
+            FieldGenerator::Company{ .. } => "company",
+            FieldGenerator::Name{ .. } => "name",
+            FieldGenerator::Product => "product",
+            FieldGenerator::Text{ .. } => "text",
+            FieldGenerator::Phone{ .. } => "phone",
+            FieldGenerator::CreditCard{ .. } => "credit_card",
+            FieldGenerator::IpAddress{ .. } => "ip",
+            FieldGenerator::MacAddress{ .. } => "mac",
+            FieldGenerator::Email{ .. } => "email",
+            FieldGenerator::Duration{ .. } => "duration",
+            FieldGenerator::Correlated => "correlated",
+            FieldGenerator::Cycle{ .. } => "cycle",
+            FieldGenerator::Sequence{ .. } => "sequence",
+            FieldGenerator::Timestamp{ .. } => "timestamp",
+            FieldGenerator::Gender{ .. } => "gender",
+            FieldGenerator::PerKeyEventTime{ .. } => "per_key_event_time",
+            FieldGenerator::Derived{ .. } => "derived",
+            FieldGenerator::Template{ .. } => "template",
+            FieldGenerator::Custom(_) => "custom"
+        }
+    }
+}
+
+/// The values already produced by a unique field, shared by every generator thread
+///
+/// Only a 64-bit hash of each value is kept, so the check is probabilistic: two different
+/// values whose hashes collide count as the same value, and the second is regenerated (or
+/// the field reported exhausted).  The chance of any collision is roughly n² / 2^65 for n
+/// values, negligible below hundreds of millions of values.
+pub struct UniqueValues {
+    seen: Mutex<HashSet<u64>>
+}
+
+impl UniqueValues {
+    pub fn new() -> UniqueValues {
+        UniqueValues { seen: Mutex::new(HashSet::new()) }
+    }
+
+    /// Records the value, returning false if it was already produced
+    pub fn insert(&self, value: &TypedValue) -> bool {
+        let mut hasher = DefaultHasher::new();
+        value.to_string().hash(&mut hasher);
+        self.seen.lock().unwrap().insert(hasher.finish())
+    }
+
+    /// The number of distinct values produced so far
+    pub fn count(&self) -> usize {
+        self.seen.lock().unwrap().len()
+    }
 }
 
 pub struct Field {
@@ -23,31 +292,623 @@ pub struct Field {
     pub data_type: String,
     pub length: Option<usize>,
     pub padding: Option<char>,
+    pub clamp: Option<Clamp>,
+    pub nullable: bool,
+    /// Chance of generating a null instead of a value, between 0 and 1
+    pub null_probability: f64,
+    /// Written out in place of nulls
+    pub null_token: String,
+    pub primary_key: bool,
+    pub unique: Option<UniqueValues>,
+    /// A printf-style format applied when the value is written out as text
+    pub format: Option<FormatSpec>,
     pub generator: FieldGenerator
 }
 
-impl Generator for Field {
-    fn generate<R: rand::Rng>(&self, rng: &mut R) -> String {
+impl Field {
+    /// Creates a field with the given generator, and the same defaults as a field read from
+    /// a schema: nullable but never null, neither a key nor unique, and with no length,
+    /// padding, clamp or format.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// # extern crate rand;
+    /// use rand::Rng;
+    /// use fourree::json::parse_json;
+    /// use fourree::schema::{Field, FieldGenerator, TypedValue};
+    ///
+    /// # fn main() {
+    /// let mut schema = parse_json(r#"{"table_name": "t", "fields": []}"#).unwrap();
+    /// let id = FieldGenerator::Integer{ min: 1, max: 1, step: 1 };
+    /// schema.add_field(Field::new("id", "integer", id));
+    /// schema.add_field(Field::new("score", "integer", FieldGenerator::Custom(Box::new(
+    ///     |mut rng: &mut dyn Rng| TypedValue::Integer((&mut rng).gen_range(10, 100))
+    /// ))));
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let row = schema.generate_row(&mut rng).unwrap();
+    /// let score: i64 = row.trim_left_matches("1\t").parse().unwrap();
+    /// assert!(score >= 10 && score < 100);
+    /// # }
+    /// ```
+    pub fn new(name: &str, data_type: &str, generator: FieldGenerator) -> Field {
+        Field {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            length: None,
+            padding: None,
+            clamp: None,
+            nullable: true,
+            null_probability: 0.0,
+            null_token: String::new(),
+            primary_key: false,
+            unique: None,
+            format: None,
+            generator: generator
+        }
+    }
+
+    /// Renders a generated value as text, using the field's format when it has one, and
+    /// padding it to the field's length when both length and padding are declared.  Nulls
+    /// are rendered as the null token, which is padded but never formatted.
+    fn render(&self, value: &TypedValue) -> String {
+        let data = match *value {
+            TypedValue::Null => self.null_token.clone(),
+            _ => self.format_value(value)
+        };
+
+        match (self.length, self.padding) {
+            (Some(length), Some(p)) => data.as_str().pad(length, p, Alignment::Right, false),
+            _ => data
+        }
+    }
+
+    /// Renders a generated value as text using the field's format, without any padding
+    fn format_value(&self, value: &TypedValue) -> String {
+        match self.format {
+            Some(ref format) => format.apply(value),
+            None => value.to_string()
+        }
+    }
+
+    /// Renders a generated value as JSON: null for nulls, a number when the field's
+    /// data_type is numeric or its generator is integer or gauss and the value parses as
+    /// one, a boolean when the data_type is boolean and the value reads as one, and a
+    /// string otherwise.
+    fn render_json(&self, value: &TypedValue) -> Value {
+        if let TypedValue::Null = *value {
+            return Value::Null;
+        }
+
+        let data = self.format_value(value);
+        let numeric = match self.generator {
+            FieldGenerator::Integer{ .. } | FieldGenerator::Gauss{ .. } |
+            FieldGenerator::GaussF32{ .. } => true,
+            _ => is_numeric_type(&self.data_type)
+        };
+        if is_boolean_type(&self.data_type) {
+            match data.trim().to_lowercase().as_str() {
+                "true" | "t" | "1" => return Value::Bool(true),
+                "false" | "f" | "0" => return Value::Bool(false),
+                _ => {}
+            }
+        }
+        if numeric {
+            if let Ok(i) = data.trim().parse::<i64>() {
+                return Value::from(i);
+            }
+            if let Some(n) = data.trim().parse::<f64>().ok().and_then(Number::from_f64) {
+                return Value::Number(n);
+            }
+        }
+        Value::String(data)
+    }
+
+    /// Returns how many distinct values the field's generator can produce, ignoring any
+    /// clamp.  Counts that do not fit in a u64, continuous distributions and custom
+    /// generators are unbounded.
+    pub fn cardinality(&self) -> Cardinality {
+        match self.generator {
+            FieldGenerator::NoGen | FieldGenerator::Constant{ .. } => Cardinality::Finite(1),
+            FieldGenerator::Integer{ min, max, step: 1 } => {
+                Cardinality::Finite(max.saturating_sub(min).saturating_add(1).max(0) as u64)
+            }
+            FieldGenerator::Integer{ min, max, step } => match step_range(min, max, step) {
+                Some((low, high)) => Cardinality::Finite((high - low + 1) as u64),
+                None => Cardinality::Finite(0)
+            },
+            FieldGenerator::Decimal{ min, max, precision } |
+            FieldGenerator::GeoCoord{ min, max, precision, .. } => {
+                let low = decimal_bound(min, precision, true);
+                let high = decimal_bound(max, precision, false);
+                Cardinality::Finite(high.saturating_sub(low).saturating_add(1).max(0) as u64)
+            }
+            FieldGenerator::Currency{ min, max, .. } => {
+                let low = decimal_bound(min, 2, true);
+                let high = decimal_bound(max, 2, false);
+                Cardinality::Finite(high.saturating_sub(low).saturating_add(1).max(0) as u64)
+            }
+            FieldGenerator::Gauss{ .. } | FieldGenerator::GaussF32{ .. } |
+            FieldGenerator::Correlated | FieldGenerator::PerKeyEventTime{ .. } |
+            FieldGenerator::Sequence{ .. } | FieldGenerator::Timestamp{ .. } |
+            FieldGenerator::Regex{ .. } | FieldGenerator::Derived{ .. } |
+            FieldGenerator::Custom(_) => {
+                Cardinality::Unbounded
+            }
+            FieldGenerator::Date{ range: Some((min, max)) } => {
+                Cardinality::Finite((max - min + 1).max(0) as u64)
+            }
+            // 116 years of 365 days each
+            FieldGenerator::Date{ range: None } => Cardinality::Finite(116 * 365),
+            // Every second of those days
+            FieldGenerator::DateTime => Cardinality::Finite(116 * 365 * 24 * 60 * 60),
+            FieldGenerator::String{ length, ref charset } => {
+                Cardinality::power(charset.size(), length)
+            }
+            FieldGenerator::Choice{ ref choices, choice_length, length } => {
+                Cardinality::power(choices.len() as u64, choice_count(choice_length, length))
+            }
+            FieldGenerator::WeightedChoice{ ref weights, choice_length, length, .. } => {
+                let num_choices = choice_count(choice_length, length);
+                // Choices without any weight of their own are never picked
+                let possible = weights.iter().enumerate()
+                    .filter(|&(i, w)| *w > if i == 0 { 0.0 } else { weights[i - 1] })
+                    .count();
+                Cardinality::power(possible as u64, num_choices)
+            }
+            FieldGenerator::Company{ kind } => match kind {
+                CompanyPart::Name => Cardinality::Finite(COMPANY_NAMES.len() as u64),
+                CompanyPart::Suffix => Cardinality::Finite(COMPANY_SUFFIXES.len() as u64),
+                CompanyPart::Full => Cardinality::Finite(
+                    (COMPANY_NAMES.len() * COMPANY_SUFFIXES.len()) as u64)
+            },
+            FieldGenerator::Name{ part } => match part {
+                NamePart::First => Cardinality::Finite(FIRST_NAMES.len() as u64),
+                NamePart::Last => Cardinality::Finite(LAST_NAMES.len() as u64),
+                NamePart::Full => Cardinality::Finite((FIRST_NAMES.len() * LAST_NAMES.len()) as u64)
+            },
+            FieldGenerator::Product => Cardinality::Finite(
+                (PRODUCT_ADJECTIVES.len() * PRODUCT_MATERIALS.len() * PRODUCT_NOUNS.len()) as u64),
+            FieldGenerator::Phone{ style: PhoneStyle::E164{ ref country_codes, min_length,
+                                                            max_length } } => {
+                (min_length..max_length + 1).fold(Cardinality::Finite(0), |acc, length| {
+                    match (acc, Cardinality::power(10, length)) {
+                        (Cardinality::Finite(a), Cardinality::Finite(b)) => {
+                            a.checked_add(b).map_or(Cardinality::Unbounded, Cardinality::Finite)
+                        }
+                        _ => Cardinality::Unbounded
+                    }
+                }).product(Cardinality::Finite(country_codes.len() as u64))
+            }
+            FieldGenerator::Phone{ style: PhoneStyle::Mask{ ref format } } => {
+                Cardinality::power(10, format.matches('#').count())
+            }
+            FieldGenerator::Text{ min_words, max_words } => {
+                (min_words..max_words + 1).fold(Cardinality::Finite(0), |acc, words| {
+                    match (acc, Cardinality::power(LOREM_WORDS.len() as u64, words)) {
+                        (Cardinality::Finite(a), Cardinality::Finite(b)) => {
+                            a.checked_add(b).map_or(Cardinality::Unbounded, Cardinality::Finite)
+                        }
+                        _ => Cardinality::Unbounded
+                    }
+                })
+            }
+            FieldGenerator::Email{ ref domains, min_length, max_length } => {
+                (min_length..max_length + 1).fold(Cardinality::Finite(0), |acc, length| {
+                    match (acc, Cardinality::power(EMAIL_LOCAL_CHARS_COUNT, length)) {
+                        (Cardinality::Finite(a), Cardinality::Finite(b)) => {
+                            a.checked_add(b).map_or(Cardinality::Unbounded, Cardinality::Finite)
+                        }
+                        _ => Cardinality::Unbounded
+                    }
+                }).product(Cardinality::Finite(domains.len() as u64))
+            }
+            FieldGenerator::IpAddress{ ref network } => {
+                Cardinality::power(2, network.host_bits() as usize)
+            }
+            FieldGenerator::Bytes{ length, .. } => Cardinality::power(256, length),
+            // A locally administered address fixes two bits of the first octet
+            FieldGenerator::MacAddress{ local, .. } => {
+                Cardinality::power(2, if local { 46 } else { 48 })
+            }
+            FieldGenerator::CreditCard{ issuer } => {
+                issuer.prefixes().iter().fold(Cardinality::Finite(0), |acc, prefix| {
+                    let free_digits = issuer.length() - prefix.len() - 1;
+                    match (acc, Cardinality::power(10, free_digits)) {
+                        (Cardinality::Finite(a), Cardinality::Finite(b)) => {
+                            a.checked_add(b).map_or(Cardinality::Unbounded, Cardinality::Finite)
+                        }
+                        _ => Cardinality::Unbounded
+                    }
+                })
+            }
+            FieldGenerator::Gender{ ref labels, .. } => Cardinality::Finite(labels.len() as u64),
+            FieldGenerator::Cycle{ start, end, .. } => {
+                (end.wrapping_sub(start) as u64).checked_add(1)
+                    .map_or(Cardinality::Unbounded, Cardinality::Finite)
+            }
+            FieldGenerator::Duration{ min_seconds, max_seconds, .. } => {
+                max_seconds.saturating_sub(min_seconds).checked_add(1)
+                    .map_or(Cardinality::Unbounded, Cardinality::Finite)
+            }
+            // Different parts can fill in the same text, so this is an upper bound
+            FieldGenerator::Template{ ref parts, .. } => {
+                parts.iter().fold(Cardinality::Finite(1),
+                                  |acc, part| acc.product(part.cardinality()))
+            }
+        }
+    }
+
+    /// Generates a value, regenerating it up to max_retries times when the field is unique
+    /// and the value was already produced
+    fn generate_unique<R: rand::Rng>(&self, rng: &mut R, max_retries: u64)
+            -> Result<TypedValue, String> {
+        let unique = match self.unique {
+            Some(ref u) => u,
+            None => return self.generate(rng)
+        };
+
+        let mut retries = 0;
+        loop {
+            let value = self.generate(rng)?;
+            // Nulls are never duplicates of each other
+            if value == TypedValue::Null || unique.insert(&value) {
+                return Ok(value);
+            }
+
+            retries += 1;
+            if retries > max_retries {
+                return Err(format!(
+                    "Unique values exhausted for field {}: no new value found after {} attempts, \
+                     the generator appears to produce only {} distinct values.",
+                    self.name, max_retries, unique.count()));
+            }
+        }
+    }
+
+    /// Checks the generator's configuration, so invalid settings are reported up front
+    /// rather than by a panic partway through generation
+    fn prepare(&self) -> Result<(), String> {
+        let problem = match self.generator {
+            FieldGenerator::Integer{ min, max, .. } if min > max => {
+                Some(format!("min ({}) must not be greater than max ({})", min, max))
+            }
+            FieldGenerator::Integer{ step, .. } if step < 1 => {
+                Some(format!("step ({}) must be positive", step))
+            }
+            FieldGenerator::Integer{ min, max, step } if step_range(min, max, step).is_none() => {
+                Some(format!("no multiple of step ({}) lies from min ({}) to max ({})",
+                             step, min, max))
+            }
+            FieldGenerator::Gauss{ std_dev, .. } if std_dev < 0 => {
+                Some("std_dev must not be negative".to_string())
+            }
+            FieldGenerator::GaussF32{ std_dev, .. } if !(std_dev >= 0.0) => {
+                Some("std_dev must not be negative".to_string())
+            }
+            FieldGenerator::Gauss{ min: Some(min), max: Some(max), .. } if min > max => {
+                Some(format!("min ({}) must not be greater than max ({})", min, max))
+            }
+            FieldGenerator::GaussF32{ min: Some(min), max: Some(max), .. } if min > max => {
+                Some(format!("min ({}) must not be greater than max ({})", min, max))
+            }
+            FieldGenerator::Choice{ ref choices, choice_length, .. } |
+            FieldGenerator::WeightedChoice{ ref choices, choice_length, .. } => {
+                if choices.is_empty() {
+                    Some("at least one choice is required".to_string())
+                } else if choice_length == 0 {
+                    Some("choices must not all be empty strings".to_string())
+                } else {
+                    None
+                }
+            }
+            FieldGenerator::Phone{ style: PhoneStyle::E164{ ref country_codes, min_length,
+                                                            max_length } } => {
+                if country_codes.is_empty() {
+                    Some("at least one country code is required".to_string())
+                } else if min_length > max_length {
+                    Some("min_length must not be greater than max_length".to_string())
+                } else {
+                    None
+                }
+            }
+            FieldGenerator::Phone{ style: PhoneStyle::Mask{ ref format } }
+                    if !format.contains('#') => {
+                Some("the format must contain at least one '#'".to_string())
+            }
+            FieldGenerator::Duration{ min_seconds, max_seconds, .. }
+                    if min_seconds > max_seconds => {
+                Some("min_seconds must not be greater than max_seconds".to_string())
+            }
+            FieldGenerator::Template{ ref format, ref parts } => {
+                for part in parts.iter() {
+                    part.prepare()?;
+                }
+                fill_template(format, &vec![String::new(); parts.len()]).err()
+            }
+            _ => None
+        };
+
+        match problem {
+            Some(p) => Err(format!("Invalid generator for field {}: {}.", self.name, p)),
+            None => Ok(())
+        }
+    }
+
+    /// Generates the field's next event time for the key, when it is a per key event time field
+    fn event_time<R: rand::Rng>(&self, rng: &mut R, key: &TypedValue)
+            -> Result<TypedValue, String> {
         match self.generator {
-            FieldGenerator::Integer{ min, max } => {
-                generate_integer(rng, min, max).to_string()
+            FieldGenerator::PerKeyEventTime{ start_ms, avg_gap_ms, ref last_times, .. } => {
+                let time = generate_event_time(rng, last_times, &key.to_string(), start_ms,
+                                               avg_gap_ms);
+                Ok(TypedValue::Text(format_event_time(time)))
+            }
+            _ => self.generate(rng)
+        }
+    }
+
+    /// Converts a correlated sample into this field's type, rounding for integer columns
+    fn correlated_value(&self, sample: f64) -> TypedValue {
+        match self.data_type.as_str() {
+            "integer" => TypedValue::Integer(sample.round() as i64),
+            _ => TypedValue::Float(sample as f32)
+        }
+    }
+}
+
+impl Field {
+    /// Generates a single value for the field, which may be a null, clamped when the field
+    /// has a clamp.  Correlated, derived and per key event time fields depend on the rest
+    /// of their row, so only `Schema::generate_values` produces them, and here they are an
+    /// error.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// # extern crate rand;
+    /// use fourree::json::parse_json;
+    ///
+    /// # fn main() {
+    /// let schema = parse_json(r#"{"table_name": "t", "fields": [
+    ///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 7, "max": 7},
+    ///     {"name": "next", "data_type": "integer", "generator": "derived",
+    ///      "expression": "id + 1"}
+    /// ]}"#).unwrap();
+    /// let mut rng = rand::thread_rng();
+    /// assert_eq!(schema.fields[0].generate(&mut rng).unwrap().to_string(), "7");
+    /// assert!(schema.fields[1].generate(&mut rng).is_err());
+    /// # }
+    /// ```
+    pub fn generate<R: rand::Rng>(&self, rng: &mut R) -> Result<TypedValue, String> {
+        // Only draw when nulls are possible, so schemas without them generate the same values
+        if self.null_probability > 0.0 && rng.gen::<f64>() < self.null_probability {
+            return Ok(TypedValue::Null);
+        }
+
+        let value = match self.generator {
+            FieldGenerator::Constant{ ref value } => TypedValue::Text(value.clone()),
+            FieldGenerator::Integer{ min, max, step } => {
+                TypedValue::Integer(generate_stepped_integer(rng, min, max, step))
+            }
+            FieldGenerator::Decimal{ min, max, precision } => {
+                TypedValue::Text(generate_decimal(rng, min, max, precision))
+            }
+            FieldGenerator::Currency{ min, max, ref symbol, grouping } => {
+                TypedValue::Text(generate_currency(rng, min, max, symbol, grouping))
+            }
+            FieldGenerator::GeoCoord{ kind: GeoKind::Latitude, min, max, precision } => {
+                TypedValue::Text(generate_latitude(rng, min, max, precision))
+            }
+            FieldGenerator::GeoCoord{ kind: GeoKind::Longitude, min, max, precision } => {
+                TypedValue::Text(generate_longitude(rng, min, max, precision))
+            }
+            FieldGenerator::Gauss{ mean, std_dev, min, max } => {
+                TypedValue::Integer(generate_gauss(rng, mean, std_dev, min, max) as i64)
+            }
+            FieldGenerator::GaussF32{ mean, std_dev, min, max } => {
+                TypedValue::Float(generate_gauss_f32(rng, mean, std_dev, min, max))
+            }
+            FieldGenerator::String{ length, ref charset } => {
+                TypedValue::Text(generate_charset_string(rng, length, charset))
+            }
+            FieldGenerator::Bytes{ length, encoding } => {
+                TypedValue::Text(encode_bytes(&generate_bytes(rng, length), encoding))
             }
-            FieldGenerator::Gauss{ mean, std_dev } => {
-                generate_gauss(rng, mean, std_dev).to_string()
+            FieldGenerator::Regex{ ref node, .. } => {
+                TypedValue::Text(node.generate(rng))
             }
-            FieldGenerator::GaussF32{ mean, std_dev } => {
-                generate_gauss_f32(rng, mean, std_dev).to_string()
+            FieldGenerator::Date{ range: Some((min, max)) } => {
+                TypedValue::Text(generate_date_between(rng, min, max).to_string())
             }
-            FieldGenerator::String{ length } => {
-                generate_string(rng, length)
+            FieldGenerator::Date{ range: None } => {
+                TypedValue::Text(generate_date(rng).to_string())
             }
-            FieldGenerator::Date => {
-                generate_date(rng).to_string()
+            FieldGenerator::DateTime => {
+                TypedValue::Text(generate_datetime(rng))
             }
             FieldGenerator::Choice{ ref choices, choice_length, length } => {
-                generate_choice(rng, choices.as_slice(), choice_length, length).to_string()
+                TypedValue::Text(generate_choice(rng, choices.as_slice(),
+                                                 choice_count(choice_length, length)))
             }
-            _ => "None".to_string()
+            FieldGenerator::WeightedChoice{ ref choices, ref weights, choice_length, length } => {
+                TypedValue::Text(generate_weighted_choice(rng, choices, weights,
+                                                          choice_count(choice_length, length)))
+            }
+            FieldGenerator::Company{ kind } => {
+                TypedValue::Text(generate_company(rng, kind))
+            }
+            FieldGenerator::Name{ part } => {
+                TypedValue::Text(generate_name(rng, part))
+            }
+            FieldGenerator::Product => {
+                TypedValue::Text(generate_product(rng))
+            }
+            FieldGenerator::Text{ min_words, max_words } => {
+                TypedValue::Text(generate_text(rng, min_words, max_words))
+            }
+            FieldGenerator::Phone{ ref style } => {
+                TypedValue::Text(generate_phone(rng, style))
+            }
+            FieldGenerator::IpAddress{ ref network } => {
+                TypedValue::Text(generate_ip(rng, network))
+            }
+            FieldGenerator::MacAddress{ ref separator, uppercase, local } => {
+                TypedValue::Text(generate_mac(rng, separator, uppercase, local))
+            }
+            FieldGenerator::CreditCard{ issuer } => {
+                let prefix = rng.choose(issuer.prefixes()).unwrap();
+                TypedValue::Text(generate_credit_card(rng, prefix, issuer.length()))
+            }
+            FieldGenerator::Email{ ref domains, min_length, max_length } => {
+                TypedValue::Text(generate_email(rng, domains, min_length, max_length))
+            }
+            FieldGenerator::Cycle{ start, end, ref counter } => {
+                TypedValue::Integer(generate_cycle(counter, start, end))
+            }
+            FieldGenerator::Sequence{ start, step, ref counter } => {
+                TypedValue::Integer(generate_sequence(counter, start, step))
+            }
+            FieldGenerator::Timestamp{ start_epoch, step_seconds, jitter_seconds, format,
+                                       ref counter } => {
+                let seconds = generate_timestamp(rng, counter, start_epoch, step_seconds,
+                                                 jitter_seconds);
+                match format {
+                    TimestampFormat::Epoch => TypedValue::Integer(seconds),
+                    _ => TypedValue::Text(format_timestamp(seconds, format))
+                }
+            }
+            FieldGenerator::Gender{ ref labels, ref weights } => {
+                let weights = weights.as_ref().map(|w| w.as_slice());
+                TypedValue::Text(generate_gender(rng, labels, weights))
+            }
+            FieldGenerator::Duration{ min_seconds, max_seconds, format } => {
+                let seconds = generate_duration(rng, min_seconds, max_seconds);
+                match format {
+                    DurationFormat::Seconds => TypedValue::Integer(seconds as i64),
+                    _ => TypedValue::Text(format_duration(seconds, format))
+                }
+            }
+            FieldGenerator::Template{ ref format, ref parts } => {
+                let values = parts.iter()
+                    .map(|part| part.generate(rng).map(|value| part.render(&value)))
+                    .collect::<Result<Vec<String>, String>>()?;
+                // prepare() and the parser both check the placeholders against the parts
+                TypedValue::Text(fill_template(format, &values).unwrap())
+            }
+            FieldGenerator::Custom(ref generator) => generator.generate(rng),
+            FieldGenerator::NoGen => TypedValue::Text(String::new()),
+            FieldGenerator::Correlated | FieldGenerator::Derived{ .. } |
+            FieldGenerator::PerKeyEventTime{ .. } => {
+                return Err(format!("Field {} can only be generated along with the rest of its \
+                                    row.", self.name))
+            }
+        };
+
+        match self.clamp {
+            Some(ref clamp) => Ok(clamp.apply(value)),
+            None => Ok(value)
+        }
+    }
+}
+
+/// Whether a SQL column type holds numbers, e.g. "integer", "bigint" or "numeric(10,2)"
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::schema::is_numeric_type;
+///
+/// # fn main() {
+/// assert!(is_numeric_type("integer"));
+/// assert!(is_numeric_type("NUMERIC(10, 2)"));
+/// assert!(is_numeric_type("double precision"));
+/// assert!(!is_numeric_type("varchar(10)"));
+/// # }
+/// ```
+pub fn is_numeric_type(data_type: &str) -> bool {
+    let base = data_type.split('(').next().unwrap_or("").trim().to_lowercase();
+    match base.as_str() {
+        "smallint" | "integer" | "int" | "bigint" | "int2" | "int4" | "int8" | "tinyint" |
+        "mediumint" | "smallserial" | "serial" | "bigserial" | "numeric" | "decimal" |
+        "real" | "float" | "float4" | "float8" | "double" | "double precision" => true,
+        _ => false
+    }
+}
+
+/// Whether a SQL column type holds booleans, i.e. "boolean" or "bool"
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::schema::is_boolean_type;
+///
+/// # fn main() {
+/// assert!(is_boolean_type("boolean"));
+/// assert!(is_boolean_type("BOOL"));
+/// assert!(!is_boolean_type("bit(1)"));
+/// # }
+/// ```
+pub fn is_boolean_type(data_type: &str) -> bool {
+    match data_type.trim().to_lowercase().as_str() {
+        "boolean" | "bool" => true,
+        _ => false
+    }
+}
+
+/// A pair of fields drawn together from a bivariate normal distribution, referenced by
+/// their index in the schema's fields
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Correlation {
+    pub fields: (usize, usize),
+    pub means: (f64, f64),
+    pub std_devs: (f64, f64),
+    pub coefficient: f64
+}
+
+/// When delimited output wraps fields in double quotes, RFC 4180 style
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Quoting {
+    /// Fields are written as generated
+    None,
+    /// Only fields containing the delimiter, a quote or a line break are quoted
+    Minimal,
+    /// Every field is quoted
+    All
+}
+
+impl Quoting {
+    /// Quotes the rendered field if this mode calls for it, doubling any quotes inside it.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// use fourree::schema::Quoting;
+    ///
+    /// # fn main() {
+    /// assert_eq!(Quoting::Minimal.apply("plain", ",", "\n"), "plain");
+    /// assert_eq!(Quoting::Minimal.apply("Smith, John", ",", "\n"), "\"Smith, John\"");
+    /// assert_eq!(Quoting::Minimal.apply("6'2\"", ",", "\n"), "\"6'2\"\"\"");
+    /// assert_eq!(Quoting::All.apply("plain", ",", "\n"), "\"plain\"");
+    /// assert_eq!(Quoting::None.apply("Smith, John", ",", "\n"), "Smith, John");
+    /// # }
+    /// ```
+    pub fn apply(&self, field: &str, delimiter: &str, record_separator: &str) -> String {
+        let quote = match *self {
+            Quoting::None => false,
+            Quoting::Minimal => {
+                (!delimiter.is_empty() && field.contains(delimiter)) ||
+                    field.contains(record_separator) ||
+                    field.contains(|c| c == '"' || c == '\n' || c == '\r')
+            },
+            Quoting::All => true
+        };
+
+        if quote {
+            format!("\"{}\"", field.replace("\"", "\"\""))
+        } else {
+            field.to_string()
         }
     }
 }
@@ -55,7 +916,16 @@ impl Generator for Field {
 pub struct Schema {
     pub table_name: String,
     pub delimiter: String,
-    pub fields: Vec<Field>
+    /// Whether delimited rows and the header quote their fields
+    pub quoting: Quoting,
+    /// Terminates every row, including the header
+    pub record_separator: String,
+    pub fields: Vec<Field>,
+    pub correlated: Vec<Correlation>,
+    /// How many times a unique field regenerates a duplicate value before giving up
+    pub unique_max_retries: u64,
+    /// The field whose value keys each Kafka message, unless --kafka_key names another
+    pub kafka_key: Option<String>
 }
 
 impl Schema {
@@ -63,11 +933,119 @@ impl Schema {
         self.fields.push(f);
     }
 
+    /// Initializes and validates every field's generator before any rows are generated, so
+    /// that a misconfigured schema fails immediately instead of on its first row.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// use fourree::json::parse_json;
+    ///
+    /// # fn main() {
+    /// let schema = parse_json(r#"{"table_name": "t", "fields": [
+    ///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 10, "max": 5}
+    /// ]}"#).unwrap();
+    ///
+    /// let error = schema.prepare().err().unwrap();
+    /// assert!(error.contains("id"));
+    /// # }
+    /// ```
+    pub fn prepare(&self) -> Result<(), String> {
+        for field in self.fields.iter() {
+            field.prepare()?;
+        }
+        Ok(())
+    }
+
+    /// Combines two schemas horizontally, appending the other schema's fields after this
+    /// one's.  The merged schema keeps this schema's table name.  Fails if a field name
+    /// appears in both, or if the schemas use different delimiters or record separators.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// # extern crate rand;
+    /// use fourree::json::parse_json;
+    ///
+    /// # fn main() {
+    /// let ids = parse_json(r#"{"table_name": "orders", "fields": [
+    ///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10},
+    ///     {"name": "created", "data_type": "date", "generator": "date"}
+    /// ]}"#).unwrap();
+    /// let amounts = parse_json(r#"{"table_name": "amounts", "fields": [
+    ///     {"name": "net", "data_type": "integer", "generator": "integer", "min": 0, "max": 10},
+    ///     {"name": "tax", "data_type": "integer", "generator": "integer", "min": 0, "max": 10},
+    ///     {"name": "currency", "data_type": "char(3)", "generator": "choice", "choices": ["USD"]}
+    /// ]}"#).unwrap();
+    ///
+    /// let schema = ids.merge(amounts).unwrap();
+    /// assert_eq!(schema.fields.len(), 5);
+    /// assert_eq!(schema.generate_header(), "id\tcreated\tnet\ttax\tcurrency\n");
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let row = schema.generate_row(&mut rng).unwrap();
+    /// assert_eq!(row.split('\t').count(), 5);
+    /// assert!(row.ends_with("\tUSD"));
+    ///
+    /// let duplicate = parse_json(r#"{"table_name": "more", "fields": [
+    ///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10}
+    /// ]}"#).unwrap();
+    /// assert!(schema.merge(duplicate).err().unwrap().contains("id"));
+    /// # }
+    /// ```
+    pub fn merge(mut self, other: Schema) -> Result<Schema, String> {
+        if self.delimiter != other.delimiter {
+            return Err(format!("Cannot merge schemas with delimiters {:?} and {:?}.",
+                               self.delimiter, other.delimiter));
+        }
+        if self.record_separator != other.record_separator {
+            return Err(format!("Cannot merge schemas with record separators {:?} and {:?}.",
+                               self.record_separator, other.record_separator));
+        }
+        let collision = other.fields.iter().find(|o| self.fields.iter().any(|f| f.name == o.name));
+        if let Some(field) = collision {
+            return Err(format!("Cannot merge schemas, both contain field {}.", field.name));
+        }
+
+        // The other schema's correlated pairs refer to its fields by index
+        let offset = self.fields.len();
+        self.correlated.extend(other.correlated.into_iter().map(|c| Correlation {
+            fields: (c.fields.0 + offset, c.fields.1 + offset),
+            ..c
+        }));
+        self.fields.extend(other.fields);
+        Ok(self)
+    }
+
+    /// Generates the header row: the field names joined by the delimiter (or simply
+    /// concatenated for fixed width), followed by the record separator.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// use fourree::json::parse_json;
+    ///
+    /// # fn main() {
+    /// let schema = parse_json(r#"{"table_name": "t", "fields": [
+    ///     {"name": "a", "data_type": "integer", "generator": "integer", "min": 0, "max": 10},
+    ///     {"name": "b", "data_type": "integer", "generator": "integer", "min": 0, "max": 10}
+    /// ]}"#).unwrap();
+    /// assert_eq!(schema.generate_header(), "a\tb\n");
+    ///
+    /// let fixed = parse_json(r#"{"table_name": "t", "delimiter": "fixed", "fields": [
+    ///     {"name": "a", "data_type": "integer", "generator": "integer", "min": 0, "max": 10,
+    ///      "length": 2},
+    ///     {"name": "b", "data_type": "integer", "generator": "integer", "min": 0, "max": 10,
+    ///      "length": 2}
+    /// ]}"#).unwrap();
+    /// assert_eq!(fixed.generate_header(), "ab\n");
+    /// # }
+    /// ```
     pub fn generate_header(&self) -> String {
         let mut result = Vec::with_capacity(self.fields.len());
 
         for field in self.fields.iter() {
-            result.push(field.name.clone())
+            result.push(self.quoting.apply(&field.name, &self.delimiter, &self.record_separator))
         }
 
         let delim = match self.delimiter.as_str() {
@@ -76,56 +1054,390 @@ impl Schema {
         };
 
         let mut result_string = result.join(delim);
-        result_string.push('\n');
+        result_string.push_str(&self.record_separator);
         result_string
     }
 
-    pub fn generate_row(&self, rng: &mut rand::ThreadRng) -> Result<String, String> {
-        let mut result = Vec::with_capacity(self.fields.len());
+    /// Generates a CREATE TABLE statement for the schema, using each field's data_type as
+    /// the column type.  Fixed width schemas get the same column types; the field lengths
+    /// only affect how rows are written.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// use fourree::json::parse_json;
+    ///
+    /// # fn main() {
+    /// let schema = parse_json(r#"{"table_name": "users", "fields": [
+    ///     {"name": "id", "data_type": "integer", "generator": "integer",
+    ///      "min": 0, "max": 10, "primary_key": true},
+    ///     {"name": "name", "data_type": "varchar(8)", "generator": "string",
+    ///      "length": 8, "nullable": false},
+    ///     {"name": "nickname", "data_type": "varchar(8)", "generator": "string", "length": 8}
+    /// ]}"#).unwrap();
+    ///
+    /// assert_eq!(schema.generate_ddl(),
+    ///            "CREATE TABLE users (\n    id integer NOT NULL,\n    name varchar(8) NOT NULL,\n    \
+    ///             nickname varchar(8),\n    PRIMARY KEY (id)\n);\n");
+    ///
+    /// let fixed = parse_json(r#"{"table_name": "codes", "delimiter": "fixed", "fields": [
+    ///     {"name": "id", "data_type": "integer", "generator": "integer",
+    ///      "min": 0, "max": 10, "length": 2, "padding": "0"},
+    ///     {"name": "code", "data_type": "char(3)", "generator": "string", "length": 3}
+    /// ]}"#).unwrap();
+    /// assert_eq!(fixed.generate_ddl(),
+    ///            "CREATE TABLE codes (\n    id integer,\n    code char(3)\n);\n");
+    /// # }
+    /// ```
+    pub fn generate_ddl(&self) -> String {
+        let mut columns: Vec<String> = self.fields.iter().map(|field| {
+            if field.nullable && !field.primary_key {
+                format!("    {} {}", field.name, field.data_type)
+            } else {
+                format!("    {} {} NOT NULL", field.name, field.data_type)
+            }
+        }).collect();
 
-        for field in self.fields.iter() {
-            let mut field_data = field.generate(rng);
-
-            if self.delimiter == "fixed" {
-                let field_length = field.length.ok_or(
-                    format!("'length' is required for a fixed file
-                             format, but is missing for field {}", field.name))?;
-
-                match field.padding {
-                    Some(p) => {
-                        field_data = field_data
-                            .as_str()
-                            .pad(field_length, p, Alignment::Right, false);
-                    },
-                    None => {
-                        let length_diff = field_length - field_data.len();
-                        if !length_diff == 0 {
-                            return Err(format!(
-                                "'padding' is undefined for field {} but
-                                field_data is less than 'length'.", field.name))
-                        }
-                    }
+        let primary_keys: Vec<&str> = self.fields.iter()
+            .filter(|f| f.primary_key)
+            .map(|f| f.name.as_str())
+            .collect();
+        if !primary_keys.is_empty() {
+            columns.push(format!("    PRIMARY KEY ({})", primary_keys.join(", ")));
+        }
+
+        format!("CREATE TABLE {} (\n{}\n);\n", self.table_name, columns.join(",\n"))
+    }
+
+    /// Generates a single row.  Fields that declare a length and padding are padded to
+    /// their length whatever the delimiter, not only in fixed-width mode.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// # extern crate rand;
+    /// use fourree::json::parse_json;
+    ///
+    /// # fn main() {
+    /// let schema = parse_json(r#"{"table_name": "t", "fields": [
+    ///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 7, "max": 7,
+    ///      "length": 5, "padding": "0"},
+    ///     {"name": "code", "data_type": "char(2)", "generator": "choice", "choices": ["AB"]}
+    /// ]}"#).unwrap();
+    /// let mut rng = rand::thread_rng();
+    ///
+    /// assert_eq!(schema.generate_row(&mut rng).unwrap(), "00007\tAB");
+    ///
+    /// let fixed = parse_json(r#"{"table_name": "t", "delimiter": "fixed", "fields": [
+    ///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 7, "max": 7,
+    ///      "length": 5, "padding": "0"},
+    ///     {"name": "code", "data_type": "char(2)", "generator": "choice", "choices": ["AB"],
+    ///      "length": 2}
+    /// ]}"#).unwrap();
+    /// assert_eq!(fixed.generate_row(&mut rng).unwrap(), "00007AB");
+    ///
+    /// let too_long = parse_json(r#"{"table_name": "t", "delimiter": "fixed", "fields": [
+    ///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 123456,
+    ///      "max": 123456, "length": 5, "padding": "0"}
+    /// ]}"#).unwrap();
+    /// assert!(too_long.generate_row(&mut rng).unwrap_err().contains("longer"));
+    ///
+    /// let unpadded = parse_json(r#"{"table_name": "t", "delimiter": "fixed", "fields": [
+    ///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 7, "max": 7,
+    ///      "length": 5}
+    /// ]}"#).unwrap();
+    /// assert!(unpadded.generate_row(&mut rng).unwrap_err().contains("shorter"));
+    /// # }
+    /// ```
+    pub fn generate_row<R: rand::Rng>(&self, rng: &mut R) -> Result<String, String> {
+        // Decide on the row layout once, rather than for every field
+        if self.delimiter == "fixed" {
+            return self.generate_fixed_row(rng);
+        }
+
+        let result: Vec<String> = self.fields.iter()
+            .zip(self.generate_values(rng)?)
+            .map(|(field, value)| {
+                self.quoting.apply(&field.render(&value), &self.delimiter, &self.record_separator)
+            })
+            .collect();
+
+        Ok(result.join(&self.delimiter))
+    }
+
+    /// Generates a row as a JSON object keyed by field name, in field order.  Fields with a
+    /// numeric data_type or an integer or gauss generator are written as numbers, boolean
+    /// fields as booleans, nulls as null, and everything else as strings; formats apply,
+    /// but padding does not.  Rows are compact unless `spaces` puts a space after each ':'
+    /// and ','.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// # extern crate rand;
+    /// use std::fs::File;
+    /// use std::io::Read;
+    ///
+    /// use fourree::config::{Config, OutputFormat, OutputMode};
+    /// use fourree::json::parse_json;
+    /// use fourree::util::generate_data;
+    ///
+    /// # fn main() {
+    /// let schema = parse_json(r#"{"table_name": "t", "fields": [
+    ///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 7, "max": 7,
+    ///      "length": 5, "padding": "0"},
+    ///     {"name": "code", "data_type": "char(2)", "generator": "choice", "choices": ["A\"B"]},
+    ///     {"name": "price", "data_type": "numeric(4,2)", "generator": "choice",
+    ///      "choices": ["1.50"]},
+    ///     {"name": "note", "data_type": "text", "generator": "choice", "choices": ["x"],
+    ///      "null_probability": 1},
+    ///     {"name": "score", "data_type": "varchar(8)", "generator": "gauss", "mean": 3,
+    ///      "std_dev": 0},
+    ///     {"name": "active", "data_type": "boolean", "generator": "choice", "choices": ["t"]},
+    ///     {"name": "zip", "data_type": "char(5)", "generator": "choice", "choices": ["02139"]}
+    /// ]}"#).unwrap();
+    /// let mut rng = rand::thread_rng();
+    ///
+    /// assert_eq!(schema.generate_row_json(&mut rng, false).unwrap(),
+    ///            concat!(r#"{"id":7,"code":"A\"B","price":1.5,"note":null,"#,
+    ///                    r#""score":3,"active":true,"zip":"02139"}"#));
+    ///
+    /// // --json_spaces puts a space after each ':' and ','
+    /// let jsonl = |spaces| {
+    ///     let schema = parse_json(r#"{"table_name": "t", "fields": [
+    ///         {"name": "id", "data_type": "integer", "generator": "integer", "min": 7, "max": 7},
+    ///         {"name": "code", "data_type": "char(1)", "generator": "choice", "choices": ["A"]}
+    ///     ]}"#).unwrap();
+    ///     let path = std::env::temp_dir().join("fourree-json-spaces.jsonl");
+    ///     let config = Config {
+    ///         num_rows: 1,
+    ///         output_format: OutputFormat::JsonLines { spaces: spaces },
+    ///         output_mode: OutputMode::File,
+    ///         output_file: Some(path.to_str().unwrap().to_string()),
+    ///         ..Default::default()
+    ///     };
+    ///     generate_data(&config, schema).unwrap();
+    ///
+    ///     let mut contents = String::new();
+    ///     File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+    ///     contents
+    /// };
+    /// assert_eq!(jsonl(false), "{\"id\":7,\"code\":\"A\"}\n");
+    /// assert_eq!(jsonl(true), "{\"id\": 7, \"code\": \"A\"}\n");
+    /// # }
+    /// ```
+    pub fn generate_row_json<R: rand::Rng>(&self, rng: &mut R, spaces: bool)
+            -> Result<String, String> {
+        let (colon, comma) = if spaces { (": ", ", ") } else { (":", ",") };
+        let members: Vec<String> = self.fields.iter()
+            .zip(self.generate_values(rng)?)
+            .map(|(field, value)| {
+                format!("{}{}{}", Value::String(field.name.clone()), colon,
+                        field.render_json(&value))
+            })
+            .collect();
+
+        Ok(format!("{{{}}}", members.join(comma)))
+    }
+
+    /// Generates one value per field, sampling each correlated pair once for the row, then
+    /// evaluating derived fields from the rest.  Fails when a unique field cannot find a new
+    /// value within `unique_max_retries` attempts, or when a derived field's expression
+    /// cannot be evaluated.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// # extern crate rand;
+    /// use fourree::json::parse_json;
+    ///
+    /// # fn main() {
+    /// let schema = parse_json(r#"{"table_name": "t", "fields": [
+    ///     {"name": "digit", "data_type": "char(1)", "generator": "choice", "unique": true,
+    ///      "choices": ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"]}
+    /// ]}"#).unwrap();
+    /// let mut rng = rand::thread_rng();
+    ///
+    /// let rows: Result<Vec<String>, String> = schema.rows(&mut rng).take(100).collect();
+    /// let error = rows.err().unwrap();
+    /// assert!(error.contains("exhausted for field digit"));
+    /// assert!(error.contains("only 10 distinct values"));
+    ///
+    /// let orders = parse_json(r#"{"table_name": "orders", "fields": [
+    ///     {"name": "total", "data_type": "numeric(8,2)", "generator": "derived",
+    ///      "expression": "round(price * quantity, 2)"},
+    ///     {"name": "price", "data_type": "numeric(6,2)", "generator": "decimal",
+    ///      "min": 0.5, "max": 99.99, "precision": 2},
+    ///     {"name": "quantity", "data_type": "integer", "generator": "integer", "min": 1,
+    ///      "max": 10}
+    /// ]}"#).unwrap();
+    /// for row in orders.rows(&mut rng).take(100) {
+    ///     let values: Vec<f64> = row.unwrap().split('\t').map(|v| v.parse().unwrap()).collect();
+    ///     assert!((values[0] - values[1] * values[2]).abs() < 0.005);
+    /// }
+    /// # }
+    /// ```
+    pub fn generate_values<R: rand::Rng>(&self, rng: &mut R) -> Result<Vec<TypedValue>, String> {
+        let mut values: Vec<TypedValue> = Vec::with_capacity(self.fields.len());
+        for (i, field) in self.fields.iter().enumerate() {
+            let value = match field.generator {
+                FieldGenerator::PerKeyEventTime{ ref key_field, .. } => {
+                    let key = self.fields[..i].iter().position(|f| &f.name == key_field)
+                        .ok_or(format!("Key field {} must come before field {}.",
+                                       key_field, field.name))?;
+                    field.event_time(rng, &values[key])?
                 }
+                // Evaluated once the rest of the row is generated
+                FieldGenerator::Derived{ .. } => TypedValue::Null,
+                // Filled in below from the field's correlated pair
+                FieldGenerator::Correlated => TypedValue::Null,
+                _ => field.generate_unique(rng, self.unique_max_retries)?
+            };
+            values.push(value);
+        }
+
+        for pair in self.correlated.iter() {
+            let (x, y) = generate_bivariate_normal(rng, pair.means, pair.std_devs,
+                                                   pair.coefficient);
+            values[pair.fields.0] = self.fields[pair.fields.0].correlated_value(x);
+            values[pair.fields.1] = self.fields[pair.fields.1].correlated_value(y);
+        }
+
+        self.evaluate_derived(&mut values)?;
+        Ok(values)
+    }
+
+    /// Fills in the values of derived fields, evaluating each after any derived fields it
+    /// refers to.  The parser rejects cycles, but a schema built in code may still have one.
+    fn evaluate_derived(&self, values: &mut [TypedValue]) -> Result<(), String> {
+        let mut pending: Vec<usize> = self.fields.iter().enumerate()
+            .filter_map(|(i, f)| match f.generator {
+                FieldGenerator::Derived{ .. } => Some(i),
+                _ => None
+            })
+            .collect();
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let index: HashMap<&str, usize> = self.fields.iter().enumerate()
+            .map(|(i, f)| (f.name.as_str(), i))
+            .collect();
+
+        while !pending.is_empty() {
+            let ready = pending.iter().position(|&i| match self.fields[i].generator {
+                FieldGenerator::Derived{ ref inputs, .. } => inputs.iter().all(|name| {
+                    index.get(name.as_str()).map_or(true, |j| !pending.contains(j))
+                }),
+                _ => true
+            }).ok_or(format!("Derived field {} depends on itself.",
+                             self.fields[pending[0]].name))?;
+            let i = pending.remove(ready);
+            let field = &self.fields[i];
+
+            let value = match field.generator {
+                FieldGenerator::Derived{ ref expression, .. } => {
+                    let row: HashMap<&str, &TypedValue> = index.iter()
+                        .map(|(&name, &j)| (name, &values[j]))
+                        .collect();
+                    expression.evaluate(&row)
+                        .map_err(|e| format!("Failed to derive field {}: {}", field.name, e))?
+                }
+                _ => unreachable!()
+            };
+            values[i] = match field.clamp {
+                Some(ref clamp) => clamp.apply(value),
+                None => value
+            };
+        }
+        Ok(())
+    }
+
+    /// Generates a row where every field is padded to its length and nothing separates them
+    fn generate_fixed_row<R: rand::Rng>(&self, rng: &mut R) -> Result<String, String> {
+        let mut result = Vec::with_capacity(self.fields.len());
+
+        for (field, value) in self.fields.iter().zip(self.generate_values(rng)?) {
+            // render() pads every field that declares padding
+            let field_data = field.render(&value);
+
+            let field_length = field.length.ok_or(
+                format!("'length' is required for a fixed file format, but is missing for \
+                         field {}", field.name))?;
+
+            let data_length = field_data.chars().count();
+            if data_length > field_length {
+                return Err(format!("Generated value '{}' for field {} is longer than its \
+                                    'length' of {}.", field_data, field.name, field_length))
+            }
+            if data_length < field_length && field.padding.is_none() {
+                return Err(format!("'padding' is undefined for field {} but the generated \
+                                    value '{}' is shorter than its 'length' of {}.",
+                                   field.name, field_data, field_length))
             }
             result.push(field_data);
         }
 
-        let delim = match self.delimiter.as_str() {
-            "fixed" => "",
-            d => d
-        };
+        Ok(result.concat())
+    }
 
-        Ok(result.join(delim))
+    /// Returns an endless iterator that generates one row per call to `next()`, so rows
+    /// can be pulled lazily and combined with `take`, `filter`, etc.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// # extern crate rand;
+    /// use fourree::json::parse_json;
+    ///
+    /// # fn main() {
+    /// let schema = parse_json(r#"{"table_name": "t", "fields": [
+    ///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+    /// ]}"#).unwrap();
+    /// let mut rng = rand::thread_rng();
+    ///
+    /// let rows: Result<Vec<String>, String> = schema.rows(&mut rng).take(5).collect();
+    /// assert_eq!(rows.unwrap().len(), 5);
+    /// # }
+    /// ```
+    pub fn rows<'a, R: rand::Rng>(&'a self, rng: &'a mut R)
+            -> impl Iterator<Item = Result<String, String>> + 'a {
+        iter::repeat(()).map(move |_| self.generate_row(rng))
     }
 
-    pub fn generate_rows(&self, rng: &mut rand::ThreadRng, size: u64) -> Result<String, String> {
+    /// Generates `size` rows, each terminated by the schema's record separator
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// # extern crate rand;
+    /// use fourree::json::parse_json;
+    ///
+    /// # fn main() {
+    /// let schema = parse_json(r#"{"table_name": "t", "delimiter": "|", "record_separator": "~",
+    ///   "fields": [
+    ///     {"name": "a", "data_type": "integer", "generator": "integer", "min": 0, "max": 10},
+    ///     {"name": "b", "data_type": "integer", "generator": "integer", "min": 0, "max": 10}
+    /// ]}"#).unwrap();
+    /// let mut rng = rand::thread_rng();
+    ///
+    /// assert_eq!(schema.generate_header(), "a|b~");
+    ///
+    /// let rows = schema.generate_rows(&mut rng, 3).unwrap();
+    /// assert!(!rows.contains('\n'));
+    /// assert!(rows.ends_with('~'));
+    /// let records: Vec<&str> = rows.trim_end_matches('~').split('~').collect();
+    /// assert_eq!(records.len(), 3);
+    /// assert!(records.iter().all(|r| r.split('|').count() == 2));
+    /// # }
+    /// ```
+    pub fn generate_rows<R: rand::Rng>(&self, rng: &mut R, size: u64) -> Result<String, String> {
         let mut output = String::new();
 
-        for _ in 0..size {
-            let row = self.generate_row(rng)?;
+        for row in self.rows(rng).take(size as usize) {
+            let row = row?;
             debug!("{}", row);
             output.push_str(&row);
-            output.push('\n');
+            output.push_str(&self.record_separator);
         }
         Ok(output)
     }
@@ -0,0 +1,163 @@
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use json::parse_json;
+
+/// Largest request body accepted by the validation server
+pub const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Validates a schema, returning the HTTP status and body to respond with: 200 and a
+/// summary of the table's fields, or 400 and the reason the schema is invalid.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::server::validate_schema;
+///
+/// # fn main() {
+/// let (status, body) = validate_schema(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10}
+/// ]}"#);
+/// assert_eq!(status, 200);
+/// assert_eq!(body, "Table: t\nFields: 1\nid\tinteger\t11\n");
+///
+/// assert_eq!(validate_schema(r#"{"fields": []}"#).0, 400);
+/// assert_eq!(validate_schema("not json").0, 400);
+/// # }
+/// ```
+pub fn validate_schema(raw_json: &str) -> (u16, String) {
+    match parse_json(raw_json).and_then(|schema| schema.prepare().map(|_| schema)) {
+        Ok(schema) => {
+            let mut summary = format!("Table: {}\nFields: {}\n",
+                                      schema.table_name, schema.fields.len());
+            for field in schema.fields.iter() {
+                summary.push_str(&format!("{}\t{}\t{}\n", field.name, field.data_type,
+                                          field.cardinality()));
+            }
+            (200, summary)
+        },
+        Err(e) => (400, format!("{}\n", e))
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error"
+    }
+}
+
+/// Reads a single request from the stream, returning its method, path and body
+fn read_request(stream: &TcpStream) -> Result<(String, String, String), (u16, String)> {
+    let bad_request = |e: String| (400, format!("{}\n", e));
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).map_err(|e| bad_request(e.to_string()))?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().ok_or(bad_request("Empty request.".to_string()))?.to_string();
+    let path = parts.next().ok_or(bad_request("Missing request path.".to_string()))?.to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).map_err(|e| bad_request(e.to_string()))? == 0 {
+            break;
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        let mut pair = header.splitn(2, ':');
+        let name = pair.next().unwrap_or("").trim().to_lowercase();
+        if name == "content-length" {
+            content_length = pair.next().unwrap_or("").trim().parse::<usize>()
+                .map_err(|_| bad_request("Invalid Content-Length.".to_string()))?;
+        }
+    }
+
+    if content_length > MAX_BODY_BYTES {
+        return Err((413, format!("Schemas may be at most {} bytes.\n", MAX_BODY_BYTES)));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|e| bad_request(e.to_string()))?;
+    let body = String::from_utf8(body)
+        .map_err(|_| bad_request("The schema must be UTF-8.".to_string()))?;
+
+    Ok((method, path, body))
+}
+
+/// Answers one HTTP request on the stream
+fn handle_connection(mut stream: TcpStream) -> Result<(), String> {
+    let (status, body) = match read_request(&stream) {
+        Ok((ref method, ref path, ref body)) if path == "/validate" => {
+            if method == "POST" {
+                validate_schema(body)
+            } else {
+                (405, "Only POST is supported.\n".to_string())
+            }
+        },
+        Ok((_, path, _)) => (404, format!("No such endpoint: {}\n", path)),
+        Err(error) => error
+    };
+
+    info!("Validation server responding with {}", status);
+    let response = format!("HTTP/1.1 {} {}\r\nContent-Type: text/plain; charset=utf-8\r\n\
+                            Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                           status, reason_phrase(status), body.len(), body);
+    stream.write_all(response.as_bytes()).map_err(|e| e.to_string())
+}
+
+/// Serves `POST /validate` on the listener, answering each connection on its own thread.
+/// Only returns if the listener fails.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use std::io::prelude::*;
+/// use std::net::{TcpListener, TcpStream};
+/// use std::thread;
+/// use fourree::server::serve;
+///
+/// fn post(address: &str, body: &str) -> String {
+///     let mut stream = TcpStream::connect(address).unwrap();
+///     write!(stream, "POST /validate HTTP/1.1\r\nHost: localhost\r\n\
+///                     Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+///     let mut response = String::new();
+///     stream.read_to_string(&mut response).unwrap();
+///     response
+/// }
+///
+/// # fn main() {
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let address = listener.local_addr().unwrap().to_string();
+/// thread::spawn(move || serve(listener));
+///
+/// let valid = post(&address, r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10}
+/// ]}"#);
+/// assert!(valid.starts_with("HTTP/1.1 200 OK\r\n"));
+/// assert!(valid.ends_with("id\tinteger\t11\n"));
+///
+/// let invalid = post(&address, r#"{"fields": []}"#);
+/// assert!(invalid.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+/// # }
+/// ```
+pub fn serve(listener: TcpListener) -> Result<(), String> {
+    for stream in listener.incoming() {
+        let stream = stream.map_err(|e| e.to_string())?;
+        thread::spawn(move || {
+            if let Err(e) = handle_connection(stream) {
+                warn!("Validation request failed: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use schema::{Schema, TypedValue, Quoting};
+
+/// The SQL flavour used when quoting identifiers and escaping string values
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SqlDialect {
+    Postgres,
+    MySql,
+    Sqlite
+}
+
+/// Parses the name of a SQL dialect, as given to `--dialect`
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::sql::{parse_dialect, SqlDialect};
+///
+/// # fn main() {
+/// assert_eq!(parse_dialect("mysql"), Ok(SqlDialect::MySql));
+/// assert!(parse_dialect("oracle").is_err());
+/// # }
+/// ```
+pub fn parse_dialect(name: &str) -> Result<SqlDialect, String> {
+    match name {
+        "postgres" | "postgresql" => Ok(SqlDialect::Postgres),
+        "mysql" => Ok(SqlDialect::MySql),
+        "sqlite" => Ok(SqlDialect::Sqlite),
+        other => Err(format!(
+            "Unsupported SQL dialect: {}, expected 'postgres', 'mysql' or 'sqlite'.", other))
+    }
+}
+
+/// Quotes a table or column name
+pub fn quote_identifier(name: &str, dialect: SqlDialect) -> String {
+    match dialect {
+        SqlDialect::MySql => format!("`{}`", name.replace("`", "``")),
+        _ => format!("\"{}\"", name.replace("\"", "\"\""))
+    }
+}
+
+/// Renders a value as a SQL literal.  Numbers are left bare, nulls become NULL, and text
+/// is quoted and escaped for the dialect.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::schema::TypedValue;
+/// use fourree::sql::{quote_value, SqlDialect};
+///
+/// # fn main() {
+/// let name = TypedValue::Text("O'Brien".to_string());
+/// assert_eq!(quote_value(&name, SqlDialect::Postgres), "'O''Brien'");
+/// assert_eq!(quote_value(&name, SqlDialect::MySql), "'O\\'Brien'");
+/// assert_eq!(quote_value(&TypedValue::Integer(42), SqlDialect::Sqlite), "42");
+/// assert_eq!(quote_value(&TypedValue::Null, SqlDialect::MySql), "NULL");
+/// # }
+/// ```
+pub fn quote_value(value: &TypedValue, dialect: SqlDialect) -> String {
+    match *value {
+        TypedValue::Text(ref s) => match dialect {
+            SqlDialect::MySql => format!("'{}'", s.replace("\\", "\\\\").replace("'", "\\'")),
+            _ => format!("'{}'", s.replace("'", "''"))
+        },
+        TypedValue::Null => "NULL".to_string(),
+        ref number => number.to_string()
+    }
+}
+
+/// Builds a single multi-row INSERT statement for the given rows of values, which must be
+/// in the same order as the schema's fields.  Returns an empty string when there are no rows.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+/// use fourree::sql::{insert_statement, SqlDialect};
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "people", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10},
+///     {"name": "surname", "data_type": "varchar(8)", "generator": "choice",
+///      "choices": ["O'Brien"]}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+/// let rows: Vec<_> = (0..3).map(|_| schema.generate_values(&mut rng).unwrap()).collect();
+///
+/// let sql = insert_statement(&schema, &rows, SqlDialect::Postgres);
+/// assert!(sql.starts_with("INSERT INTO \"people\" (\"id\", \"surname\") VALUES\n"));
+/// assert!(sql.ends_with(");\n"));
+///
+/// let tuples: Vec<&str> = sql.lines().skip(1).collect();
+/// assert_eq!(tuples.len(), 3);
+/// for tuple in tuples {
+///     let values: Vec<&str> = tuple.trim_matches(|c| c == '(' || c == ')' || c == ',' || c == ';')
+///                                   .split(", ")
+///                                   .collect();
+///     assert_eq!(values.len(), 2);
+///     assert_eq!(values[1], "'O''Brien'");
+/// }
+/// # }
+/// ```
+pub fn insert_statement(schema: &Schema, rows: &[Vec<TypedValue>], dialect: SqlDialect) -> String {
+    if rows.is_empty() {
+        return String::new();
+    }
+
+    let columns: Vec<String> = schema.fields.iter()
+        .map(|f| quote_identifier(&f.name, dialect))
+        .collect();
+
+    let tuples: Vec<String> = rows.iter().map(|row| {
+        let values: Vec<String> = row.iter().map(|v| quote_value(v, dialect)).collect();
+        format!("({})", values.join(", "))
+    }).collect();
+
+    format!("INSERT INTO {} ({}) VALUES\n{};\n",
+            quote_identifier(&schema.table_name, dialect), columns.join(", "), tuples.join(",\n"))
+}
+
+/// Builds the PostgreSQL `COPY ... FROM STDIN` statement that loads rows in the schema's
+/// own delimited format, as COPY's csv format when the schema quotes its fields and text
+/// format otherwise.  Either takes one row per line and a single byte delimiter, and at most
+/// one string that stands for NULL.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::json::parse_json;
+/// use fourree::sql::copy_statement;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "users", "delimiter": ",", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10},
+///     {"name": "nickname", "data_type": "varchar(8)", "generator": "string", "length": 8,
+///      "null_probability": 0.5, "null_token": "\\N"}
+/// ]}"#).unwrap();
+/// assert_eq!(copy_statement(&schema).unwrap(),
+///            "COPY \"users\" (\"id\", \"nickname\") FROM STDIN \
+///             WITH (FORMAT text, DELIMITER ',', NULL '\\N')");
+///
+/// let piped = parse_json(r#"{"table_name": "t", "delimiter": "||", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10}
+/// ]}"#).unwrap();
+/// assert!(copy_statement(&piped).is_err());
+///
+/// let quoted = parse_json(r#"{"table_name": "t", "delimiter": ",", "quoting": "minimal",
+///   "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10}
+/// ]}"#).unwrap();
+/// assert!(copy_statement(&quoted).unwrap().contains("FORMAT csv, DELIMITER ','"));
+/// # }
+/// ```
+pub fn copy_statement(schema: &Schema) -> Result<String, String> {
+    if schema.delimiter.len() != 1 {
+        return Err(format!("PostgreSQL output needs a single character delimiter, not '{}'.",
+                           schema.delimiter));
+    }
+    if schema.record_separator != "\n" {
+        return Err("PostgreSQL output needs rows separated by newlines.".to_string());
+    }
+
+    let mut null_tokens: Vec<&str> = schema.fields.iter()
+        .filter(|f| f.null_probability > 0.0)
+        .map(|f| f.null_token.as_str())
+        .collect();
+    null_tokens.sort();
+    null_tokens.dedup();
+    let null_option = match null_tokens.len() {
+        0 => String::new(),
+        1 => format!(", NULL '{}'", null_tokens[0].replace("'", "''")),
+        _ => return Err("PostgreSQL output needs every field to use the same null_token."
+                        .to_string())
+    };
+
+    let columns: Vec<String> = schema.fields.iter()
+        .map(|f| quote_identifier(&f.name, SqlDialect::Postgres))
+        .collect();
+
+    let format = if schema.quoting == Quoting::None { "text" } else { "csv" };
+
+    Ok(format!("COPY {} ({}) FROM STDIN WITH (FORMAT {}, DELIMITER '{}'{})",
+               quote_identifier(&schema.table_name, SqlDialect::Postgres), columns.join(", "),
+               format, schema.delimiter.replace("'", "''"), null_option))
+}
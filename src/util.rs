@@ -3,9 +3,18 @@ use rand;
 use std::io;
 use std::io::Write;
 use std::io::BufWriter;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
 use std::default::Default;
+use std::mem;
+use std::any::Any;
+use std::panic;
+use std::process::Command;
+use std::str::FromStr;
 
+use reqwest;
+use reqwest::header::ContentType;
+use reqwest::mime::Mime;
 use rusoto_core::Region;
 use rusoto_s3::{S3, S3Client, CreateMultipartUploadRequest, UploadPartRequest,
                 CompletedPart, StreamingBody, CompleteMultipartUploadRequest,
@@ -13,40 +22,754 @@ use rusoto_s3::{S3, S3Client, CreateMultipartUploadRequest, UploadPartRequest,
 
 use std::thread;
 use std::thread::JoinHandle;
-use std::sync::Arc;
-use std::sync::mpsc::{channel, Sender, Receiver};
+use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use rand::{Rng, StdRng, SeedableRng};
+use rand::distributions::{IndependentSample, Range};
+use rayon::ThreadPoolBuilder;
+use rayon::prelude::*;
+use std::sync::mpsc::{sync_channel, SyncSender, Receiver};
 
-use config::{Config, OutputMode};
+#[cfg(feature = "kafka")]
+use std::sync::atomic::AtomicUsize;
+use std::time::Duration;
+#[cfg(feature = "kafka")]
+use rdkafka::ClientContext;
+#[cfg(feature = "kafka")]
+use rdkafka::config::ClientConfig;
+#[cfg(feature = "kafka")]
+use rdkafka::error::KafkaError;
+#[cfg(feature = "kafka")]
+use rdkafka::producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext};
+#[cfg(feature = "kafka")]
+use rdkafka::types::RDKafkaErrorCode;
+#[cfg(feature = "postgres")]
+use postgres::{Client, NoTls};
+
+use config::{Config, OutputMode, OutputFormat, batch_count, effective_thread_count};
+use crypto::{ChunkEncryptor, HEADER};
 use schema::Schema;
+use generators::{DurationFormat, format_duration};
+use sql::{SqlDialect, insert_statement};
+#[cfg(feature = "postgres")]
+use sql::copy_statement;
+
+/// Builds the text written ahead of the data rows: the DDL and/or header, when requested.
+/// The preamble is never counted towards `num_rows`.  A SQL file never gets a header, since
+/// it would not be valid SQL.
+pub fn output_preamble(config: &Config, schema: &Schema) -> String {
+    let mut preamble = String::new();
+
+    if config.display_ddl {
+        preamble.push_str(&schema.generate_ddl());
+    }
+    if config.display_header && config.output_mode != OutputMode::SqlFile {
+        preamble.push_str(&schema.generate_header());
+    }
+    preamble
+}
+
+/// The thread writing to the output, which returns why it failed instead of panicking
+pub type OutputThread = JoinHandle<Result<(), String>>;
 
 /// Creates the thread used to write data to the output (file, database, stdout, etc.)
-pub fn initialize_output_thread(config: &Config) ->
-        Result<(Sender<String>, JoinHandle<()>), String> {
-    let (sender, receiver) = channel();
+///
+/// The channel only ever carries batches of data rows; any preamble is handed to the
+/// output thread directly so it can tell the two apart.  It holds at most
+/// `channel_capacity` batches, so generator threads block when the output falls behind
+/// rather than queueing without limit.
+///
+/// Output that can be discarded, such as an S3 multipart upload, is abandoned rather than
+/// completed when `generation_failed` is set by the time the channel closes.
+pub fn initialize_output_thread(config: &Config, schema: &Schema,
+                                generation_failed: Arc<AtomicBool>) ->
+        Result<(SyncSender<String>, OutputThread), String> {
+    let (sender, receiver) = sync_channel(config.channel_capacity);
+    let preamble = output_preamble(config, schema);
+
+    if config.encrypt_key.is_some() && config.output_mode != OutputMode::File &&
+       config.output_mode != OutputMode::S3 && config.output_mode != OutputMode::SqlFile {
+        return Err("Encryption is only supported for file, sql_file and s3 output.".to_string());
+    }
 
     let thread = match config.output_mode {
-        OutputMode::Stdout => stdout_thread(receiver)?,
-        OutputMode::File => file_thread(config, receiver)?,
-        OutputMode::PostgreSQL => {
-            return Err("PostgreSQL output not yet implemented!".to_string())
+        OutputMode::Stdout => stdout_thread(receiver, preamble)?,
+        OutputMode::File if config.partition_by.is_some() => {
+            partitioned_file_thread(config, schema, receiver, preamble)?
+        },
+        OutputMode::File if splits_files(config) => {
+            split_file_thread(config, schema, receiver, preamble)?
+        },
+        OutputMode::File | OutputMode::SqlFile => file_thread(config, schema, receiver, preamble)?,
+        OutputMode::PostgreSQL => postgres_output(config, schema, receiver)?,
+        OutputMode::S3 => s3_thread(config, schema, receiver, preamble, generation_failed)?,
+        OutputMode::Kafka => {
+            if !preamble.is_empty() {
+                warn!("The header and DDL are not published to Kafka, only data rows.");
+            }
+            kafka_output(config, schema, receiver)?
         },
-        OutputMode::S3 => s3_thread(config, receiver)?,
+        OutputMode::Http => http_thread(config, schema, receiver, preamble)?,
         OutputMode::None => {
             return Err("An invalid output mode was specified.".to_string())
         }
     };
 
-    Ok((sender, thread))
+    match config.sample {
+        Some(size) => {
+            let (relay_sender, relay_receiver) = sync_channel(config.channel_capacity);
+            let separator = schema.record_separator.clone();
+            let rng = stream_rng(config.seed, SAMPLE_STREAM);
+            Ok((relay_sender, sample_thread(relay_receiver, sender, thread, size, separator, rng)))
+        },
+        None => Ok((sender, thread))
+    }
+}
+
+/// A uniform random sample of a fixed number of rows from a stream of unknown length,
+/// kept using reservoir sampling.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+/// use fourree::util::Reservoir;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 1000000}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+/// let rows = schema.generate_rows(&mut rng, 1000).unwrap();
+///
+/// let mut reservoir = Reservoir::new(5);
+/// for row in rows.lines() {
+///     reservoir.offer(&mut rng, row);
+/// }
+///
+/// assert_eq!(reservoir.rows().len(), 5);
+/// assert!(reservoir.rows().iter().all(|sampled| rows.lines().any(|row| row == sampled)));
+/// # }
+/// ```
+pub struct Reservoir {
+    size: usize,
+    seen: u64,
+    rows: Vec<String>
+}
+
+impl Reservoir {
+    pub fn new(size: usize) -> Reservoir {
+        Reservoir { size: size, seen: 0, rows: Vec::with_capacity(size) }
+    }
+
+    /// Considers a row for the sample, keeping every row seen with equal probability
+    pub fn offer<R: rand::Rng>(&mut self, rng: &mut R, row: &str) {
+        self.seen += 1;
+        if self.rows.len() < self.size {
+            self.rows.push(row.to_string());
+        } else {
+            let slot = Range::new(0, self.seen).ind_sample(rng);
+            if slot < self.size as u64 {
+                self.rows[slot as usize] = row.to_string();
+            }
+        }
+    }
+
+    pub fn rows(&self) -> &[String] {
+        &self.rows
+    }
+}
+
+/// Returns a thread that samples the rows on their way to the output thread, then prints
+/// the sample to stderr once the output thread has finished.
+fn sample_thread(receiver: Receiver<String>, output: SyncSender<String>,
+                 output_thread: OutputThread, size: usize, separator: String,
+                 mut rng: StdRng) -> OutputThread {
+    thread::spawn(move || {
+        let mut reservoir = Reservoir::new(size);
+
+        for batch in receiver.iter() {
+            for row in batch.split(separator.as_str()).filter(|r| !r.is_empty()) {
+                reservoir.offer(&mut rng, row);
+            }
+            // The output thread has failed, and joining it below reports why
+            if output.send(batch).is_err() {
+                break;
+            }
+        }
+
+        drop(output);
+        output_thread.join().map_err(panic_message)??;
+
+        let stderr = io::stderr();
+        let mut stderr_lock = stderr.lock();
+        for row in reservoir.rows() {
+            write!(stderr_lock, "{}{}", row, separator)
+                .map_err(|e| format!("Failed to write the sample to stderr: {}", e))?;
+        }
+        Ok(())
+    })
+}
+
+/// Placeholders that may appear in an output template
+pub const TEMPLATE_PLACEHOLDERS: [&'static str; 4] = ["table", "date", "shard", "seed"];
+
+/// Checks that every `{placeholder}` in an output template is supported.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::validate_output_template;
+///
+/// # fn main() {
+/// assert!(validate_output_template("{table}-{date}.csv").is_ok());
+/// assert!(validate_output_template("{tabel}.csv").is_err());
+/// assert!(validate_output_template("{table.csv").is_err());
+/// # }
+/// ```
+pub fn validate_output_template(template: &str) -> Result<(), String> {
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..].find('}')
+            .ok_or(format!("Unclosed placeholder in output template {}", template))?;
+        let name = &rest[start + 1..start + end];
+        if !TEMPLATE_PLACEHOLDERS.contains(&name) {
+            return Err(format!("Unknown placeholder {{{}}} in output template, expected one of {}",
+                               name, TEMPLATE_PLACEHOLDERS.join(", ")));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
+}
+
+/// Expands an output template into a file name.  `{shard}` is zero padded to four digits,
+/// `{date}` is the current UTC date as YYYYMMDD and `{seed}` is "unseeded" when no seed
+/// is in use.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::expand_output_template;
+///
+/// # fn main() {
+/// let names: Vec<String> = (1..3)
+///     .map(|shard| expand_output_template("{table}-{shard}.csv", "users", shard, None).unwrap())
+///     .collect();
+/// assert_eq!(names, vec!["users-0001.csv", "users-0002.csv"]);
+/// # }
+/// ```
+pub fn expand_output_template(template: &str, table: &str, shard: u64, seed: Option<u64>)
+        -> Result<String, String> {
+    validate_output_template(template)?;
+
+    let date = time::strftime("%Y%m%d", &time::now_utc()).map_err(|e| e.to_string())?;
+    let seed = match seed {
+        Some(s) => s.to_string(),
+        None => "unseeded".to_string()
+    };
+
+    Ok(template.replace("{table}", table)
+               .replace("{date}", &date)
+               .replace("{shard}", &format!("{:04}", shard))
+               .replace("{seed}", &seed))
+}
+
+/// Number of rows generated to estimate the average row size in a dry run
+const DRY_RUN_SAMPLE_ROWS: u64 = 100;
+
+/// Describes what a run would produce without producing it: the estimated output size,
+/// and the cardinality of each field (or only of the fields named in `fields`).
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::config::Config;
+/// use fourree::json::parse_json;
+/// use fourree::util::dry_run_report;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10},
+///     {"name": "score", "data_type": "integer", "generator": "gauss", "mean": 0, "std_dev": 5}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+///
+/// let report = dry_run_report(&Config::default(), &schema, &mut rng, &[]).unwrap();
+/// assert!(report.contains("Estimated size:"));
+/// assert!(report.contains("id\tinteger\t11\n"));
+/// assert!(report.contains("score\tinteger\tunbounded\n"));
+///
+/// let report = dry_run_report(&Config::default(), &schema, &mut rng,
+///                             &["score".to_string()]).unwrap();
+/// assert!(!report.contains("id\t"));
+/// assert!(dry_run_report(&Config::default(), &schema, &mut rng, &["x".to_string()]).is_err());
+/// # }
+/// ```
+pub fn dry_run_report<R: rand::Rng>(config: &Config, schema: &Schema, rng: &mut R,
+                                    fields: &[String]) -> Result<String, String> {
+    for name in fields.iter() {
+        if !schema.fields.iter().any(|f| &f.name == name) {
+            return Err(format!("Unknown field for --count: {}", name));
+        }
+    }
+
+    let sample_rows = DRY_RUN_SAMPLE_ROWS.min(config.num_rows).max(1);
+    let sample = schema.generate_rows(rng, sample_rows)?;
+    let row_bytes = sample.len() as f64 / sample_rows as f64;
+    let estimate = output_preamble(config, schema).len() as f64 + row_bytes * config.num_rows as f64;
+
+    let mut report = format!("Table: {}\nEstimated size: {:.0} bytes for {} rows ({:.1} bytes per row)\n",
+                             schema.table_name, estimate, config.num_rows, row_bytes);
+    report.push_str("Field cardinality:\n");
+    for field in schema.fields.iter() {
+        if fields.is_empty() || fields.contains(&field.name) {
+            report.push_str(&format!("{}\t{}\t{}\n", field.name, field.data_type,
+                                     field.cardinality()));
+        }
+    }
+    Ok(report)
+}
+
+/// Checks that every field of the schema can generate values, and summarizes the table and
+/// the generator of each field, for `--validate`.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::json::parse_json;
+/// use fourree::util::validation_summary;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10},
+///     {"name": "color", "data_type": "varchar(5)", "generator": "choice",
+///      "choices": ["red", "green"], "weights": [3, 1]}
+/// ]}"#).unwrap();
+/// assert_eq!(validation_summary(&schema).unwrap(),
+///            "Table: t\nFields: 2\nid\tinteger\ncolor\tchoice\n");
+///
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 10, "max": 0}
+/// ]}"#).unwrap();
+/// assert!(validation_summary(&schema).is_err());
+/// # }
+/// ```
+pub fn validation_summary(schema: &Schema) -> Result<String, String> {
+    schema.prepare()?;
+
+    let mut summary = format!("Table: {}\nFields: {}\n", schema.table_name, schema.fields.len());
+    for field in schema.fields.iter() {
+        summary.push_str(&format!("{}\t{}\n", field.name, field.generator.name()));
+    }
+    Ok(summary)
+}
+
+/// Creates an encryptor when an encryption key was configured
+fn output_encryptor(config: &Config) -> Result<Option<ChunkEncryptor>, String> {
+    match config.encrypt_key {
+        Some(ref key) => Ok(Some(ChunkEncryptor::new(key)?)),
+        None => Ok(None)
+    }
+}
+
+/// Encrypts a chunk of output when encryption is enabled, otherwise passes it through
+fn encode_chunk(encryptor: &mut Option<ChunkEncryptor>, data: Vec<u8>) -> Result<Vec<u8>, String> {
+    match *encryptor {
+        Some(ref mut e) => e.encrypt(&data),
+        None => Ok(data)
+    }
 }
 
-/// Returns a thread that outputs to Stdout
-pub fn stdout_thread(receiver: Receiver<String>) -> Result<JoinHandle<()>, String> {
+/// The final chunk that ends encrypted output, or nothing when encryption is disabled
+fn encode_end(encryptor: &mut Option<ChunkEncryptor>) -> Result<Vec<u8>, String> {
+    match *encryptor {
+        Some(ref mut e) => e.finish(),
+        None => Ok(Vec::new())
+    }
+}
+
+/// Returns a thread that outputs to Stdout.  Should a write fail, e.g. on a closed pipe,
+/// the thread returns the reason, which `generate_data` reports when it joins the thread.
+pub fn stdout_thread(receiver: Receiver<String>, preamble: String)
+        -> Result<OutputThread, String> {
     let thread = thread::spawn(move || {
-        let stdout = io::stdout();
-        let mut stdout_lock = stdout.lock();
+        write_stdout(&receiver, preamble).map_err(|e| format!("Failed to write to stdout: {}", e))
+    });
+    Ok(thread)
+}
+
+/// Writes the preamble and then every batch to stdout, until the generators hang up
+fn write_stdout(receiver: &Receiver<String>, preamble: String) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout_lock = stdout.lock();
+    stdout_lock.write_all(preamble.as_bytes())?;
+
+    for output in receiver.iter() {
+        stdout_lock.write_all(output.as_bytes())?;
+    }
+    info!("Schema generation complete.");
+    stdout_lock.flush()
+}
+
+/// Returns the path of the output file, named by the output template when one is set
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::config::Config;
+/// use fourree::json::parse_json;
+/// use fourree::util::output_file_path;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "users", "fields": []}"#).unwrap();
+/// let config = Config {
+///     output_template: Some("{table}-{seed}.csv".to_string()),
+///     seed: Some(42),
+///     ..Default::default()
+/// };
+/// assert_eq!(output_file_path(&config, &schema).unwrap(), "users-42.csv");
+///
+/// let config = Config { seed: None, ..config };
+/// assert_eq!(output_file_path(&config, &schema).unwrap(), "users-unseeded.csv");
+/// # }
+/// ```
+pub fn output_file_path(config: &Config, schema: &Schema) -> Result<String, String> {
+    match (config.output_template.as_ref(), config.output_file.clone()) {
+        (Some(template), _) => {
+            expand_output_template(template, &schema.table_name, 1, config.seed)
+        }
+        (None, Some(f)) => Ok(f),
+        (None, None) => Err("output_file required when OutputMode == File!".to_string())
+    }
+}
+
+/// Runs the `--on_complete` shell command for a finished output file, with `{file}`
+/// replaced by the file's quoted path.  A non-zero exit status is an error.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use std::fs::File;
+/// use std::io::Read;
+/// use fourree::util::run_on_complete;
+///
+/// # fn main() {
+/// let record = std::env::temp_dir().join("fourree-on-complete.txt");
+/// let command = format!("echo {{file}} > {}", record.display());
+/// run_on_complete(&command, "/tmp/output file.csv").unwrap();
+///
+/// let mut contents = String::new();
+/// File::open(&record).unwrap().read_to_string(&mut contents).unwrap();
+/// assert_eq!(contents, "/tmp/output file.csv\n");
+///
+/// let error = run_on_complete("exit 3", "/tmp/output.csv").err().unwrap();
+/// assert!(error.contains("exit 3"));
+/// assert!(error.contains("/tmp/output.csv"));
+/// # }
+/// ```
+pub fn run_on_complete(command: &str, path: &str) -> Result<(), String> {
+    let quoted = format!("'{}'", path.replace("'", "'\\''"));
+    let command_line = command.replace("{file}", &quoted);
+
+    let status = Command::new("sh").arg("-c").arg(&command_line).status()
+        .map_err(|e| format!("Failed to run --on_complete command for {}: {}", path, e))?;
+    if !status.success() {
+        return Err(format!("--on_complete command `{}` failed for {} with {}",
+                           command_line, path, status));
+    }
+    Ok(())
+}
+
+/// Returns a thread that outputs to a file, named by the output template when one is set.
+/// Should a write fail, e.g. on a full disk, the thread returns the reason, which
+/// `generate_data` reports when it joins the thread.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::config::{Config, OutputMode};
+/// use fourree::json::parse_json;
+/// use fourree::util::generate_data;
+///
+/// # fn main() {
+/// let schema = || parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+/// ]}"#).unwrap();
+/// let config = Config {
+///     num_rows: 10,
+///     output_mode: OutputMode::File,
+///     output_file: Some("/nonexistent/fourree.csv".to_string()),
+///     ..Default::default()
+/// };
+/// let error = generate_data(&config, schema()).err().unwrap();
+/// assert!(error.starts_with("Failed to create /nonexistent/fourree.csv"));
+///
+/// if cfg!(target_os = "linux") {
+///     let config = Config { output_file: Some("/dev/full".to_string()), ..config };
+///     let error = generate_data(&config, schema()).err().unwrap();
+///     assert!(error.starts_with("Failed to write to /dev/full"));
+/// }
+/// # }
+/// ```
+pub fn file_thread(config: &Config, schema: &Schema, receiver: Receiver<String>, preamble: String)
+        -> Result<OutputThread, String> {
+    let output_file = output_file_path(config, schema)?;
+    let mut encryptor = output_encryptor(config)?;
+
+    Ok(thread::spawn(move || write_file(&output_file, &mut encryptor, &receiver, preamble)))
+}
+
+/// Writes the preamble and then every batch to the file, until the generators hang up,
+/// encrypting them when there is an encryptor
+fn write_file(path: &str, encryptor: &mut Option<ChunkEncryptor>, receiver: &Receiver<String>,
+              preamble: String) -> Result<(), String> {
+    let write_error = |e: io::Error| format!("Failed to write to {}: {}", path, e);
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut writer = BufWriter::new(file);
+
+    if encryptor.is_some() {
+        writer.write_all(HEADER).map_err(write_error)?;
+    }
+    if !preamble.is_empty() {
+        writer.write_all(&encode_chunk(encryptor, preamble.into_bytes())?).map_err(write_error)?;
+    }
+
+    for output in receiver.iter() {
+        let chunk = encode_chunk(encryptor, output.into_bytes())?;
+        writer.write_all(&chunk).map_err(write_error)?;
+    }
+    writer.write_all(&encode_end(encryptor)?).map_err(write_error)?;
+    info!("Schema generation complete.");
+    // Dropping the writer would flush it too, but ignore any error
+    writer.flush().map_err(write_error)
+}
+
+/// Most partition files kept open at once; beyond this the least recently written one is
+/// closed, and reopened for appending if its partition comes up again.
+pub const MAX_OPEN_PARTITIONS: usize = 64;
+
+/// Makes a partition key's value safe to use in a file name: anything other than ASCII
+/// letters, digits, '-' and '_' becomes '_'.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::sanitize_partition;
+///
+/// # fn main() {
+/// assert_eq!(sanitize_partition("us-east"), "us-east");
+/// assert_eq!(sanitize_partition("../etc/passwd"), "___etc_passwd");
+/// assert_eq!(sanitize_partition(""), "_");
+/// # }
+/// ```
+pub fn sanitize_partition(value: &str) -> String {
+    if value.is_empty() {
+        return "_".to_string();
+    }
+    value.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Returns the path of a partition's file, which is the output file with the sanitized
+/// partition value inserted before its extension.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::partition_file_path;
+///
+/// # fn main() {
+/// assert_eq!(partition_file_path("out/users.csv", "east"), "out/users.east.csv");
+/// assert_eq!(partition_file_path("users", "a b"), "users.a_b");
+/// # }
+/// ```
+pub fn partition_file_path(output_file: &str, partition: &str) -> String {
+    let path = Path::new(output_file);
+    let stem = path.file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned());
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, sanitize_partition(partition), ext.to_string_lossy()),
+        None => format!("{}.{}", stem, sanitize_partition(partition))
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// Returns an output template that writes each table to the output file with the table's
+/// name inserted before its extension, for when one run writes several tables.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::{table_output_template, expand_output_template};
+///
+/// # fn main() {
+/// assert_eq!(table_output_template("out/data.csv"), "out/data.{table}.csv");
+/// assert_eq!(table_output_template("data"), "data.{table}");
+///
+/// let template = table_output_template("out/data.csv");
+/// assert_eq!(expand_output_template(&template, "users", 1, None).unwrap(), "out/data.users.csv");
+/// # }
+/// ```
+pub fn table_output_template(output_file: &str) -> String {
+    let path = Path::new(output_file);
+    let stem = path.file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned());
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{{table}}.{}", stem, ext.to_string_lossy()),
+        None => format!("{}.{{table}}", stem)
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// The open files of a partitioned output, keeping at most `max_open` of them open
+struct PartitionWriters {
+    output_file: String,
+    preamble: String,
+    max_open: usize,
+    /// Open writers, least recently written first
+    open: Vec<(String, BufWriter<File>)>,
+    /// Every file created so far, in order of creation
+    created: Vec<String>,
+    /// The partition value written to each file
+    values: HashMap<String, String>
+}
+
+impl PartitionWriters {
+    fn write(&mut self, partition: &str, data: &str) -> Result<(), String> {
+        let path = partition_file_path(&self.output_file, partition);
+        // Values that only differ in the characters sanitizing replaces share a path
+        match self.values.get(&path) {
+            Some(value) if value != partition => {
+                return Err(format!("Partition values '{}' and '{}' would both be written to {}.",
+                                   value, partition, path))
+            },
+            Some(_) => {},
+            None => {
+                self.values.insert(path.clone(), partition.to_string());
+            }
+        }
+
+        let writer = match self.open.iter().position(|&(ref p, _)| *p == path) {
+            Some(index) => self.open.remove(index).1,
+            None => {
+                if self.open.len() >= self.max_open {
+                    let (_, mut evicted) = self.open.remove(0);
+                    evicted.flush().map_err(|e| e.to_string())?;
+                }
+                self.open_writer(&path)?
+            }
+        };
+
+        self.open.push((path, writer));
+        let &mut (_, ref mut writer) = self.open.last_mut().unwrap();
+        writer.write_all(data.as_bytes()).map_err(|e| e.to_string())
+    }
 
+    /// Creates the partition's file with the preamble, or reopens an evicted one to append
+    fn open_writer(&mut self, path: &str) -> Result<BufWriter<File>, String> {
+        if self.created.iter().any(|p| p == path) {
+            let file = OpenOptions::new().append(true).open(path).map_err(|e| e.to_string())?;
+            return Ok(BufWriter::new(file));
+        }
+
+        let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+        writer.write_all(self.preamble.as_bytes()).map_err(|e| e.to_string())?;
+        self.created.push(path.to_string());
+        Ok(writer)
+    }
+
+    /// Flushes every open file, returning the paths of all the files written
+    fn finish(mut self) -> Result<Vec<String>, String> {
+        for &mut (_, ref mut writer) in self.open.iter_mut() {
+            writer.flush().map_err(|e| e.to_string())?;
+        }
+        Ok(self.created)
+    }
+}
+
+/// Returns a thread that writes each row to a file named by the value of the
+/// `--partition_by` field, creating files as new values appear.  Each file gets its own
+/// copy of the preamble, and `--on_complete` runs for each file once all are written.
+/// Two values that sanitize to the same file name, such as "a/b" and "a_b", fail the run
+/// rather than share a file.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use std::fs::File;
+/// use std::io::Read;
+///
+/// use fourree::config::{Config, OutputMode};
+/// use fourree::json::parse_json;
+/// use fourree::util::{generate_data, partition_file_path};
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "delimiter": ",", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100},
+///     {"name": "region", "data_type": "varchar(4)", "generator": "choice",
+///      "choices": ["east", "west", "north"]}
+/// ]}"#).unwrap();
+///
+/// let path = std::env::temp_dir().join("fourree-partitioned.csv");
+/// let path = path.to_str().unwrap();
+/// let config = Config {
+///     num_rows: 300,
+///     batch_size: 10,
+///     output_mode: OutputMode::File,
+///     output_file: Some(path.to_string()),
+///     partition_by: Some("region".to_string()),
+///     display_header: true,
+///     ..Default::default()
+/// };
+/// generate_data(&config, schema).unwrap();
+///
+/// let mut total = 0;
+/// for region in ["east", "west", "north"].iter() {
+///     let mut contents = String::new();
+///     File::open(partition_file_path(path, region)).unwrap()
+///         .read_to_string(&mut contents).unwrap();
+///     let mut lines = contents.lines();
+///     assert_eq!(lines.next(), Some("id,region"));
+///     for line in lines {
+///         assert!(line.ends_with(&format!(",{}", region)));
+///         total += 1;
+///     }
+/// }
+/// assert_eq!(total, 300);
+///
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "region", "data_type": "varchar(3)", "generator": "choice",
+///      "choices": ["a/b", "a_b"]}
+/// ]}"#).unwrap();
+/// let config = Config { partition_by: Some("region".to_string()), ..config };
+/// let error = generate_data(&config, schema).err().unwrap();
+/// assert!(error.contains("would both be written to"));
+/// # }
+/// ```
+pub fn partitioned_file_thread(config: &Config, schema: &Schema, receiver: Receiver<String>,
+                               preamble: String) -> Result<OutputThread, String> {
+    let key_field = config.partition_by.clone()
+        .ok_or("partition_by required for partitioned output!".to_string())?;
+    let locator = KeyLocator::new(schema, &key_field)?;
+    let record_separator = schema.record_separator.clone();
+    let on_complete = config.on_complete.clone();
+    let mut writers = PartitionWriters {
+        output_file: output_file_path(config, schema)?,
+        preamble: preamble,
+        max_open: MAX_OPEN_PARTITIONS,
+        open: Vec::new(),
+        created: Vec::new(),
+        values: HashMap::new()
+    };
+
+    Ok(thread::spawn(move || {
         loop {
-            let output = match receiver.recv() {
+            let output: String = match receiver.recv() {
                 Ok(message) => {
                     message
                 }
@@ -56,22 +779,218 @@ pub fn stdout_thread(receiver: Receiver<String>) -> Result<JoinHandle<()>, Strin
                 }
             };
 
-            write!(stdout_lock, "{}", output).unwrap();
+            for row in output.split_terminator(record_separator.as_str()) {
+                let partition = locator.locate(row).unwrap_or("").trim();
+                writers.write(partition, &format!("{}{}", row, record_separator))?;
+            }
         }
-    });
-    Ok(thread)
+
+        let files = writers.finish()?;
+        info!("Wrote {} partition files.", files.len());
+        if let Some(command) = on_complete {
+            for file in files.iter() {
+                run_on_complete(&command, file)?;
+            }
+        }
+        Ok(())
+    }))
 }
 
-/// Returns a thread that outputs to a file
-pub fn file_thread(config: &Config, receiver: Receiver<String>) -> Result<JoinHandle<()>, String> {
-    let output_file = match config.output_file.clone() {
-        Some(f) => f,
-        None => return Err("output_file required when OutputMode == File!".to_string())
+/// Whether file output is split by `--max_rows_per_file`, which only happens when there
+/// are more rows than fit in one file
+fn splits_files(config: &Config) -> bool {
+    config.max_rows_per_file.map_or(false, |max_rows| max_rows < config.num_rows)
+}
+
+/// Returns the path of the `number`th file of a split output, which is the output file
+/// with the zero padded number appended to its name, before its extension.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::numbered_file_path;
+///
+/// # fn main() {
+/// assert_eq!(numbered_file_path("out/users.csv", 1), "out/users-0001.csv");
+/// assert_eq!(numbered_file_path("users", 12), "users-0012");
+/// # }
+/// ```
+pub fn numbered_file_path(output_file: &str, number: u64) -> String {
+    let path = Path::new(output_file);
+    let stem = path.file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned());
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{:04}.{}", stem, number, ext.to_string_lossy()),
+        None => format!("{}-{:04}", stem, number)
     };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// Returns the path of the `number`th file of a split output.  An output template with a
+/// `{shard}` placeholder is expanded with the file's number as the shard; otherwise the
+/// output file, already named by any template, is numbered by `numbered_file_path`.
+fn split_file_path(template: Option<&String>, table: &str, seed: Option<u64>,
+                   output_file: &str, number: u64) -> Result<String, String> {
+    match template {
+        Some(template) if template.contains("{shard}") => {
+            expand_output_template(template, table, number, seed)
+        }
+        _ => Ok(numbered_file_path(output_file, number))
+    }
+}
+
+/// Splits `data`, made of whole rows that each end with `separator`, after its first
+/// `rows` rows.  Returns those rows, how many there were, and whatever is left.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::split_rows;
+///
+/// # fn main() {
+/// assert_eq!(split_rows("a\nb\nc\n", 2, "\n"), ("a\nb\n", 2, "c\n"));
+/// assert_eq!(split_rows("a~~b~~", 5, "~~"), ("a~~b~~", 2, ""));
+/// # }
+/// ```
+pub fn split_rows<'a>(data: &'a str, rows: u64, separator: &str) -> (&'a str, u64, &'a str) {
+    let mut taken = 0;
+    let mut end = 0;
+    for (index, _) in data.match_indices(separator) {
+        if taken == rows {
+            break;
+        }
+        taken += 1;
+        end = index + separator.len();
+    }
+    (&data[..end], taken, &data[end..])
+}
+
+/// Returns a thread that writes the rows to numbered files of at most
+/// `--max_rows_per_file` rows each, named by the output template's `{shard}` when it has
+/// one, and by `numbered_file_path` otherwise.  Each file gets its own copy of the
+/// preamble, and `--on_complete` runs for each file once all are written.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use std::fs::File;
+/// use std::io::Read;
+///
+/// use fourree::config::{Config, OutputMode};
+/// use fourree::json::parse_json;
+/// use fourree::util::{generate_data, numbered_file_path};
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+/// ]}"#).unwrap();
+///
+/// let path = std::env::temp_dir().join("fourree-split.txt");
+/// let path = path.to_str().unwrap();
+/// let config = Config {
+///     num_rows: 25,
+///     batch_size: 7,
+///     output_mode: OutputMode::File,
+///     output_file: Some(path.to_string()),
+///     max_rows_per_file: Some(10),
+///     display_header: true,
+///     ..Default::default()
+/// };
+/// generate_data(&config, schema).unwrap();
+///
+/// let mut counts = Vec::new();
+/// for number in 1..4 {
+///     let mut contents = String::new();
+///     File::open(numbered_file_path(path, number)).unwrap()
+///         .read_to_string(&mut contents).unwrap();
+///     assert!(contents.starts_with("id\n"));
+///     counts.push(contents.lines().count() - 1);
+/// }
+/// assert_eq!(counts, vec![10, 10, 5]);
+/// assert!(File::open(numbered_file_path(path, 4)).is_err());
+///
+/// // The header is repeated in every file without counting towards num_rows: 10 rows in
+/// // files of 5 give two files of 1 + 5 lines
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+/// ]}"#).unwrap();
+/// let halves = std::env::temp_dir().join("fourree-split-header.txt");
+/// let halves = halves.to_str().unwrap();
+/// generate_data(&Config {
+///     num_rows: 10,
+///     batch_size: 7,
+///     output_mode: OutputMode::File,
+///     output_file: Some(halves.to_string()),
+///     max_rows_per_file: Some(5),
+///     display_header: true,
+///     ..Default::default()
+/// }, schema).unwrap();
+///
+/// for number in 1..3 {
+///     let mut contents = String::new();
+///     File::open(numbered_file_path(halves, number)).unwrap()
+///         .read_to_string(&mut contents).unwrap();
+///     assert_eq!(contents.lines().next(), Some("id"));
+///     assert_eq!(contents.lines().count(), 1 + 5);
+/// }
+/// assert!(File::open(numbered_file_path(halves, 3)).is_err());
+///
+/// // With room for every row, there is just the one file, named as usual
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+/// ]}"#).unwrap();
+/// let path = std::env::temp_dir().join("fourree-unsplit.txt");
+/// let config = Config {
+///     output_file: Some(path.to_str().unwrap().to_string()),
+///     max_rows_per_file: Some(25),
+///     ..config
+/// };
+/// generate_data(&config, schema).unwrap();
+///
+/// let mut contents = String::new();
+/// File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+/// assert_eq!(contents.lines().count(), 26);
+///
+/// // A template with {shard} numbers the files itself
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+/// ]}"#).unwrap();
+/// let dir = std::env::temp_dir();
+/// let template = format!("{}/fourree-{{table}}-{{shard}}.txt", dir.display());
+/// let config = Config {
+///     output_file: None,
+///     output_template: Some(template),
+///     max_rows_per_file: Some(10),
+///     ..config
+/// };
+/// generate_data(&config, schema).unwrap();
+///
+/// let mut counts = Vec::new();
+/// for shard in 1..4 {
+///     let mut contents = String::new();
+///     File::open(dir.join(format!("fourree-t-{:04}.txt", shard))).unwrap()
+///         .read_to_string(&mut contents).unwrap();
+///     counts.push(contents.lines().count() - 1);
+/// }
+/// assert_eq!(counts, vec![10, 10, 5]);
+/// assert!(File::open(dir.join("fourree-t-0001-0001.txt")).is_err());
+/// # }
+/// ```
+pub fn split_file_thread(config: &Config, schema: &Schema, receiver: Receiver<String>,
+                         preamble: String) -> Result<OutputThread, String> {
+    let max_rows = config.max_rows_per_file
+        .ok_or("max_rows_per_file required for split output!".to_string())?;
+    let output_file = output_file_path(config, schema)?;
+    let template = config.output_template.clone();
+    let table = schema.table_name.clone();
+    let seed = config.seed;
+    let record_separator = schema.record_separator.clone();
+    let on_complete = config.on_complete.clone();
 
     Ok(thread::spawn(move || {
-        let file = File::create(output_file).unwrap();
-        let mut writer = BufWriter::new(file);
+        let write_error = |path: &str, e: io::Error| format!("Failed to write to {}: {}", path, e);
+        let mut files = Vec::new();
+        let mut writer: Option<(String, BufWriter<File>)> = None;
+        let mut rows_in_file = 0;
 
         loop {
             let output: String = match receiver.recv() {
@@ -84,20 +1003,152 @@ pub fn file_thread(config: &Config, receiver: Receiver<String>) -> Result<JoinHa
                 }
             };
 
-            // Panic will be caught when main attempts to join()
-            writer.write(output.as_bytes()).unwrap();
+            let mut rest = output.as_str();
+            while !rest.is_empty() {
+                if writer.is_none() || rows_in_file == max_rows {
+                    if let Some((path, mut full)) = writer.take() {
+                        full.flush().map_err(|e| write_error(&path, e))?;
+                    }
+                    let number = files.len() as u64 + 1;
+                    let path = split_file_path(template.as_ref(), &table, seed, &output_file,
+                                               number)?;
+                    let file = File::create(&path)
+                        .map_err(|e| format!("Failed to create {}: {}", path, e))?;
+                    let mut next = BufWriter::new(file);
+                    next.write_all(preamble.as_bytes()).map_err(|e| write_error(&path, e))?;
+                    files.push(path.clone());
+                    writer = Some((path, next));
+                    rows_in_file = 0;
+                }
+
+                let (rows, count, remainder) = split_rows(rest, max_rows - rows_in_file,
+                                                          &record_separator);
+                if let Some((ref path, ref mut file)) = writer {
+                    file.write_all(rows.as_bytes()).map_err(|e| write_error(path, e))?;
+                }
+                rows_in_file += count;
+                rest = remainder;
+            }
         }
+
+        if let Some((path, mut last)) = writer {
+            last.flush().map_err(|e| write_error(&path, e))?;
+        }
+        info!("Wrote {} files.", files.len());
+        if let Some(command) = on_complete {
+            for file in files.iter() {
+                run_on_complete(&command, file)?;
+            }
+        }
+        Ok(())
     }))
 }
 
-/// Returns a thread that outputs to an S3 bucket
-pub fn s3_thread(config: &Config, receiver: Receiver<String>) -> Result<JoinHandle<()>, String> {
-    let output_location = match config.output_file.clone() {
-        Some(f) => f,
-        None => return Err("output_file required when OutputMode == S3!".to_string())
-    };
+/// Minimum size S3 accepts for every part of a multipart upload except the last.
+pub const S3_PART_SIZE: usize = 5242880;
+
+/// Once `data` holds at least `part_size` bytes of complete rows, removes and returns
+/// them, leaving any trailing partial row behind so a part never ends mid-row.  Rows end
+/// with `separator`.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::take_complete_rows;
+///
+/// # fn main() {
+/// let mut data = "row1\nrow2\nrow".to_string();
+/// assert_eq!(take_complete_rows(&mut data, 20, "\n"), None);
+///
+/// let part = take_complete_rows(&mut data, 8, "\n").unwrap();
+/// assert_eq!(part, "row1\nrow2\n");
+/// assert_eq!(data, "row");
+///
+/// let mut data = "row1~~row2~~row".to_string();
+/// assert_eq!(take_complete_rows(&mut data, 8, "~~").unwrap(), "row1~~row2~~");
+/// # }
+/// ```
+pub fn take_complete_rows(data: &mut String, part_size: usize, separator: &str) -> Option<String> {
+    if data.len() < part_size {
+        return None;
+    }
+
+    match data.rfind(separator) {
+        Some(last_row_start) if last_row_start + separator.len() >= part_size => {
+            let remainder = data.split_off(last_row_start + separator.len());
+            Some(mem::replace(data, remainder))
+        }
+        _ => None
+    }
+}
+
+/// What the S3 output thread does next: buffer a batch of rows, or upload whatever is left
+/// and finish once the generators hang up.  Kept apart from the data, so no batch can be
+/// mistaken for the end of the output.
+#[derive(Clone, PartialEq, Debug)]
+pub enum S3Message {
+    Data(String),
+    Finish
+}
+
+impl S3Message {
+    /// Waits for the next batch, turning the closed channel into `Finish`
+    pub fn receive(receiver: &Receiver<String>) -> S3Message {
+        match receiver.recv() {
+            Ok(message) => S3Message::Data(message),
+            Err(_) => S3Message::Finish
+        }
+    }
+}
 
-    let split_location: Vec<&str> = output_location.split(':').collect();
+/// Buffers the message's rows in `data` and returns the next part to upload, if any: a
+/// part of complete rows once `part_size` bytes have built up, or everything left on
+/// `Finish`.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::{next_s3_part, S3Message, S3_PART_SIZE};
+///
+/// # fn main() {
+/// let mut data = String::new();
+/// // A batch that happens to read "done" is data like any other
+/// assert_eq!(next_s3_part(&mut data, S3Message::Data("done".to_string()), S3_PART_SIZE, "\n"),
+///            None);
+/// assert_eq!(data, "done");
+///
+/// let part = next_s3_part(&mut data, S3Message::Finish, S3_PART_SIZE, "\n");
+/// assert_eq!(part, Some("done".to_string()));
+/// assert!(data.is_empty());
+/// # }
+/// ```
+pub fn next_s3_part(data: &mut String, message: S3Message, part_size: usize, separator: &str)
+        -> Option<String> {
+    match message {
+        S3Message::Data(rows) => {
+            data.push_str(&rows);
+            take_complete_rows(data, part_size, separator)
+        },
+        S3Message::Finish => Some(data.drain(..).collect())
+    }
+}
+
+/// Splits an S3 output location of the form bucket:path, requiring both parts.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::parse_s3_location;
+///
+/// # fn main() {
+/// assert!(parse_s3_location("mybucket:").is_err());
+/// assert!(parse_s3_location(":key").is_err());
+/// assert_eq!(parse_s3_location("mybucket:path/file.csv").unwrap(),
+///            ("mybucket".to_string(), "path/file.csv".to_string()));
+/// # }
+/// ```
+pub fn parse_s3_location(location: &str) -> Result<(String, String), String> {
+    let split_location: Vec<&str> = location.splitn(2, ':').collect();
 
     if split_location.len() < 2 {
         return Err(
@@ -105,11 +1156,90 @@ pub fn s3_thread(config: &Config, receiver: Receiver<String>) -> Result<JoinHand
         );
     }
 
-    let bucket = split_location[0].to_string();
-    let output_file = split_location[1].to_string();
+    let bucket = split_location[0].trim();
+    let key = split_location[1].trim();
+
+    if bucket.is_empty() {
+        return Err(format!("The S3 bucket is missing from output_file {}.", location));
+    }
+    if key.is_empty() {
+        return Err(format!("The S3 key is missing from output_file {}.", location));
+    }
+
+    Ok((bucket.to_string(), key.to_string()))
+}
+
+/// Parses the AWS region S3 output uploads to, such as "eu-west-1", defaulting to us-east-1.
+/// With an endpoint, the upload goes to that S3-compatible store instead, under a region of
+/// any name.  Requests always address the bucket in the path, as MinIO expects.
+///
+/// To check an upload against a local MinIO by hand:
+///
+/// ```text
+/// docker run -p 9000:9000 -e MINIO_ROOT_USER=minio -e MINIO_ROOT_PASSWORD=minio123 \
+///     minio/minio server /data
+/// AWS_ACCESS_KEY_ID=minio AWS_SECRET_ACCESS_KEY=minio123 aws --endpoint-url \
+///     http://localhost:9000 s3 mb s3://test
+/// AWS_ACCESS_KEY_ID=minio AWS_SECRET_ACCESS_KEY=minio123 fourree schema.json -o s3 \
+///     -f test:rows.txt --s3_endpoint http://localhost:9000
+/// ```
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rusoto_core;
+/// use rusoto_core::Region;
+/// use fourree::util::parse_s3_region;
+///
+/// # fn main() {
+/// assert_eq!(parse_s3_region(Some("us-west-2"), None).unwrap(), Region::UsWest2);
+/// assert_eq!(parse_s3_region(None, None).unwrap(), Region::UsEast1);
+/// assert!(parse_s3_region(Some("moon-north-1"), None).is_err());
+///
+/// assert_eq!(parse_s3_region(None, Some("http://localhost:9000")).unwrap(), Region::Custom {
+///     name: "us-east-1".to_string(),
+///     endpoint: "http://localhost:9000".to_string()
+/// });
+/// assert_eq!(parse_s3_region(Some("minio"), Some("http://localhost:9000")).unwrap(),
+///            Region::Custom {
+///                name: "minio".to_string(),
+///                endpoint: "http://localhost:9000".to_string()
+///            });
+/// assert!(parse_s3_region(None, Some("localhost:9000")).is_err());
+/// # }
+/// ```
+pub fn parse_s3_region(region: Option<&str>, endpoint: Option<&str>) -> Result<Region, String> {
+    match (region, endpoint) {
+        (_, Some(e)) if !e.starts_with("http://") && !e.starts_with("https://") => {
+            Err(format!("Invalid S3 endpoint {}, expected a URL such as http://localhost:9000.",
+                        e))
+        },
+        (r, Some(e)) => Ok(Region::Custom {
+            name: r.unwrap_or("us-east-1").to_string(),
+            endpoint: e.trim_end_matches('/').to_string()
+        }),
+        (Some(r), None) => {
+            Region::from_str(r).map_err(|e| format!("Invalid S3 region {}: {}", r, e))
+        },
+        (None, None) => Ok(Region::UsEast1)
+    }
+}
+
+/// Returns a thread that outputs to an S3 bucket
+pub fn s3_thread(config: &Config, schema: &Schema, receiver: Receiver<String>, preamble: String,
+                 generation_failed: Arc<AtomicBool>) -> Result<OutputThread, String> {
+    let record_separator = schema.record_separator.clone();
+    let output_location = match config.output_file.clone() {
+        Some(f) => f,
+        None => return Err("output_file required when OutputMode == S3!".to_string())
+    };
+
+    let (bucket, output_file) = parse_s3_location(&output_location)?;
 
     // Initiate multipart upload process
-    let client = S3Client::new(Region::UsEast1);
+    let region = parse_s3_region(config.s3_region.as_ref().map(|r| r.as_str()),
+                                 config.s3_endpoint.as_ref().map(|e| e.as_str()))?;
+    let client = S3Client::new(region);
     let create_multipart_req = CreateMultipartUploadRequest {
         bucket: bucket.to_owned(),
         key: output_file.to_owned(),
@@ -127,31 +1257,46 @@ pub fn s3_thread(config: &Config, receiver: Receiver<String>) -> Result<JoinHand
         Some(id) => id,
         None => return Err("No UploadID returned from S3!".to_string())
     };
+    let mut encryptor = output_encryptor(config)?;
 
     Ok(thread::spawn(move || {
         let mut part_number = 1;
-        let mut data = String::new();
+        let mut data = preamble;
         let mut completed_parts = Vec::new();
 
         loop {
-            let message: String = match receiver.recv() {
-                Ok(message) => {
-                    message
-                }
-                Err(_) => {
-                    "done".to_string()
-                }
-            };
+            let message = S3Message::receive(&receiver);
+            let finished = message == S3Message::Finish;
 
-            if &message != "done" {
-                data.push_str(&message);
+            // The generators hang up on failure too, when the partial output must not be kept
+            if finished && generation_failed.load(Ordering::SeqCst) {
+                warn!("Data generation failed, aborting the multipart upload.");
+                abort_s3_upload(&client, &bucket, &output_file, &upload_id);
+                return Ok(());
             }
 
-            if data.len() > 5242880 || &message == "done" {
+            if let Some(local_data) = next_s3_part(&mut data, message, S3_PART_SIZE,
+                                                   &record_separator) {
                 info!("Writing part to S3...");
 
-                let local_data: String = data.drain(..).collect();
-                let local_data_bytes = local_data.into_bytes();
+                let mut local_data_bytes = Vec::new();
+                if encryptor.is_some() && part_number == 1 {
+                    local_data_bytes.extend_from_slice(HEADER);
+                }
+                // The last part also carries the final chunk that ends encrypted output
+                let encoded = encode_chunk(&mut encryptor, local_data.into_bytes())
+                    .and_then(|chunk| if finished {
+                        Ok([chunk, encode_end(&mut encryptor)?].concat())
+                    } else {
+                        Ok(chunk)
+                    });
+                match encoded {
+                    Ok(chunk) => local_data_bytes.extend(chunk),
+                    Err(e) => {
+                        abort_s3_upload(&client, &bucket, &output_file, &upload_id);
+                        return Err(e);
+                    }
+                }
 
                 let mut tries = 1;
                 let part_response;
@@ -173,7 +1318,8 @@ pub fn s3_thread(config: &Config, receiver: Receiver<String>) -> Result<JoinHand
                             error!("Error: {:?}", error);
                             if tries > 5 {
                                 abort_s3_upload(&client, &bucket, &output_file, &upload_id);
-                                panic!("Multipart upload failed after {} attempts.", tries);
+                                return Err(format!("Multipart upload failed after {} attempts.",
+                                                   tries));
                             }
                             error!("Multipart upload failed, retry {}", tries);
                             tries += 1;
@@ -192,7 +1338,7 @@ pub fn s3_thread(config: &Config, receiver: Receiver<String>) -> Result<JoinHand
                 part_number += 1;
             }
 
-            if &message == "done" {
+            if finished {
                 break;
             }
         }
@@ -212,17 +1358,566 @@ pub fn s3_thread(config: &Config, receiver: Receiver<String>) -> Result<JoinHand
             Ok(r) => {
                 debug!("{:#?}", r);
                 info!("Multipart upload completed.");
+                Ok(())
             },
             Err(error) => {
                 info!("Multipart upload failed, aborting...");
                 info!("Error: {:?}", error);
                 abort_s3_upload(&client, &bucket, &output_file, &upload_id);
-                panic!("{:?}", error);
+                Err(format!("{:?}", error))
             }
+        }
+    }))
+}
+
+/// Parses the Content-Type that http output POSTs its batches with, such as "text/csv".
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::parse_content_type;
+///
+/// # fn main() {
+/// assert!(parse_content_type("text/csv").is_ok());
+/// assert!(parse_content_type("csv").is_err());
+/// # }
+/// ```
+pub fn parse_content_type(content_type: &str) -> Result<ContentType, String> {
+    content_type.parse::<Mime>()
+        .map(ContentType)
+        .map_err(|e| format!("Invalid content type {}: {}", content_type, e))
+}
+
+/// How long http output waits before retrying a batch that has failed `failures` times:
+/// 100ms, doubling with each failure up to 10s.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use std::time::Duration;
+/// use fourree::util::http_retry_delay;
+///
+/// # fn main() {
+/// assert_eq!(http_retry_delay(1), Duration::from_millis(100));
+/// assert_eq!(http_retry_delay(3), Duration::from_millis(400));
+/// assert_eq!(http_retry_delay(30), Duration::from_secs(10));
+/// # }
+/// ```
+pub fn http_retry_delay(failures: u32) -> Duration {
+    let millis = 100u64.saturating_mul(1u64 << failures.saturating_sub(1).min(32));
+    Duration::from_millis(millis.min(10000))
+}
+
+/// POSTs a batch, retrying server errors and failed connections until it has failed more
+/// than `retries` times.  Any other response is an error straight away.
+fn post_batch(client: &reqwest::Client, url: &str, content_type: &ContentType, body: &str,
+              retries: u32) -> Result<(), String> {
+    let mut failures = 0;
+    loop {
+        let result = client.post(url).and_then(|mut request| {
+            request.header(content_type.clone()).body(body.to_string()).send()
+        });
+        let error = match result {
+            Ok(ref response) if response.status().is_success() => return Ok(()),
+            Ok(ref response) if response.status().is_server_error() => {
+                format!("POST to {} failed: {}", url, response.status())
+            },
+            Ok(response) => return Err(format!("POST to {} was rejected: {}", url,
+                                               response.status())),
+            Err(e) => format!("POST to {} failed: {}", url, e)
+        };
+
+        failures += 1;
+        if failures > retries {
+            return Err(format!("{}, giving up after {} attempts.", error, failures));
+        }
+        let delay = http_retry_delay(failures);
+        warn!("{}, retry {} in {:?}", error, failures, delay);
+        thread::sleep(delay);
+    }
+}
+
+/// Once `data` holds at least `rows` complete rows, removes and returns the first `rows`
+/// of them.  Rows end with `separator`.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::take_rows;
+///
+/// # fn main() {
+/// let mut data = "row1\nrow2\nrow3\n".to_string();
+/// assert_eq!(take_rows(&mut data, 4, "\n"), None);
+///
+/// assert_eq!(take_rows(&mut data, 2, "\n").unwrap(), "row1\nrow2\n");
+/// assert_eq!(data, "row3\n");
+/// # }
+/// ```
+pub fn take_rows(data: &mut String, rows: u64, separator: &str) -> Option<String> {
+    let (last_row_start, _) = data.match_indices(separator).nth((rows as usize).checked_sub(1)?)?;
+    let remainder = data.split_off(last_row_start + separator.len());
+    Some(mem::replace(data, remainder))
+}
+
+/// Returns a thread that POSTs the rows it receives to `config.http_url` in batches of
+/// `config.batch_size` rows, with the last batch taking whatever rows are left over and
+/// any preamble sent ahead of the first batch.  Batches are cut at the schema's record
+/// separator, whatever the size of the chunks the generators send.  Once a batch can't be
+/// delivered the thread returns the reason, which `generate_data` reports when it joins
+/// the thread.
+pub fn http_thread(config: &Config, schema: &Schema, receiver: Receiver<String>,
+                   preamble: String) -> Result<OutputThread, String> {
+    let url = config.http_url.clone()
+        .ok_or("A URL is required when OutputMode == Http!".to_string())?;
+    let content_type = parse_content_type(&config.http_content_type)?;
+    let retries = config.http_retries;
+    let batch_size = config.batch_size;
+    let separator = schema.record_separator.clone();
+    let client = reqwest::Client::new()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    Ok(thread::spawn(move || {
+        let mut preamble = preamble;
+        let mut data = String::new();
+        // Chunks always end on a row boundary, so counting separators counts whole rows
+        let mut rows = 0;
+        for chunk in receiver.iter() {
+            rows += chunk.matches(separator.as_str()).count() as u64;
+            data.push_str(&chunk);
+
+            while rows >= batch_size {
+                if let Some(batch) = take_rows(&mut data, batch_size, &separator) {
+                    let body = mem::take(&mut preamble) + &batch;
+                    post_batch(&client, &url, &content_type, &body, retries)?;
+                }
+                rows -= batch_size;
+            }
+        }
+        info!("Schema generation complete.");
+
+        // A preamble with no rows after it still goes out
+        if !preamble.is_empty() || !data.is_empty() {
+            post_batch(&client, &url, &content_type, &(preamble + &data), retries)?;
+        }
+        info!("HTTP output completed.");
+        Ok(())
+    }))
+}
+
+/// Locates the value of a single field within a generated row, used to derive message keys.
+pub struct KeyLocator {
+    delimiter: String,
+    index: usize,
+    span: Option<(usize, usize)>
+}
+
+impl KeyLocator {
+    /// Builds a locator for the field named `key_field`, using the schema's delimiter, or
+    /// the field lengths when the schema is fixed width.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate fourree;
+    /// use fourree::json::parse_json;
+    /// use fourree::util::KeyLocator;
+    ///
+    /// # fn main() {
+    /// let schema = parse_json(r#"{"table_name": "t", "delimiter": ",", "fields": [
+    ///     {"name": "a", "data_type": "integer", "generator": "integer", "min": 0, "max": 10},
+    ///     {"name": "b", "data_type": "integer", "generator": "integer", "min": 0, "max": 10}
+    /// ]}"#).unwrap();
+    /// let locator = KeyLocator::new(&schema, "b").unwrap();
+    /// assert_eq!(locator.locate("1,2"), Some("2"));
+    /// assert!(KeyLocator::new(&schema, "c").is_err());
+    /// # }
+    /// ```
+    pub fn new(schema: &Schema, key_field: &str) -> Result<KeyLocator, String> {
+        let index = schema.fields.iter()
+            .position(|f| f.name == key_field)
+            .ok_or(format!("Key field {} does not exist in the schema.", key_field))?;
+
+        let span = if schema.delimiter == "fixed" {
+            let start = schema.fields[..index].iter()
+                .map(|f| f.length.unwrap_or(0))
+                .sum();
+            Some((start, start + schema.fields[index].length.unwrap_or(0)))
+        } else {
+            None
         };
+
+        Ok(KeyLocator {
+            delimiter: schema.delimiter.clone(),
+            index: index,
+            span: span
+        })
+    }
+
+    /// Returns the key field's value within `row`, if present.
+    pub fn locate<'a>(&self, row: &'a str) -> Option<&'a str> {
+        match self.span {
+            Some((start, end)) => row.get(start..end),
+            None => row.split(self.delimiter.as_str()).nth(self.index)
+        }
+    }
+}
+
+/// Splits a Kafka output location of the form brokers:topic on its final ':', since the
+/// brokers carry ports of their own.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::parse_kafka_location;
+///
+/// # fn main() {
+/// assert_eq!(parse_kafka_location("host1:9092,host2:9092:rows").unwrap(),
+///            ("host1:9092,host2:9092".to_string(), "rows".to_string()));
+/// assert!(parse_kafka_location("localhost:9092:").is_err());
+/// assert!(parse_kafka_location("rows").is_err());
+/// # }
+/// ```
+pub fn parse_kafka_location(location: &str) -> Result<(String, String), String> {
+    match location.rfind(':') {
+        Some(i) if i > 0 && i < location.len() - 1 => {
+            Ok((location[..i].to_string(), location[i + 1..].to_string()))
+        },
+        _ => Err(format!("output_file must follow the format brokers:topic when \
+                          OutputMode == Kafka, not {}.", location))
+    }
+}
+
+/// Starts the Kafka output thread, or errors when the feature wasn't compiled in.
+#[cfg(feature = "kafka")]
+fn kafka_output(config: &Config, schema: &Schema, receiver: Receiver<String>)
+        -> Result<OutputThread, String> {
+    kafka_thread(config, schema, receiver)
+}
+
+#[cfg(not(feature = "kafka"))]
+fn kafka_output(_config: &Config, _schema: &Schema, _receiver: Receiver<String>)
+        -> Result<OutputThread, String> {
+    Err("Kafka output requires fourree to be built with the 'kafka' feature.".to_string())
+}
+
+/// Counts failed deliveries reported back by the Kafka producer.
+#[cfg(feature = "kafka")]
+struct DeliveryTracker {
+    failed: AtomicUsize
+}
+
+#[cfg(feature = "kafka")]
+impl ClientContext for DeliveryTracker {}
+
+#[cfg(feature = "kafka")]
+impl ProducerContext for DeliveryTracker {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult, _: Self::DeliveryOpaque) {
+        if let Err((ref error, _)) = *result {
+            error!("Kafka delivery failed: {}", error);
+            self.failed.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Returns a thread that publishes each row as a message to a Kafka topic, or each chunk
+/// of rows it receives with `kafka_batches`.  Rows are keyed by the field named by
+/// `kafka_key`, or else by the schema's own "kafka_key".
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rdkafka;
+/// use std::sync::mpsc::channel;
+/// use std::time::Duration;
+///
+/// use rdkafka::config::ClientConfig;
+/// use rdkafka::Message;
+/// use rdkafka::consumer::{BaseConsumer, Consumer};
+/// use rdkafka::mocking::MockCluster;
+///
+/// use fourree::config::{Config, OutputMode};
+/// use fourree::json::parse_json;
+/// use fourree::util::kafka_thread;
+///
+/// # fn main() {
+/// let cluster = MockCluster::new(1).unwrap();
+/// cluster.create_topic("rows", 1, 1).unwrap();
+/// cluster.create_topic("batches", 1, 1).unwrap();
+///
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10}
+/// ]}"#).unwrap();
+/// let config = Config {
+///     output_mode: OutputMode::Kafka,
+///     kafka_brokers: Some(cluster.bootstrap_servers()),
+///     kafka_topic: Some("rows".to_string()),
+///     kafka_key: Some("id".to_string()),
+///     ..Default::default()
+/// };
+///
+/// let (sender, receiver) = channel();
+/// let thread = kafka_thread(&config, &schema, receiver).unwrap();
+/// sender.send("1\n2\n3\n".to_string()).unwrap();
+/// drop(sender);
+/// thread.join().unwrap().unwrap();
+///
+/// let consumer: BaseConsumer = ClientConfig::new()
+///     .set("bootstrap.servers", &cluster.bootstrap_servers())
+///     .set("group.id", "fourree-test")
+///     .set("auto.offset.reset", "earliest")
+///     .create()
+///     .unwrap();
+/// consumer.subscribe(&["rows"]).unwrap();
+///
+/// let mut received = 0;
+/// while received < 3 {
+///     if let Some(message) = consumer.poll(Duration::from_secs(10)) {
+///         message.unwrap();
+///         received += 1;
+///     }
+/// }
+///
+/// // Batches go out whole, and can't be keyed
+/// let config = Config {
+///     kafka_topic: Some("batches".to_string()),
+///     kafka_key: None,
+///     kafka_batches: true,
+///     ..config
+/// };
+/// let keyed = parse_json(r#"{"table_name": "t", "kafka_key": "id", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10}
+/// ]}"#).unwrap();
+/// assert!(kafka_thread(&config, &keyed, channel().1).is_err());
+///
+/// let (sender, receiver) = channel();
+/// let thread = kafka_thread(&config, &schema, receiver).unwrap();
+/// sender.send("4\n5\n".to_string()).unwrap();
+/// drop(sender);
+/// thread.join().unwrap().unwrap();
+///
+/// let consumer: BaseConsumer = ClientConfig::new()
+///     .set("bootstrap.servers", &cluster.bootstrap_servers())
+///     .set("group.id", "fourree-test-batches")
+///     .set("auto.offset.reset", "earliest")
+///     .create()
+///     .unwrap();
+/// consumer.subscribe(&["batches"]).unwrap();
+/// loop {
+///     if let Some(message) = consumer.poll(Duration::from_secs(10)) {
+///         assert_eq!(message.unwrap().payload(), Some(&b"4\n5\n"[..]));
+///         break;
+///     }
+/// }
+/// # }
+/// ```
+#[cfg(feature = "kafka")]
+pub fn kafka_thread(config: &Config, schema: &Schema, receiver: Receiver<String>)
+        -> Result<OutputThread, String> {
+    let brokers = config.kafka_brokers.clone()
+        .ok_or("Brokers are required when OutputMode == Kafka!".to_string())?;
+    let topic = config.kafka_topic.clone()
+        .ok_or("A topic is required when OutputMode == Kafka!".to_string())?;
+    let key_field = config.kafka_key.as_ref().or(schema.kafka_key.as_ref());
+    if key_field.is_some() && config.kafka_batches {
+        return Err("A Kafka message key needs one message per row, not --kafka_messages batch."
+                   .to_string())
+    }
+    if key_field.is_some() && config.output_format != OutputFormat::Delimited {
+        return Err("A Kafka message key is not supported with --format jsonl.".to_string())
+    }
+    let key_locator = match key_field {
+        Some(key_field) => Some(KeyLocator::new(schema, key_field)?),
+        None => None
+    };
+    let per_batch = config.kafka_batches;
+    let record_separator = schema.record_separator.clone();
+
+    let tracker = DeliveryTracker { failed: AtomicUsize::new(0) };
+    let producer: BaseProducer<DeliveryTracker> = ClientConfig::new()
+        .set("bootstrap.servers", &brokers)
+        .create_with_context(tracker)
+        .map_err(|e| format!("Failed to create Kafka producer: {}", e))?;
+
+    Ok(thread::spawn(move || {
+        loop {
+            let output: String = match receiver.recv() {
+                Ok(message) => {
+                    message
+                }
+                Err(_) => {
+                    info!("Schema generation complete.");
+                    break;
+                }
+            };
+
+            let messages: Vec<&str> = if per_batch {
+                vec![output.as_str()]
+            } else {
+                output.split(record_separator.as_str()).filter(|r| !r.is_empty()).collect()
+            };
+
+            for row in messages {
+                let key = key_locator.as_ref().and_then(|l| l.locate(row));
+
+                loop {
+                    let mut record = BaseRecord::to(&topic).payload(row);
+                    if let Some(k) = key {
+                        record = record.key(k);
+                    }
+
+                    match producer.send(record) {
+                        Ok(_) => break,
+                        Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => {
+                            // Wait for the local queue to drain before retrying
+                            producer.poll(Duration::from_millis(100));
+                        },
+                        Err((error, _)) => {
+                            return Err(format!("Failed to produce Kafka message: {}", error))
+                        }
+                    }
+                }
+                producer.poll(Duration::from_millis(0));
+            }
+        }
+
+        info!("Flushing Kafka producer...");
+        producer.flush(Duration::from_secs(30))
+            .map_err(|e| format!("Failed to flush Kafka producer: {}", e))?;
+
+        let failed = producer.context().failed.load(Ordering::SeqCst);
+        if failed > 0 {
+            return Err(format!("{} Kafka messages failed to be delivered.", failed));
+        }
+        info!("Kafka output completed.");
+        Ok(())
     }))
 }
 
+/// Starts the PostgreSQL output thread, or errors when the feature wasn't compiled in.
+#[cfg(feature = "postgres")]
+fn postgres_output(config: &Config, schema: &Schema, receiver: Receiver<String>)
+        -> Result<OutputThread, String> {
+    postgres_thread(config, schema, receiver)
+}
+
+#[cfg(not(feature = "postgres"))]
+fn postgres_output(_config: &Config, _schema: &Schema, _receiver: Receiver<String>)
+        -> Result<OutputThread, String> {
+    Err("PostgreSQL output requires fourree to be built with the 'postgres' feature.".to_string())
+}
+
+/// Returns a thread that streams the rows into the schema's table with `COPY ... FROM STDIN`,
+/// all in one transaction.  With `display_ddl` the table is created first, in the same
+/// transaction.  Any failure rolls the transaction back and returns the reason, which
+/// `generate_data` reports when it joins the thread.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate postgres;
+/// use std::sync::mpsc::channel;
+/// use postgres::{Client, NoTls};
+///
+/// use fourree::config::{Config, OutputMode};
+/// use fourree::json::parse_json;
+/// use fourree::util::postgres_thread;
+///
+/// # fn main() {
+/// // Requires a scratch database, e.g. FOURREE_TEST_DATABASE_URL=postgresql://localhost/test
+/// let conn = match std::env::var("FOURREE_TEST_DATABASE_URL") {
+///     Ok(c) => c,
+///     Err(_) => return
+/// };
+/// let mut client = Client::connect(&conn, NoTls).unwrap();
+/// client.batch_execute("DROP TABLE IF EXISTS fourree_copy").unwrap();
+///
+/// let schema = parse_json(r#"{"table_name": "fourree_copy", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 10}
+/// ]}"#).unwrap();
+/// let config = Config {
+///     output_mode: OutputMode::PostgreSQL,
+///     connection_string: Some(conn.clone()),
+///     display_ddl: true,
+///     ..Default::default()
+/// };
+///
+/// let (sender, receiver) = channel();
+/// let thread = postgres_thread(&config, &schema, receiver).unwrap();
+/// sender.send("1\n2\n3\n".to_string()).unwrap();
+/// drop(sender);
+/// thread.join().unwrap().unwrap();
+///
+/// let count: i64 = client.query_one("SELECT count(*) FROM fourree_copy", &[]).unwrap().get(0);
+/// assert_eq!(count, 3);
+///
+/// // A row that doesn't fit the table rolls the whole COPY back
+/// let (sender, receiver) = channel();
+/// let config = Config { display_ddl: false, ..config };
+/// let thread = postgres_thread(&config, &schema, receiver).unwrap();
+/// sender.send("4\nfive\n".to_string()).unwrap();
+/// drop(sender);
+/// assert!(thread.join().unwrap().is_err());
+///
+/// let count: i64 = client.query_one("SELECT count(*) FROM fourree_copy", &[]).unwrap().get(0);
+/// assert_eq!(count, 3);
+/// client.batch_execute("DROP TABLE fourree_copy").unwrap();
+/// # }
+/// ```
+#[cfg(feature = "postgres")]
+pub fn postgres_thread(config: &Config, schema: &Schema, receiver: Receiver<String>)
+        -> Result<OutputThread, String> {
+    let connection_string = config.connection_string.clone()
+        .ok_or("A connection string is required when OutputMode == PostgreSQL!".to_string())?;
+    let copy = copy_statement(schema)?;
+    let ddl = if config.display_ddl { Some(schema.generate_ddl()) } else { None };
+    if config.display_header {
+        warn!("The header is not copied to PostgreSQL, only data rows.");
+    }
+
+    let mut client = Client::connect(&connection_string, NoTls)
+        .map_err(|e| format!("Failed to connect to PostgreSQL: {}", postgres_error(e)))?;
+
+    Ok(thread::spawn(move || {
+        let rows = copy_rows(&mut client, ddl, &copy, receiver)
+            .map_err(|e| format!("PostgreSQL COPY failed and was rolled back: {}", e))?;
+        info!("PostgreSQL output completed, copied {} rows.", rows);
+        Ok(())
+    }))
+}
+
+/// Copies every batch from the receiver in a single transaction, returning the row count.
+/// Returning early drops the transaction, which rolls it back.
+#[cfg(feature = "postgres")]
+fn copy_rows(client: &mut Client, ddl: Option<String>, copy: &str, receiver: Receiver<String>)
+        -> Result<u64, String> {
+    let mut transaction = client.transaction().map_err(postgres_error)?;
+    if let Some(ddl) = ddl {
+        transaction.batch_execute(&ddl).map_err(postgres_error)?;
+    }
+
+    let rows = {
+        let mut writer = transaction.copy_in(copy).map_err(postgres_error)?;
+        for batch in receiver.iter() {
+            writer.write_all(batch.as_bytes()).map_err(|e| e.to_string())?;
+        }
+        info!("Schema generation complete.");
+        writer.finish().map_err(postgres_error)?
+    };
+
+    transaction.commit().map_err(postgres_error)?;
+    Ok(rows)
+}
+
+/// Describes a PostgreSQL error, using the server's own message when there is one
+#[cfg(feature = "postgres")]
+fn postgres_error(error: postgres::Error) -> String {
+    match error.as_db_error() {
+        Some(db_error) => db_error.to_string(),
+        None => error.to_string()
+    }
+}
+
 pub fn abort_s3_upload(client: &S3Client, bucket: &String, key: &String, upload_id: &String) {
     let abort_multipart_upload_req = AbortMultipartUploadRequest {
         bucket: bucket.to_owned(),
@@ -243,77 +1938,951 @@ pub fn abort_s3_upload(client: &S3Client, bucket: &String, key: &String, upload_
     }
 }
 
+/// Default number of times a duplicate row or unique value is regenerated before giving up.
+pub const DEDUPE_MAX_RETRIES: u64 = 1000;
+
+/// Tracks the hashes of every row emitted so far, shared by all generator threads.
+///
+/// Deduplication happens before rows are sent to the output thread, so that a duplicate
+/// can be regenerated and the requested row count still reached.  This costs roughly
+/// 8 bytes (plus hash set overhead) per distinct row for the whole run, and every row
+/// passes through a single lock, which serializes part of the work across threads.
+///
+/// Only a 64-bit hash of each row is kept, so two different rows whose hashes collide are
+/// treated as duplicates and the second is regenerated.  The check is probabilistic: the
+/// chance of any collision is roughly n² / 2^65 for n rows, about 3 in 10,000 at 100
+/// million rows, and a collision only costs a retry, never a duplicate row.
+pub struct RowDeduper {
+    seen: Mutex<HashSet<u64>>,
+    max_retries: u64
+}
+
+impl RowDeduper {
+    pub fn new(max_retries: u64) -> RowDeduper {
+        RowDeduper {
+            seen: Mutex::new(HashSet::new()),
+            max_retries: max_retries
+        }
+    }
+
+    /// Records the row, returning false if an identical row was already emitted
+    pub fn insert(&self, row: &str) -> bool {
+        let mut hasher = DefaultHasher::new();
+        row.hash(&mut hasher);
+        self.seen.lock().unwrap().insert(hasher.finish())
+    }
+}
+
+/// Generates `size` rows that have not been produced before, regenerating duplicates up
+/// to the deduper's retry limit.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::json::parse_json;
+/// use fourree::config::OutputFormat;
+/// use fourree::util::{generate_distinct_rows, RowDeduper};
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "c", "data_type": "char(1)", "generator": "choice", "choices": ["A", "B"]}
+/// ]}"#).unwrap();
+/// let mut rng = rand::thread_rng();
+///
+/// let deduper = RowDeduper::new(1000);
+/// let rows = generate_distinct_rows(&schema, &mut rng, 2, &deduper, OutputFormat::Delimited)
+///     .unwrap();
+/// assert!(rows == "A\nB\n" || rows == "B\nA\n");
+///
+/// let error = generate_distinct_rows(&schema, &mut rng, 1, &deduper, OutputFormat::Delimited)
+///     .err().unwrap();
+/// assert!(error.contains("exhausted"));
+/// # }
+/// ```
+pub fn generate_distinct_rows<R: rand::Rng>(schema: &Schema, rng: &mut R, size: u64,
+                                            deduper: &RowDeduper, format: OutputFormat)
+        -> Result<String, String> {
+    let mut output = String::new();
 
-/// Generates a batch of data based on the provided parameters.
-pub fn generate_batch(schema: &Schema, batch_size: u64,
-                  channel: &Sender<String>, rng: &mut rand::ThreadRng) {
+    for _ in 0..size {
+        output.push_str(&generate_distinct_row(schema, rng, deduper, format)?);
+        output.push_str(&schema.record_separator);
+    }
+    Ok(output)
+}
+
+/// Generates a single row that has not been produced before
+fn generate_distinct_row<R: rand::Rng>(schema: &Schema, rng: &mut R, deduper: &RowDeduper,
+                                       format: OutputFormat) -> Result<String, String> {
+    let mut retries = 0;
+    loop {
+        let row = generate_formatted_row(schema, rng, format)?;
+        if deduper.insert(&row) {
+            return Ok(row);
+        }
+
+        retries += 1;
+        if retries > deduper.max_retries {
+            return Err(format!(
+                "Distinct rows exhausted: no new row found after {} attempts.", retries - 1));
+        }
+    }
+}
+
+/// Generates a single row in the output format
+fn generate_formatted_row<R: rand::Rng>(schema: &Schema, rng: &mut R, format: OutputFormat)
+        -> Result<String, String> {
+    match format {
+        OutputFormat::Delimited => schema.generate_row(rng),
+        OutputFormat::JsonLines{ spaces } => schema.generate_row_json(rng, spaces)
+    }
+}
+
+/// Rows are sent to the output thread in chunks of about this many bytes, so that the
+/// memory a batch holds does not grow with its size
+pub const OUTPUT_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Sends rows to the output thread as they are generated, each terminated by the separator
+/// and grouped into chunks of about `OUTPUT_CHUNK_BYTES` that always end on a row boundary.
+/// The channel may carry any message that a chunk converts into.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use std::sync::mpsc::sync_channel;
+/// use fourree::util::{send_rows, OUTPUT_CHUNK_BYTES};
+///
+/// # fn main() {
+/// let row = "x".repeat(1000);
+/// let (sender, receiver) = sync_channel(1000);
+/// send_rows((0..200).map(|_| Ok(row.clone())), "\n", &sender).unwrap();
+/// drop(sender);
+///
+/// let chunks: Vec<String> = receiver.iter().collect();
+/// assert!(chunks.len() > 1);
+/// assert!(chunks.iter().all(|c| c.len() <= OUTPUT_CHUNK_BYTES + 1001 && c.ends_with('\n')));
+/// assert_eq!(chunks.concat().lines().count(), 200);
+/// # }
+/// ```
+pub fn send_rows<I, T>(rows: I, separator: &str, channel: &SyncSender<T>) -> Result<(), String>
+        where I: Iterator<Item = Result<String, String>>, T: From<String> {
+    let mut chunk = String::new();
+    for row in rows {
+        chunk.push_str(&row?);
+        chunk.push_str(separator);
+        if chunk.len() >= OUTPUT_CHUNK_BYTES {
+            channel.send(T::from(mem::replace(&mut chunk, String::new())))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    if !chunk.is_empty() {
+        channel.send(T::from(chunk)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Generates a batch of data based on the provided parameters, streaming the rows to the
+/// output thread in chunks.  When a SQL dialect is given, the batch is instead rendered
+/// as a single INSERT statement.
+pub fn generate_batch<R, T>(schema: &Schema, batch_size: u64, channel: &SyncSender<T>,
+                            rng: &mut R, deduper: Option<&RowDeduper>,
+                            dialect: Option<SqlDialect>, format: OutputFormat)
+        -> Result<(), String> where R: rand::Rng, T: From<String> {
     let batch_start = time::precise_time_s();
-    let rows = schema.generate_rows(rng, batch_size).unwrap();
-    channel.send(rows).unwrap();
+    let separator = schema.record_separator.as_str();
+    match (dialect, deduper) {
+        (Some(d), _) => {
+            let values = (0..batch_size).map(|_| schema.generate_values(rng))
+                .collect::<Result<Vec<_>, String>>()?;
+            channel.send(T::from(insert_statement(schema, &values, d)))
+                .map_err(|e| e.to_string())?;
+        },
+        (None, Some(d)) => {
+            let rows = (0..batch_size).map(|_| generate_distinct_row(schema, rng, d, format));
+            send_rows(rows, separator, channel)?;
+        },
+        (None, None) => {
+            let rows = (0..batch_size).map(|_| generate_formatted_row(schema, rng, format));
+            send_rows(rows, separator, channel)?;
+        }
+    }
     let batch_elapsed = time::precise_time_s();
     info!("{} rows proccessed, {} s elapsed", batch_size, batch_elapsed-batch_start);
+    Ok(())
+}
+
+/// Seconds between progress reports, with `--progress`
+pub const PROGRESS_INTERVAL_SECONDS: f64 = 5.0;
+
+/// Describes how far along a run is: the rows generated out of the total, the throughput,
+/// and the time left at that throughput.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::progress_report;
+///
+/// # fn main() {
+/// assert_eq!(progress_report(2500, 10000, 5.0),
+///            "2500 of 10000 rows generated (25.0%), 500 rows/s, ETA 00:00:15");
+/// assert_eq!(progress_report(0, 10000, 0.0),
+///            "0 of 10000 rows generated (0.0%), 0 rows/s, ETA unknown");
+/// # }
+/// ```
+pub fn progress_report(rows_done: u64, total_rows: u64, elapsed: f64) -> String {
+    let percent = if total_rows == 0 {
+        100.0
+    } else {
+        rows_done as f64 * 100.0 / total_rows as f64
+    };
+    let rate = if elapsed > 0.0 { rows_done as f64 / elapsed } else { 0.0 };
+    let eta = if rate > 0.0 {
+        let seconds = (total_rows.saturating_sub(rows_done) as f64 / rate).round() as u64;
+        format_duration(seconds, DurationFormat::Hms)
+    } else {
+        "unknown".to_string()
+    };
+
+    format!("{} of {} rows generated ({:.1}%), {:.0} rows/s, ETA {}", rows_done, total_rows,
+            percent, rate, eta)
+}
+
+/// Counts the rows generated by every thread, logging a progress report at most once per
+/// interval and once more when the last row is done.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::Progress;
+///
+/// # fn main() {
+/// let progress = Progress::new(100, 5.0);
+/// progress.record(40);
+/// progress.record(60);
+/// assert_eq!(progress.rows_done(), 100);
+/// # }
+/// ```
+pub struct Progress {
+    total_rows: u64,
+    rows_done: AtomicU64,
+    start: f64,
+    interval_ms: u64,
+    /// Milliseconds from the start to the last report
+    last_report_ms: AtomicU64
+}
+
+impl Progress {
+    pub fn new(total_rows: u64, interval: f64) -> Progress {
+        Progress {
+            total_rows: total_rows,
+            rows_done: AtomicU64::new(0),
+            start: time::precise_time_s(),
+            interval_ms: (interval * 1000.0) as u64,
+            last_report_ms: AtomicU64::new(0)
+        }
+    }
+
+    /// Adds a finished batch of rows, reporting progress when the interval has passed
+    pub fn record(&self, rows: u64) {
+        let done = self.rows_done.fetch_add(rows, Ordering::SeqCst) + rows;
+        let elapsed = time::precise_time_s() - self.start;
+        let elapsed_ms = (elapsed * 1000.0) as u64;
+
+        let last = self.last_report_ms.load(Ordering::SeqCst);
+        let due = done >= self.total_rows || elapsed_ms.saturating_sub(last) >= self.interval_ms;
+        // Only the thread that moves the last report time on logs it
+        if due && self.last_report_ms.compare_exchange(last, elapsed_ms, Ordering::SeqCst,
+                                                       Ordering::SeqCst).is_ok() {
+            info!("{}", progress_report(done, self.total_rows, elapsed));
+        }
+    }
+
+    /// How many rows have been recorded so far
+    pub fn rows_done(&self) -> u64 {
+        self.rows_done.load(Ordering::SeqCst)
+    }
+}
+
+/// Claims up to `wanted` rows from the shared pool of remaining rows, returning how many
+/// were claimed, which is 0 once every row has been handed out.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use std::sync::atomic::AtomicU64;
+/// use fourree::util::{claim_rows, jittered_batch_size};
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// let remaining = AtomicU64::new(1003);
+/// let mut sizes = Vec::new();
+/// loop {
+///     let claimed = claim_rows(&remaining, jittered_batch_size(&mut rng, 50, 20.0));
+///     if claimed == 0 {
+///         break;
+///     }
+///     sizes.push(claimed);
+/// }
+///
+/// // Every row is claimed exactly once, in batches of 40 to 60 rows (bar the last)
+/// assert_eq!(sizes.iter().sum::<u64>(), 1003);
+/// assert!(sizes[..sizes.len() - 1].iter().all(|&s| s >= 40 && s <= 60));
+/// assert!(sizes.iter().any(|&s| s != sizes[0]));
+/// # }
+/// ```
+pub fn claim_rows(remaining: &AtomicU64, wanted: u64) -> u64 {
+    let mut current = remaining.load(Ordering::SeqCst);
+    while current > 0 {
+        let claimed = wanted.min(current);
+        match remaining.compare_exchange(current, current - claimed, Ordering::SeqCst,
+                                         Ordering::SeqCst) {
+            Ok(_) => return claimed,
+            Err(actual) => current = actual
+        }
+    }
+    0
+}
+
+/// Picks a batch size within ±`jitter_pct` percent of `batch_size`, and at least one row.
+pub fn jittered_batch_size<R: rand::Rng>(rng: &mut R, batch_size: u64, jitter_pct: f64) -> u64 {
+    if jitter_pct <= 0.0 {
+        return batch_size;
+    }
+
+    let spread = (batch_size as f64 * jitter_pct / 100.0).round() as u64;
+    let low = batch_size.saturating_sub(spread).max(1);
+    let high = batch_size + spread;
+    Range::new(low, high + 1).ind_sample(rng)
+}
+
+/// Seed streams for the random numbers a seeded run draws outside of its workers
+const BATCH_PLAN_STREAM: usize = 1;
+const SAMPLE_STREAM: usize = 2;
+/// Seed stream from which each batch of a seeded run draws its own generator
+const BATCH_STREAM: usize = 3;
+
+/// Returns the random number generator for worker thread `index`.  With a seed it is a
+/// `StdRng` seeded from `seed + index`, so the same seed reproduces the same values;
+/// otherwise it is seeded randomly.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use rand::Rng;
+/// use fourree::util::worker_rng;
+///
+/// # fn main() {
+/// let a: Vec<u32> = worker_rng(Some(42), 0).gen_iter().take(5).collect();
+/// let b: Vec<u32> = worker_rng(Some(42), 0).gen_iter().take(5).collect();
+/// let c: Vec<u32> = worker_rng(Some(42), 1).gen_iter().take(5).collect();
+/// assert_eq!(a, b);
+/// assert!(a != c);
+/// # }
+/// ```
+pub fn worker_rng(seed: Option<u64>, index: u64) -> StdRng {
+    match seed {
+        Some(s) => SeedableRng::from_seed(&[s.wrapping_add(index) as usize][..]),
+        None => random_rng()
+    }
+}
+
+/// Returns a random number generator for one of the seed's other streams, which never
+/// coincides with a worker's
+fn stream_rng(seed: Option<u64>, stream: usize) -> StdRng {
+    match seed {
+        Some(s) => SeedableRng::from_seed(&[s as usize, stream][..]),
+        None => random_rng()
+    }
+}
+
+/// Returns the random number generator for batch `index` of a seeded run, which is the same
+/// whichever thread generates the batch
+fn batch_rng(seed: u64, index: u64) -> StdRng {
+    SeedableRng::from_seed(&[seed as usize, BATCH_STREAM, index as usize][..])
+}
+
+/// Returns a `StdRng` seeded from the thread's own generator
+fn random_rng() -> StdRng {
+    let key: Vec<usize> = rand::thread_rng().gen_iter().take(8).collect();
+    SeedableRng::from_seed(&key[..])
+}
+
+/// Minimum fractional throughput gain each added thread must bring for `--threads auto`
+/// to keep scaling up.
+pub const AUTO_THREADS_MIN_GAIN: f64 = 0.1;
+
+/// Batches per thread timed before each `--threads auto` decision.
+const AUTO_THREADS_SAMPLE_BATCHES: u64 = 4;
+
+/// How `--threads auto` should change the number of threads after a measurement
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ThreadDecision {
+    ScaleUp,
+    BackOff,
+    Settle
+}
+
+/// Decides the next step for `--threads auto`, given the throughput (rows per second)
+/// measured with one thread fewer, if any, and with the current number of threads.
+/// Threads are added while each one improves throughput by AUTO_THREADS_MIN_GAIN; once
+/// throughput plateaus or drops, the last thread added is removed and the count settles.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// use fourree::util::{auto_thread_decision, ThreadDecision};
+///
+/// # fn main() {
+/// // Throughput from 1 to 4 threads: 100, 190, 270, 275 rows/s
+/// assert_eq!(auto_thread_decision(None, 100.0, 1, 8), ThreadDecision::ScaleUp);
+/// assert_eq!(auto_thread_decision(Some(100.0), 190.0, 2, 8), ThreadDecision::ScaleUp);
+/// assert_eq!(auto_thread_decision(Some(190.0), 270.0, 3, 8), ThreadDecision::ScaleUp);
+/// assert_eq!(auto_thread_decision(Some(270.0), 275.0, 4, 8), ThreadDecision::BackOff);
+///
+/// // Never past the maximum, and never below one thread
+/// assert_eq!(auto_thread_decision(Some(100.0), 200.0, 8, 8), ThreadDecision::Settle);
+/// assert_eq!(auto_thread_decision(None, 100.0, 1, 1), ThreadDecision::Settle);
+/// # }
+/// ```
+pub fn auto_thread_decision(previous: Option<f64>, current: f64, threads: u64, max_threads: u64)
+        -> ThreadDecision {
+    let improved = match previous {
+        Some(p) => current >= p * (1.0 + AUTO_THREADS_MIN_GAIN),
+        None => true
+    };
+
+    if !improved && threads > 1 {
+        ThreadDecision::BackOff
+    } else if improved && threads < max_threads {
+        ThreadDecision::ScaleUp
+    } else {
+        ThreadDecision::Settle
+    }
 }
 
 /// Generate data from a schema
-pub fn generate_data(config: &Config, schema: Schema) -> Result<(), String> {
-    // Define output_thread out of scope, so it will live beyond the data generation threads
-    // and the output_channel.
-    let output_thread;
-    {
-        let (output_channel, ot) = initialize_output_thread(config)?;
-        output_thread = ot;
-
-        let num_batches = config.num_rows / config.batch_size;
-        let batch_size = config.batch_size;
-        let batches_per_thread = num_batches / config.num_threads;
-
-        if config.display_header {
-            output_channel.send(schema.generate_header()).unwrap();
-        }
-
-        if config.num_threads > 1 {
-            // Prepare for multithreading
-            let mut handles = Vec::with_capacity(config.num_threads as usize);
-            let schema_ref = Arc::new(schema);
-
-            // Generate config.num_thread threads
-            for _ in 0..config.num_threads {
-                let thread_schema = schema_ref.clone();
-                let thread_channel = output_channel.clone();
-                handles.push(thread::spawn(move || {
-                    let mut rng = rand::thread_rng();
-
-                    // Use caluclated number of batches to run per thread
-                    for _ in 0..batches_per_thread {
-                        generate_batch(&thread_schema, batch_size, &thread_channel, &mut rng);
-                    }
-                }));
-            }
+///
+/// When multiple threads are used, the batches are generated in parallel on a rayon
+/// thread pool, whose threads steal work from each other so that faster threads naturally
+/// pick up more of it.  Batches are written in whatever order they finish, and while the
+/// rows of different batches may interleave, each row (or INSERT statement) is always
+/// written whole.  With a seed, every batch gets its own rng, seeded from the seed and
+/// the batch's index, and the batches are written in index order, so the same seed gives
+/// the same output whatever the number of threads.
+///
+/// `config.num_rows` always refers to data rows; the header is written in addition to them.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use std::fs::File;
+/// use std::io::Read;
+/// use rand::Rng;
+///
+/// use fourree::config::{Config, OutputMode};
+/// use fourree::json::parse_json;
+/// use fourree::schema::{Field, FieldGenerator, TypedValue};
+/// use fourree::util::generate_data;
+///
+/// # fn main() {
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+/// ]}"#).unwrap();
+///
+/// let path = std::env::temp_dir().join("fourree-generate-data-threads.txt");
+/// let config = Config {
+///     num_rows: 100,
+///     batch_size: 5,
+///     num_threads: 4,
+///     output_mode: OutputMode::File,
+///     output_file: Some(path.to_str().unwrap().to_string()),
+///     ..Default::default()
+/// };
+/// generate_data(&config, schema).unwrap();
+///
+/// let mut contents = String::new();
+/// File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+/// assert_eq!(contents.lines().count(), 100);
+///
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+/// ]}"#).unwrap();
+/// let config = Config { display_header: true, ..config };
+/// generate_data(&config, schema).unwrap();
+///
+/// let mut contents = String::new();
+/// File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+/// assert_eq!(contents.lines().next(), Some("id"));
+/// assert_eq!(contents.lines().count(), 101);
+///
+/// // More threads than batches still produces every row
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+/// ]}"#).unwrap();
+/// let config = Config { num_rows: 2, batch_size: 1, num_threads: 8, display_header: false, ..config };
+/// generate_data(&config, schema).unwrap();
+///
+/// let mut contents = String::new();
+/// File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+/// assert_eq!(contents.lines().count(), 2);
+///
+/// // Batches that don't divide evenly between the threads, and rows that don't fill a
+/// // whole number of batches, still produce exactly num_rows
+/// for &(num_rows, batch_size, num_threads) in [(1000, 1, 3), (10, 3, 2), (1003, 10, 4)].iter() {
+///     for &seed in [None, Some(7)].iter() {
+///         let schema = parse_json(r#"{"table_name": "t", "fields": [
+///             {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+///         ]}"#).unwrap();
+///         let config = Config {
+///             num_rows: num_rows,
+///             batch_size: batch_size,
+///             num_threads: num_threads,
+///             seed: seed,
+///             output_mode: OutputMode::File,
+///             output_file: Some(path.to_str().unwrap().to_string()),
+///             ..Default::default()
+///         };
+///         generate_data(&config, schema).unwrap();
+///
+///         let mut contents = String::new();
+///         File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+///         assert_eq!(contents.lines().count() as u64, num_rows);
+///     }
+/// }
+///
+/// // Auto threads scale between one and num_threads, still producing every row
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+/// ]}"#).unwrap();
+/// let config = Config { num_rows: 1000, batch_size: 10, num_threads: 4, auto_threads: true, ..config };
+/// generate_data(&config, schema).unwrap();
+///
+/// let mut contents = String::new();
+/// File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+/// assert_eq!(contents.lines().count(), 1000);
+///
+/// // Jittered batches still add up to exactly num_rows, even when it is not a whole
+/// // number of batches
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+/// ]}"#).unwrap();
+/// let config = Config { num_rows: 1003, auto_threads: false, batch_jitter: 50.0, ..config };
+/// generate_data(&config, schema).unwrap();
+///
+/// let mut contents = String::new();
+/// File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+/// assert_eq!(contents.lines().count(), 1003);
+///
+/// // The same seed reproduces the same rows, in the same order
+/// let seeded = |seed, num_threads| {
+///     let schema = parse_json(r#"{"table_name": "t", "fields": [
+///         {"name": "id", "data_type": "integer", "generator": "integer", "min": 0, "max": 100}
+///     ]}"#).unwrap();
+///     let config = Config {
+///         num_rows: 1003,
+///         batch_size: 10,
+///         batch_jitter: 50.0,
+///         num_threads: num_threads,
+///         seed: Some(seed),
+///         output_mode: OutputMode::File,
+///         output_file: Some(path.to_str().unwrap().to_string()),
+///         ..Default::default()
+///     };
+///     generate_data(&config, schema).unwrap();
+///     let mut contents = String::new();
+///     File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+///     contents
+/// };
+/// assert_eq!(seeded(7, 4), seeded(7, 4));
+/// assert!(seeded(7, 4) != seeded(8, 4));
+///
+/// // ...and whatever the number of threads
+/// assert_eq!(seeded(7, 1), seeded(7, 4));
+/// assert_eq!(seeded(7, 3), seeded(7, 8));
+///
+/// // A line terminator replaces the record separator after the header and every row,
+/// // fixed width rows included
+/// let schema = parse_json(r#"{"table_name": "t", "fields": [
+///     {"name": "id", "data_type": "integer", "generator": "integer", "min": 7, "max": 7,
+///      "length": 2, "padding": "0"}
+/// ]}"#).unwrap();
+/// let config = Config {
+///     num_rows: 2,
+///     display_header: true,
+///     line_terminator: Some("\r\n".to_string()),
+///     output_mode: OutputMode::File,
+///     output_file: Some(path.to_str().unwrap().to_string()),
+///     ..Default::default()
+/// };
+/// generate_data(&config, schema).unwrap();
+///
+/// let mut contents = String::new();
+/// File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+/// assert_eq!(contents, "id\r\n07\r\n07\r\n");
+///
+/// // A panicking generator fails the run with its message, on one thread or several
+/// for &num_threads in [1, 4].iter() {
+///     let mut schema = parse_json(r#"{"table_name": "t", "fields": []}"#).unwrap();
+///     schema.add_field(Field::new("bug", "integer", FieldGenerator::Custom(Box::new(
+///         |_: &mut dyn Rng| -> TypedValue { panic!("generator bug") }
+///     ))));
+///     let config = Config {
+///         num_rows: 100,
+///         batch_size: 10,
+///         num_threads: num_threads,
+///         output_mode: OutputMode::File,
+///         output_file: Some(path.to_str().unwrap().to_string()),
+///         ..Default::default()
+///     };
+///     assert_eq!(generate_data(&config, schema).err().unwrap(), "generator bug");
+/// }
+/// # }
+/// ```
+pub fn generate_data(config: &Config, mut schema: Schema) -> Result<(), String> {
+    schema.unique_max_retries = config.unique_max_retries;
+    if config.output_format != OutputFormat::Delimited {
+        schema.record_separator = "\n".to_string();
+    }
+    if let Some(ref terminator) = config.line_terminator {
+        schema.record_separator = terminator.clone();
+    }
+    schema.prepare()?;
+
+    let completed_file = match config.output_mode {
+        // Each partition or split file is handed to the hook by the output thread
+        OutputMode::File | OutputMode::SqlFile if config.on_complete.is_some() &&
+                config.partition_by.is_none() && !splits_files(config) => {
+            Some(output_file_path(config, &schema)?)
+        },
+        _ => None
+    };
+
+    let generation_failed = Arc::new(AtomicBool::new(false));
+    let (output_channel, output_thread) =
+        initialize_output_thread(config, &schema, generation_failed.clone())?;
+
+    // A panicking generator fails the run like any other error.  The channel stays open
+    // until the failure is flagged, so the output thread can't mistake it for the end.
+    let generated = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        generate_to_channel(config, schema, output_channel.clone())
+    })).unwrap_or_else(|e| Err(panic_message(e)));
+    if generated.is_err() {
+        generation_failed.store(true, Ordering::SeqCst);
+    }
+    drop(output_channel);
+
+    // Now wait for output thread to complete.  When it fails the generators only see a
+    // closed channel, so its error is the one worth reporting.
+    output_thread.join().map_err(panic_message)??;
+    info!("Output thread completed.");
+    generated?;
+
+    match (config.on_complete.as_ref(), completed_file) {
+        (Some(command), Some(path)) => run_on_complete(command, &path),
+        _ => Ok(())
+    }
+}
 
-            // Wait for generator threads to complete
-            for handle in handles {
-                match handle.join() {
-                    Ok(_) => info!("Thread completed."),
-                    Err(e) => return Err(format!("{:#?}", e))
-                };
+/// Generates every row of the schema and sends them down the output channel, which is
+/// dropped on return so that the output thread can finish.
+fn generate_to_channel(config: &Config, schema: Schema, output_channel: SyncSender<String>)
+        -> Result<(), String> {
+    let num_batches = batch_count(config.num_rows, config.batch_size);
+    let num_threads = effective_thread_count(config.num_threads, num_batches);
+    let batch_size = config.batch_size;
+    let batch_jitter = config.batch_jitter;
+    // The last batch takes whatever rows are left over
+    let total_rows = config.num_rows;
+    let deduper = if config.dedupe_rows {
+        Some(Arc::new(RowDeduper::new(config.unique_max_retries)))
+    } else {
+        None
+    };
+    let dialect = if config.output_mode == OutputMode::SqlFile {
+        Some(config.dialect)
+    } else {
+        None
+    };
+    let format = config.output_format;
+    let progress = if config.progress {
+        Some(Arc::new(Progress::new(total_rows, PROGRESS_INTERVAL_SECONDS)))
+    } else {
+        None
+    };
+
+    if let Some(seed) = config.seed {
+        generate_in_order(config, schema, &output_channel, seed, num_threads, total_rows, deduper,
+                          dialect, progress)?;
+    } else if num_threads > 1 && config.auto_threads {
+        generate_auto_threads(config, schema, &output_channel, num_threads, total_rows, deduper,
+                              dialect, progress)?;
+    } else if num_threads > 1 {
+        let batches = batch_sizes(&mut random_rng(), total_rows, batch_size, batch_jitter);
+        let pool = ThreadPoolBuilder::new().num_threads(num_threads as usize).build()
+            .map_err(|e| e.to_string())?;
+        let deduper = deduper.as_ref().map(|d| &**d);
+        let progress = progress.as_ref().map(|p| &**p);
+
+        // Each batch goes to the output as soon as it is done, in whatever order the
+        // threads finish them
+        pool.install(|| {
+            batches.par_iter().try_for_each_init(random_rng, |rng, &rows| -> Result<(), String> {
+                generate_batch(&schema, rows, &output_channel, rng, deduper, dialect, format)?;
+                if let Some(p) = progress {
+                    p.record(rows);
+                }
+                Ok(())
+            })
+        })?;
+    } else {
+        let mut rng = random_rng();
+        let mut rows_remaining = total_rows;
+
+        while rows_remaining > 0 {
+            let rows = jittered_batch_size(&mut rng, batch_size, batch_jitter)
+                .min(rows_remaining);
+            generate_batch(&schema, rows, &output_channel, &mut rng,
+                           deduper.as_ref().map(|d| &**d), dialect, format)?;
+            if let Some(ref p) = progress {
+                p.record(rows);
             }
+            rows_remaining -= rows;
+        }
+    }
+    // output_channel goes out of scope here, thus causing the output thread to terminate
+    Ok(())
+}
 
-            // output_channel goes out of scope here, thus causing the output thread to terminate
-        } else {
-            let mut rng = rand::thread_rng();
+/// Splits `total_rows` into batches of `batch_size` rows, each jittered by up to
+/// `batch_jitter` percent, with the last batch taking whatever is left.
+///
+/// # Examples
+/// ```
+/// # extern crate fourree;
+/// # extern crate rand;
+/// use fourree::util::batch_sizes;
+///
+/// # fn main() {
+/// let mut rng = rand::thread_rng();
+/// assert_eq!(batch_sizes(&mut rng, 10, 4, 0.0), vec![4, 4, 2]);
+///
+/// let sizes = batch_sizes(&mut rng, 1003, 50, 20.0);
+/// assert_eq!(sizes.iter().sum::<u64>(), 1003);
+/// assert!(sizes[..sizes.len() - 1].iter().all(|&s| s >= 40 && s <= 60));
+/// # }
+/// ```
+pub fn batch_sizes<R: rand::Rng>(rng: &mut R, total_rows: u64, batch_size: u64,
+                                 batch_jitter: f64) -> Vec<u64> {
+    let mut sizes = Vec::new();
+    let mut rows_remaining = total_rows;
+    while rows_remaining > 0 {
+        let rows = jittered_batch_size(rng, batch_size, batch_jitter).min(rows_remaining);
+        sizes.push(rows);
+        rows_remaining -= rows;
+    }
+    sizes
+}
+
+/// Generates an unseeded run for `--threads auto`, starting with one worker thread and
+/// adding more while each improves throughput.  Workers pull batches from a shared counter
+/// until every row has been claimed.
+fn generate_auto_threads(config: &Config, schema: Schema, output_channel: &SyncSender<String>,
+                         num_threads: u64, total_rows: u64,
+                         deduper: Option<Arc<RowDeduper>>, dialect: Option<SqlDialect>,
+                         progress: Option<Arc<Progress>>) -> Result<(), String> {
+    let (batch_size, batch_jitter, format) =
+        (config.batch_size, config.batch_jitter, config.output_format);
+    let schema_ref = Arc::new(schema);
+    let rows_remaining = Arc::new(AtomicU64::new(total_rows));
+    let rows_done = Arc::new(AtomicU64::new(0));
+    // Threads numbered at or above the limit stop once their current batch is done
+    let thread_limit = Arc::new(AtomicU64::new(1));
+
+    let spawn_worker = |index: u64| -> JoinHandle<Result<(), String>> {
+        let thread_schema = schema_ref.clone();
+        let thread_channel = output_channel.clone();
+        let thread_remaining = rows_remaining.clone();
+        let thread_done = rows_done.clone();
+        let thread_limit = thread_limit.clone();
+        let thread_deduper = deduper.clone();
+        let thread_progress = progress.clone();
+        thread::spawn(move || {
+            let mut rng = worker_rng(None, index);
 
-            for _ in 0..num_batches {
-                generate_batch(&schema, config.batch_size, &output_channel, &mut rng);
+            // Keep pulling batches until none are left
+            while index < thread_limit.load(Ordering::SeqCst) {
+                let wanted = jittered_batch_size(&mut rng, batch_size, batch_jitter);
+                let rows = claim_rows(&thread_remaining, wanted);
+                if rows == 0 {
+                    break;
+                }
+                generate_batch(&thread_schema, rows, &thread_channel, &mut rng,
+                               thread_deduper.as_ref().map(|d| &**d), dialect, format)?;
+                thread_done.fetch_add(rows, Ordering::SeqCst);
+                if let Some(ref p) = thread_progress {
+                    p.record(rows);
+                }
             }
+            Ok(())
+        })
+    };
+
+    let mut handles = vec![spawn_worker(0)];
+    let mut threads = 1;
+    let mut previous = None;
+
+    loop {
+        // Time a few batches per thread, unless the work runs out first
+        let start_done = rows_done.load(Ordering::SeqCst);
+        let target = start_done + AUTO_THREADS_SAMPLE_BATCHES * threads * batch_size;
+        let start = time::precise_time_s();
+        let running = |handles: &Vec<JoinHandle<Result<(), String>>>| {
+            rows_remaining.load(Ordering::SeqCst) > 0 &&
+                handles.iter().any(|h| !h.is_finished())
+        };
+        while rows_done.load(Ordering::SeqCst) < target && running(&handles) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        if !running(&handles) {
+            break;
+        }
+
+        let elapsed = time::precise_time_s() - start;
+        let rows = rows_done.load(Ordering::SeqCst) - start_done;
+        let throughput = rows as f64 / elapsed;
+
+        match auto_thread_decision(previous, throughput, threads, num_threads) {
+            ThreadDecision::ScaleUp => {
+                thread_limit.store(threads + 1, Ordering::SeqCst);
+                handles.push(spawn_worker(threads));
+                threads += 1;
+            },
+            ThreadDecision::BackOff => {
+                threads -= 1;
+                thread_limit.store(threads, Ordering::SeqCst);
+                break;
+            },
+            ThreadDecision::Settle => break
         }
+        previous = Some(throughput);
+    }
+    info!("Auto threads chose {} threads", threads);
+
+    // Wait for generator threads to complete
+    for handle in handles {
+        match handle.join() {
+            Ok(Ok(_)) => info!("Thread completed."),
+            Ok(Err(e)) => return Err(e),
+            Err(e) => return Err(panic_message(e))
+        };
     }
+    Ok(())
+}
+
+/// What a worker of a seeded run sends to be forwarded in order: each chunk of a batch's
+/// rows, then the end of that batch.  Kept apart from the data, like `S3Message`, so the
+/// end of a batch never depends on what a chunk holds.
+enum BatchMessage {
+    Chunk(String),
+    EndOfBatch(u64)
+}
 
-    // Now wait for output thread to complete
-    match output_thread.join() {
-        Ok(_) => {
-            info!("Output thread completed.");
+impl From<String> for BatchMessage {
+    fn from(chunk: String) -> BatchMessage {
+        BatchMessage::Chunk(chunk)
+    }
+}
+
+/// Generates a seeded run reproducibly, with the same output whatever the number of threads.
+/// Every worker replays the same seeded sequence of batch sizes and generates batches
+/// `index`, `index + num_threads`, ..., each with an rng seeded from the seed and the
+/// batch's index, and the batches are forwarded to the output in order.  With several
+/// threads, values shared between them, such as sequences, unique fields and
+/// --dedupe_rows, still depend on timing.
+fn generate_in_order(config: &Config, schema: Schema, output_channel: &SyncSender<String>,
+                     seed: u64, num_threads: u64, total_rows: u64,
+                     deduper: Option<Arc<RowDeduper>>, dialect: Option<SqlDialect>,
+                     progress: Option<Arc<Progress>>) -> Result<(), String> {
+    let schema_ref = Arc::new(schema);
+    let mut receivers = Vec::with_capacity(num_threads as usize);
+    let mut handles: Vec<JoinHandle<Result<(), String>>> = Vec::new();
+
+    for index in 0..num_threads {
+        let (sender, receiver) = sync_channel(config.channel_capacity);
+        receivers.push(receiver);
+
+        let thread_schema = schema_ref.clone();
+        let thread_deduper = deduper.clone();
+        let thread_progress = progress.clone();
+        let (batch_size, batch_jitter, format) =
+            (config.batch_size, config.batch_jitter, config.output_format);
+        handles.push(thread::spawn(move || {
+            let mut plan = stream_rng(Some(seed), BATCH_PLAN_STREAM);
+            let mut rows_remaining = total_rows;
+            let mut batch = 0;
+
+            while rows_remaining > 0 {
+                let rows = jittered_batch_size(&mut plan, batch_size, batch_jitter)
+                    .min(rows_remaining);
+                rows_remaining -= rows;
+                if batch % num_threads == index {
+                    let mut rng = batch_rng(seed, batch);
+                    generate_batch(&thread_schema, rows, &sender, &mut rng,
+                                   thread_deduper.as_ref().map(|d| &**d), dialect, format)?;
+                    if let Some(ref p) = thread_progress {
+                        p.record(rows);
+                    }
+                    sender.send(BatchMessage::EndOfBatch(batch)).map_err(|e| e.to_string())?;
+                }
+                batch += 1;
+            }
             Ok(())
-        },
-        Err(e) => Err(format!("{:#?}", e))
+        }));
+    }
+
+    // A worker's channel closes once it has no batches left, or has failed
+    let mut batch = 0;
+    'batches: loop {
+        let receiver = &receivers[(batch % num_threads) as usize];
+        loop {
+            match receiver.recv() {
+                Ok(BatchMessage::Chunk(chunk)) => {
+                    output_channel.send(chunk).map_err(|e| e.to_string())?
+                },
+                Ok(BatchMessage::EndOfBatch(end)) if end == batch => break,
+                Ok(BatchMessage::EndOfBatch(end)) => {
+                    return Err(format!("Batch {} ended while waiting for batch {}.", end, batch))
+                },
+                Err(_) => break 'batches
+            }
+        }
+        batch += 1;
+    }
+    // Unblock any worker still sending, should another have failed
+    drop(receivers);
+
+    for handle in handles {
+        match handle.join() {
+            Ok(Ok(_)) => info!("Thread completed."),
+            Ok(Err(e)) => return Err(e),
+            Err(e) => return Err(panic_message(e))
+        };
+    }
+    Ok(())
+}
+
+/// Extracts the message from a thread's panic, e.g. one raised by a failing output thread
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "A thread panicked.".to_string()
+        }
     }
 }